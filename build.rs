@@ -1,4 +1,6 @@
 fn main() {
+    println!("cargo:rerun-if-changed=src/vm.h");
+    println!("cargo:rerun-if-changed=src/vm.c");
     cc::Build::new()
         .file("src/vm.h")
         .file("src/vm.c")
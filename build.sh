@@ -1,3 +1,3 @@
 rm -r target/debug/build
 rm src/vm.o
-cargo run
\ No newline at end of file
+cargo run run bin.svm runtime.svm
\ No newline at end of file
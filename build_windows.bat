@@ -1,3 +1,3 @@
 rd /s /q ".\target\debug\build"
 rm "src\vm.obj"
-cargo run
\ No newline at end of file
+cargo run run bin.svm runtime.svm
\ No newline at end of file
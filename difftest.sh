@@ -0,0 +1,27 @@
+#!/bin/sh
+# Run modules on both runtime systems, vm.c and the Rust interpreter,
+# and check that they print the same things and exit with the same status.
+# The input, if any, is read from a file given before the modules. For example:
+#     ./difftest.sh bin.svm runtime.svm
+#     ./difftest.sh --input in.txt echo.svm
+set -u
+input=/dev/null
+if [ "${1:-}" = "--input" ]; then
+    input=$2
+    shift 2
+fi
+cargo build -q || exit 1
+dir=$(mktemp -d)
+trap 'rm -r "$dir"' EXIT
+for backend in c rust; do
+    target/debug/sabervm run --backend $backend "$@" < "$input" > "$dir/out.$backend" 2> "$dir/err.$backend"
+    echo $? > "$dir/status.$backend"
+done
+same=0
+for file in out err status; do
+    if ! diff -u --label "$file (c)" --label "$file (rust)" "$dir/$file.c" "$dir/$file.rust"; then
+        same=1
+    fi
+done
+[ $same = 0 ] && echo "same output and exit status ($(cat "$dir/status.c"))"
+exit $same
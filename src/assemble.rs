@@ -0,0 +1,583 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// The textual assembly language for SaberVM.
+//
+// The mnemonics are exactly the ones printed by `Pretty for Op1`,
+// separated by newlines or semicolons, with `#` starting a comment:
+//
+//     data_section {
+//         "Hello, world!\n" 0x00
+//     }
+//     rgn; i32; ctget 1; handle; func 2; end; export "sultt_reprin"
+//     func 0; lced
+//     new_rgn 4096; global_func 1; call
+//     ...
+//
+// The magic number and format version aren't written out; the assembler adds them.
+// A `loc "file" line column` annotation gives the source location of the op after it,
+// and goes into the module's debug section.
+// A `name "T"` annotation names the type variable or region that the op after it introduces
+// (an `all`, `some`, `rgn`, or `new_rgn`), and `func_name 0 "main"` names the function with label 0.
+// Both go into the module's names section, which error messages and the IR dump print names from.
+// The optional `data_section` block must come before any op and holds string literals
+// (with `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\xNN` escapes) and single bytes.
+// The function count that `parse::lex` expects is the number of `lced`, `export`, and `import` ops,
+// since those can only appear as the terminators of forward declarations.
+// The 128-bit IDs of `export` and `import` are written as string literals of at most 16 bytes,
+// which are padded with zero bytes on the left.
+// Float literals are written as decimals, `inf`, or `NaN`, or as their bits in hexadecimal,
+// which is how the disassembler prints NaNs other than the canonical one.
+
+use crate::header::*;
+use crate::pretty::escape_bytes;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Str(Vec<u8>),
+    Open,
+    Close,
+    Sep,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => w.clone(),
+            Token::Str(bytes) => "\"".to_string() + &escape_bytes(bytes) + "\"",
+            Token::Open => "{".to_string(),
+            Token::Close => "}".to_string(),
+            Token::Sep => ";".to_string(),
+        }
+    }
+}
+
+/// Split assembly text into tokens, each tagged with its (1-based) line number.
+fn tokenize(src: &str) -> Result<Vec<(u32, Token)>, Error> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                tokens.push((line, Token::Sep));
+                line += 1;
+            }
+            ';' => tokens.push((line, Token::Sep)),
+            '{' => tokens.push((line, Token::Open)),
+            '}' => tokens.push((line, Token::Close)),
+            '#' => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '"' => {
+                let mut bytes = vec![];
+                loop {
+                    match chars.next() {
+                        None | Some('\n') => return Err(Error::AssemblyErrorUnterminatedString(line)),
+                        Some('"') => break,
+                        Some('\\') => {
+                            let escaped = match chars.next() {
+                                Some('n') => b'\n',
+                                Some('t') => b'\t',
+                                Some('r') => b'\r',
+                                Some('0') => 0,
+                                Some('\\') => b'\\',
+                                Some('"') => b'"',
+                                Some('x') => {
+                                    let hex: String = (0..2)
+                                        .map_while(|_| chars.next_if(char::is_ascii_hexdigit))
+                                        .collect();
+                                    if hex.len() != 2 {
+                                        return Err(Error::AssemblyErrorBadEscape(line, "x".to_string() + &hex));
+                                    }
+                                    u8::from_str_radix(&hex, 16).unwrap()
+                                }
+                                Some(c) => return Err(Error::AssemblyErrorBadEscape(line, c.to_string())),
+                                None => return Err(Error::AssemblyErrorUnterminatedString(line)),
+                            };
+                            bytes.push(escaped);
+                        }
+                        Some(c) => {
+                            let mut buf = [0; 4];
+                            bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+                        }
+                    }
+                }
+                tokens.push((line, Token::Str(bytes)));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) =
+                    chars.next_if(|c| !c.is_whitespace() && !matches!(c, ';' | '{' | '}' | '"' | '#'))
+                {
+                    word.push(c);
+                }
+                tokens.push((line, Token::Word(word)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse an integer parameter, written in decimal or as `0x`-prefixed hexadecimal.
+fn number<T: TryFrom<i128>>(line: u32, mnemonic: &str, token: &Token) -> Result<T, Error> {
+    let bad_param = || Error::AssemblyErrorBadParam(line, mnemonic.to_string(), token.describe());
+    let Token::Word(word) = token else {
+        return Err(bad_param());
+    };
+    let n = match word.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => word.parse::<i128>(),
+    };
+    n.ok().and_then(|n| T::try_from(n).ok()).ok_or_else(bad_param)
+}
+
+fn param<T: TryFrom<i128>>(line: u32, mnemonic: &str, params: &[Token]) -> Result<T, Error> {
+    match params.first() {
+        Some(token) => number(line, mnemonic, token),
+        None => Err(Error::AssemblyErrorParamNeeded(line, mnemonic.to_string())),
+    }
+}
+
+/// Parse a float literal, like `1.5`, `-2e10`, `inf`, or `NaN`, into its bits,
+/// or take the bits in `0x`-prefixed hexadecimal, like `0x7fc00001`, for a NaN with a sign or payload.
+fn float_param<T: std::str::FromStr, B: TryFrom<i128>>(
+    line: u32,
+    mnemonic: &str,
+    params: &[Token],
+    to_bits: fn(T) -> B,
+) -> Result<B, Error> {
+    match params.first() {
+        Some(token @ Token::Word(word)) if word.starts_with("0x") => number(line, mnemonic, token),
+        Some(Token::Word(word)) => word
+            .parse()
+            .map(to_bits)
+            .map_err(|_| Error::AssemblyErrorBadParam(line, mnemonic.to_string(), word.clone())),
+        Some(token) => Err(Error::AssemblyErrorBadParam(line, mnemonic.to_string(), token.describe())),
+        None => Err(Error::AssemblyErrorParamNeeded(line, mnemonic.to_string())),
+    }
+}
+
+/// Parse the 128-bit ID of an `export` or `import`.
+fn id_param(line: u32, mnemonic: &str, params: &[Token]) -> Result<(u64, u64), Error> {
+    match params.first() {
+        Some(Token::Str(bytes)) if bytes.len() <= 16 => {
+            let mut id = [0u8; 16];
+            id[16 - bytes.len()..].copy_from_slice(bytes);
+            Ok((
+                u64::from_le_bytes(id[0..8].try_into().unwrap()),
+                u64::from_le_bytes(id[8..16].try_into().unwrap()),
+            ))
+        }
+        Some(token) => Err(Error::AssemblyErrorBadParam(line, mnemonic.to_string(), token.describe())),
+        None => Err(Error::AssemblyErrorParamNeeded(line, mnemonic.to_string())),
+    }
+}
+
+fn parse_op(line: u32, mnemonic: &str, params: &[Token]) -> Result<Op1, Error> {
+    let (op, arity) = match mnemonic {
+        "unique" => (Op1::Unique, 0),
+        "handle" => (Op1::Handle, 0),
+        "i32" => (Op1::I32, 0),
+        "tuple" => (Op1::Tuple(param(line, mnemonic, params)?), 1),
+        "some" => (Op1::Some, 0),
+        "all" => (Op1::All, 0),
+        "rgn" => (Op1::Rgn, 0),
+        "end" => (Op1::End, 0),
+        "app" => (Op1::App, 0),
+        "func" => (Op1::Func(param(line, mnemonic, params)?), 1),
+        "ctget" => (Op1::CTGet(param(line, mnemonic, params)?), 1),
+        "lced" => (Op1::Lced, 0),
+        "unpack" => (Op1::Unpack, 0),
+        "get" => (Op1::Get(param(line, mnemonic, params)?), 1),
+        "init" => (Op1::Init(param(line, mnemonic, params)?), 1),
+        "malloc" => (Op1::Malloc, 0),
+        "proj" => (Op1::Proj(param(line, mnemonic, params)?), 1),
+        "call" => (Op1::Call, 0),
+        "lit" => (Op1::Lit(param(line, mnemonic, params)?), 1),
+        "global_func" => (Op1::GlobalFunc(param(line, mnemonic, params)?), 1),
+        "halt" => (Op1::Halt, 0),
+        "pack" => (Op1::Pack, 0),
+        "size" => (Op1::Size(param(line, mnemonic, params)?), 1),
+        "new_rgn" => (Op1::NewRgn(param(line, mnemonic, params)?), 1),
+        "free_rgn" => (Op1::FreeRgn, 0),
+        "ptr" => (Op1::Ptr, 0),
+        "deref" => (Op1::Deref, 0),
+        "arr" => (Op1::Arr, 0),
+        "arr_mut" => (Op1::ArrMut, 0),
+        "arr_proj" => (Op1::ArrProj, 0),
+        "add" => (Op1::Add, 0),
+        "mul" => (Op1::Mul, 0),
+        "div" => (Op1::Div, 0),
+        "call_nz" => (Op1::CallNZ, 0),
+        "data" => (Op1::Data(param(line, mnemonic, params)?), 1),
+        "data_sec" => (Op1::DataSec, 0),
+        "u8" => (Op1::U8, 0),
+        "copy_n" => (Op1::CopyN, 0),
+        "u8_lit" => (Op1::U8Lit(param(line, mnemonic, params)?), 1),
+        "u8_to_i32" => (Op1::U8ToI32, 0),
+        "import" => {
+            let (a, b) = id_param(line, mnemonic, params)?;
+            (Op1::Import(a, b), 1)
+        }
+        "export" => {
+            let (a, b) = id_param(line, mnemonic, params)?;
+            (Op1::Export(a, b), 1)
+        }
+        "modulo" => (Op1::Modulo, 0),
+        "i32_to_u8" => (Op1::I32ToU8, 0),
+        "read" => (Op1::Read(param(line, mnemonic, params)?), 1),
+        "write" => (Op1::Write(param(line, mnemonic, params)?), 1),
+        "catch" => (Op1::Catch, 0),
+        "checked_add" => (Op1::CheckedAdd, 0),
+        "checked_mul" => (Op1::CheckedMul, 0),
+        "checked_div" => (Op1::CheckedDiv, 0),
+        "saturating_add" => (Op1::SaturatingAdd, 0),
+        "saturating_mul" => (Op1::SaturatingMul, 0),
+        "saturating_div" => (Op1::SaturatingDiv, 0),
+        "sub" => (Op1::Sub, 0),
+        "neg" => (Op1::Neg, 0),
+        "eq" => (Op1::Eq, 0),
+        "lt" => (Op1::Lt, 0),
+        "and" => (Op1::And, 0),
+        "or" => (Op1::Or, 0),
+        "xor" => (Op1::Xor, 0),
+        "shl" => (Op1::Shl, 0),
+        "shr" => (Op1::Shr, 0),
+        "i64" => (Op1::I64, 0),
+        "u64" => (Op1::U64, 0),
+        "i64_lit" => (Op1::I64Lit(param(line, mnemonic, params)?), 1),
+        "u64_lit" => (Op1::U64Lit(param(line, mnemonic, params)?), 1),
+        "cast" => (Op1::Cast, 0),
+        "f32" => (Op1::F32, 0),
+        "f64" => (Op1::F64, 0),
+        "f32_lit" => (Op1::F32Lit(float_param(line, mnemonic, params, f32::to_bits)?), 1),
+        "f64_lit" => (Op1::F64Lit(float_param(line, mnemonic, params, f64::to_bits)?), 1),
+        "variant" => (Op1::Variant(param(line, mnemonic, params)?), 1),
+        "inj" => (Op1::Inj(param(line, mnemonic, params)?), 1),
+        "case" => (Op1::Case(param(line, mnemonic, params)?), 1),
+        "switch" => (Op1::Switch(param(line, mnemonic, params)?), 1),
+        _ => return Err(Error::AssemblyErrorUnknownMnemonic(line, mnemonic.to_string())),
+    };
+    match params.get(arity) {
+        Some(token) => Err(Error::AssemblyErrorUnexpectedToken(line, token.describe())),
+        None => Ok(op),
+    }
+}
+
+/// Parse the file, line, and column of a `loc` annotation.
+fn parse_loc(line: u32, params: &[Token]) -> Result<(String, u32, u32), Error> {
+    let bad_param = |token: &Token| Error::AssemblyErrorBadParam(line, "loc".to_string(), token.describe());
+    match params {
+        [token @ Token::Str(file), loc_line, col] => Ok((
+            String::from_utf8(file.clone()).map_err(|_| bad_param(token))?,
+            number(line, "loc", loc_line)?,
+            number(line, "loc", col)?,
+        )),
+        [token, _, _] => Err(bad_param(token)),
+        [_, _, _, token, ..] => Err(Error::AssemblyErrorUnexpectedToken(line, token.describe())),
+        _ => Err(Error::AssemblyErrorParamNeeded(line, "loc".to_string())),
+    }
+}
+
+/// Parse the name at the end of a `name` or `func_name` annotation.
+fn parse_name(line: u32, mnemonic: &str, params: &[Token]) -> Result<String, Error> {
+    let bad_param = |token: &Token| Error::AssemblyErrorBadParam(line, mnemonic.to_string(), token.describe());
+    match params {
+        [token @ Token::Str(name)] => String::from_utf8(name.clone()).map_err(|_| bad_param(token)),
+        [token] => Err(bad_param(token)),
+        [_, token, ..] => Err(Error::AssemblyErrorUnexpectedToken(line, token.describe())),
+        [] => Err(Error::AssemblyErrorParamNeeded(line, mnemonic.to_string())),
+    }
+}
+
+fn parse_data_section(
+    tokens: &mut impl Iterator<Item = (u32, Token)>,
+    line: u32,
+) -> Result<Vec<u8>, Error> {
+    match tokens.next() {
+        Some((_, Token::Open)) => {}
+        Some((line, token)) => return Err(Error::AssemblyErrorUnexpectedToken(line, token.describe())),
+        None => return Err(Error::AssemblyErrorUnexpectedEOF(line)),
+    }
+    let mut data_section = vec![];
+    loop {
+        match tokens.next() {
+            Some((_, Token::Close)) => return Ok(data_section),
+            Some((_, Token::Sep)) => {}
+            Some((_, Token::Str(bytes))) => data_section.extend(bytes),
+            Some((line, token)) => data_section.push(number(line, "data_section", &token)?),
+            None => return Err(Error::AssemblyErrorUnexpectedEOF(line)),
+        }
+    }
+}
+
+/// Assemble SaberVM assembly text into the byte layout that `parse::go` expects.
+pub fn go(src: &str) -> Result<ByteStream, Error> {
+    let mut tokens = tokenize(src)?.into_iter().peekable();
+    let mut data_section = None;
+    let mut debug_info = None;
+    let mut names = None;
+    let mut ops = vec![];
+    while let Some((line, token)) = tokens.next() {
+        match token {
+            Token::Sep => {}
+            Token::Word(w) if w == "data_section" => {
+                if data_section.is_some() || !ops.is_empty() {
+                    return Err(Error::AssemblyErrorUnexpectedToken(line, w));
+                }
+                data_section = Some(parse_data_section(&mut tokens, line)?);
+            }
+            Token::Word(mnemonic) => {
+                let mut params = vec![];
+                while let Some((_, token)) = tokens.next_if(|(_, t)| *t != Token::Sep) {
+                    params.push(token);
+                }
+                if mnemonic == "loc" {
+                    let (file, loc_line, col) = parse_loc(line, &params)?;
+                    let debug_info: &mut DebugInfo = debug_info.get_or_insert_default();
+                    let file = match debug_info.files.iter().position(|f| *f == file) {
+                        Some(i) => i,
+                        None => {
+                            debug_info.files.push(file);
+                            debug_info.files.len() - 1
+                        }
+                    };
+                    let loc = SourceLoc { file: file as u32, line: loc_line, col };
+                    debug_info.locations.insert(ops.len() as Pos, loc);
+                } else if mnemonic == "name" {
+                    let name = parse_name(line, &mnemonic, &params)?;
+                    let names: &mut Names = names.get_or_insert_default();
+                    names.vars.insert(ops.len() as Pos, name);
+                } else if mnemonic == "func_name" {
+                    let label = param(line, &mnemonic, &params)?;
+                    let name = parse_name(line, &mnemonic, params.get(1..).unwrap_or_default())?;
+                    let names: &mut Names = names.get_or_insert_default();
+                    names.funcs.insert(label, name);
+                } else {
+                    ops.push(parse_op(line, &mnemonic, &params)?);
+                }
+            }
+            token => return Err(Error::AssemblyErrorUnexpectedToken(line, token.describe())),
+        }
+    }
+    let data_section = data_section.unwrap_or_default();
+    let n = ops
+        .iter()
+        .filter(|op| matches!(op, Op1::Lced | Op1::Export(_, _) | Op1::Import(_, _)))
+        .count() as u32;
+    let op_bytes: Vec<u8> = ops.iter().flat_map(op_to_bytes).collect();
+    Ok(module_bytes(&data_section, debug_info.as_ref(), names.as_ref(), n, &op_bytes))
+}
+
+/// Serialize a module the way `parse::lex` reads it, from its data section,
+/// its debug and names sections if it has them, its function count, and its serialized ops.
+pub fn module_bytes(
+    data_section: &[u8],
+    debug_info: Option<&DebugInfo>,
+    names: Option<&Names>,
+    n: u32,
+    op_bytes: &[u8],
+) -> ByteStream {
+    let debug_section = debug_info.map(debug_section_bytes).unwrap_or_default();
+    let names_section = names.map(names_section_bytes).unwrap_or_default();
+    let mut out = MAGIC.to_vec();
+    out.extend(FORMAT_VERSION.to_le_bytes());
+    out.extend((data_section.len() as u32).to_le_bytes());
+    out.extend(data_section);
+    out.extend((debug_section.len() as u32).to_le_bytes());
+    out.extend(debug_section);
+    out.extend((names_section.len() as u32).to_le_bytes());
+    out.extend(names_section);
+    out.extend(n.to_le_bytes());
+    out.extend(op_bytes);
+    out
+}
+
+fn debug_section_bytes(debug_info: &DebugInfo) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend((debug_info.files.len() as u32).to_le_bytes());
+    for file in &debug_info.files {
+        out.extend((file.len() as u32).to_le_bytes());
+        out.extend(file.as_bytes());
+    }
+    out.extend((debug_info.locations.len() as u32).to_le_bytes());
+    for (pos, loc) in &debug_info.locations {
+        for n in [*pos, loc.file, loc.line, loc.col] {
+            out.extend(n.to_le_bytes());
+        }
+    }
+    out
+}
+
+fn names_section_bytes(names: &Names) -> Vec<u8> {
+    let mut out = vec![];
+    for entries in [&names.funcs, &names.vars] {
+        out.extend((entries.len() as u32).to_le_bytes());
+        for (key, name) in entries {
+            out.extend(key.to_le_bytes());
+            out.extend((name.len() as u32).to_le_bytes());
+            out.extend(name.as_bytes());
+        }
+    }
+    out
+}
+
+/// Serialize an op the way `parse::lex` reads it.
+pub fn op_to_bytes(op: &Op1) -> Vec<u8> {
+    match op {
+        Op1::Unique => vec![0x00],
+        Op1::Handle => vec![0x01],
+        Op1::I32 => vec![0x02],
+        Op1::Tuple(n) => vec![0x03, *n],
+        Op1::Some => vec![0x04],
+        Op1::All => vec![0x05],
+        Op1::Rgn => vec![0x06],
+        Op1::End => vec![0x07],
+        Op1::App => vec![0x08],
+        Op1::Func(n) => vec![0x09, *n],
+        Op1::CTGet(n) => vec![0x0A, *n],
+        Op1::Lced => vec![0x0B],
+        Op1::Unpack => vec![0x0C],
+        Op1::Get(n) => vec![0x0D, *n],
+        Op1::Init(n) => vec![0x0E, *n],
+        Op1::Malloc => vec![0x0F],
+        Op1::Proj(n) => vec![0x10, *n],
+        Op1::Call => vec![0x11],
+        Op1::Lit(lit) => [vec![0x13], lit.to_le_bytes().to_vec()].concat(),
+        Op1::GlobalFunc(label) => [vec![0x14], label.to_le_bytes().to_vec()].concat(),
+        Op1::Halt => vec![0x15],
+        Op1::Pack => vec![0x16],
+        Op1::Size(s) => [vec![0x17], s.to_le_bytes().to_vec()].concat(),
+        Op1::NewRgn(s) => [vec![0x18], s.to_le_bytes().to_vec()].concat(),
+        Op1::FreeRgn => vec![0x19],
+        Op1::Ptr => vec![0x1A],
+        Op1::Deref => vec![0x1B],
+        Op1::Arr => vec![0x1C],
+        Op1::ArrMut => vec![0x1D],
+        Op1::ArrProj => vec![0x1E],
+        Op1::Add => vec![0x1F],
+        Op1::Mul => vec![0x20],
+        Op1::Div => vec![0x21],
+        Op1::CallNZ => vec![0x22],
+        Op1::Data(loc) => [vec![0x23], loc.to_le_bytes().to_vec()].concat(),
+        Op1::DataSec => vec![0x24],
+        Op1::U8 => vec![0x25],
+        Op1::CopyN => vec![0x26],
+        Op1::U8Lit(n) => vec![0x27, *n],
+        Op1::U8ToI32 => vec![0x28],
+        Op1::Import(a, b) => [vec![0x29], a.to_le_bytes().to_vec(), b.to_le_bytes().to_vec()].concat(),
+        Op1::Export(a, b) => [vec![0x2A], a.to_le_bytes().to_vec(), b.to_le_bytes().to_vec()].concat(),
+        Op1::Modulo => vec![0x2B],
+        Op1::I32ToU8 => vec![0x2C],
+        Op1::Read(c) => vec![0x2D, *c],
+        Op1::Write(c) => vec![0x2E, *c],
+        Op1::Catch => vec![0x2F],
+        Op1::CheckedAdd => vec![0x30],
+        Op1::CheckedMul => vec![0x31],
+        Op1::CheckedDiv => vec![0x32],
+        Op1::SaturatingAdd => vec![0x33],
+        Op1::SaturatingMul => vec![0x34],
+        Op1::SaturatingDiv => vec![0x35],
+        Op1::Sub => vec![0x36],
+        Op1::Neg => vec![0x37],
+        Op1::Eq => vec![0x38],
+        Op1::Lt => vec![0x39],
+        Op1::And => vec![0x3A],
+        Op1::Or => vec![0x3B],
+        Op1::Xor => vec![0x3C],
+        Op1::Shl => vec![0x3D],
+        Op1::Shr => vec![0x3E],
+        Op1::I64 => vec![0x3F],
+        Op1::U64 => vec![0x40],
+        Op1::I64Lit(n) => [vec![0x41], n.to_le_bytes().to_vec()].concat(),
+        Op1::U64Lit(n) => [vec![0x42], n.to_le_bytes().to_vec()].concat(),
+        Op1::Cast => vec![0x43],
+        Op1::F32 => vec![0x44],
+        Op1::F64 => vec![0x45],
+        Op1::F32Lit(bits) => [vec![0x46], bits.to_le_bytes().to_vec()].concat(),
+        Op1::F64Lit(bits) => [vec![0x47], bits.to_le_bytes().to_vec()].concat(),
+        Op1::Variant(n) => vec![0x48, *n],
+        Op1::Inj(i) => vec![0x49, *i],
+        Op1::Case(n) => vec![0x4A, *n],
+        Op1::Switch(n) => vec![0x4B, *n],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble;
+
+    /// Assemble the text, then check that disassembling and reassembling it gives the same bytes.
+    fn round_trip(src: &str) -> ByteStream {
+        let bytes = go(src).unwrap();
+        let text = disassemble::go(&bytes).unwrap();
+        assert_eq!(go(&text).unwrap(), bytes, "reassembling\n{}", text);
+        bytes
+    }
+
+    #[test]
+    fn repo_modules_round_trip() {
+        round_trip(include_str!("../bin.sasm"));
+        round_trip(include_str!("../runtime.sasm"));
+        round_trip(include_str!("../tuples.sasm"));
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let bytes = round_trip(
+            "data_section { \"a\\x00\\xFF\\n\\t\\r\\0\\\\\\\"\" 7 }\n\nfunc 0; lced\n\nu8_lit 0; halt\n",
+        );
+        assert_eq!(&bytes[12..22], b"a\x00\xff\n\t\r\0\\\"\x07");
+    }
+
+    #[test]
+    fn float_bits_round_trip() {
+        let lits = [
+            ("f32_lit", "0x7fc00001", 0x7fc0_0001u64, 4),
+            ("f32_lit", "0xffc00000", 0xffc0_0000, 4),
+            ("f32_lit", "-0.0", 0x8000_0000, 4),
+            ("f64_lit", "0x7ff8000000000001", 0x7ff8_0000_0000_0001, 8),
+            ("f64_lit", "0xfff8000000000000", 0xfff8_0000_0000_0000, 8),
+            ("f64_lit", "-0.0", 0x8000_0000_0000_0000, 8),
+        ];
+        for (mnemonic, param, bits, size) in lits {
+            let src = format!("func 0; lced\n\n{} {}\nu8_lit 0; halt\n", mnemonic, param);
+            let bytes = round_trip(&src);
+            let bits = &bits.to_le_bytes()[..size];
+            assert!(bytes.windows(size).any(|w| w == bits), "{}", src);
+            let text = disassemble::go(&bytes).unwrap();
+            assert!(text.contains(&format!("{} {}", mnemonic, param)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn hex_escapes_need_two_hex_digits() {
+        for (escape, found) in [("\\x4\"", "x4"), ("\\x+1\"", "x"), ("\\xg0\"", "x"), ("\\x\"", "x")] {
+            let src = format!("data_section {{ \"{}\" }}\n", escape);
+            assert_eq!(go(&src), Err(Error::AssemblyErrorBadEscape(1, found.to_string())), "{}", src);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("data_section { \"\\q\" }", Error::AssemblyErrorBadEscape(1, "q".to_string())),
+            ("data_section { \"abc }", Error::AssemblyErrorUnterminatedString(1)),
+            ("data_section { \"abc\n\" }", Error::AssemblyErrorUnterminatedString(1)),
+            ("data_section {", Error::AssemblyErrorUnexpectedEOF(1)),
+            ("func 0; lced\nfoo", Error::AssemblyErrorUnknownMnemonic(2, "foo".to_string())),
+            ("lit", Error::AssemblyErrorParamNeeded(1, "lit".to_string())),
+            ("lit x", Error::AssemblyErrorBadParam(1, "lit".to_string(), "x".to_string())),
+        ];
+        for (src, error) in cases {
+            assert_eq!(go(src), Err(error), "{}", src);
+        }
+    }
+}
@@ -0,0 +1,881 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! A builder for SaberVM modules, for compilers that target SaberVM.
+//!
+//! [`Ops`] has a method for every op that can appear in a forward declaration or a function body.
+//! [`ModuleBuilder`] collects the data section, the forward declarations, and the definitions,
+//! and serializes them in the binary format that `parse::go` reads.
+//! [`ModuleBuilder::checked_body`] gives a [`CheckedOps`] instead,
+//! which verifies each op as it's added and can report the stacks it's checking against.
+//!
+//! ```
+//! use sabervm::builder::{ModuleBuilder, Ops};
+//! use sabervm::header::{Type, Visibility};
+//!
+//! let mut module = ModuleBuilder::new();
+//! let main = module.declare(Visibility::Local, Ops::new().func(0));
+//! let mut body = module.checked_body(main)?;
+//! body.u8_lit(7)?;
+//! assert_eq!(body.stack_type(), [Type::U8]);
+//! body.halt()?;
+//! let body = body.finish()?;
+//! module.define(main, &body)?;
+//! let bytes = module.build()?;
+//! sabervm::load(&bytes)?;
+//! # Ok::<(), sabervm::Error>(())
+//! ```
+
+use crate::assemble::{module_bytes, op_to_bytes};
+use crate::header::*;
+use crate::verify::{type_pass, FunctionVerifier};
+use std::collections::{BTreeMap, HashMap};
+
+/// A sequence of ops, built up one method call at a time.
+#[derive(Clone, Debug, Default)]
+pub struct Ops {
+    ops: Vec<Op1>,
+    /// The source locations of some of the ops, by their index.
+    locations: Vec<(usize, SourceLoc)>,
+    /// The names of the variables some of the ops introduce, by their index.
+    names: Vec<(usize, String)>,
+}
+
+impl Ops {
+    pub fn new() -> Self {
+        Ops { ops: vec![], locations: vec![], names: vec![] }
+    }
+
+    pub fn ops(&self) -> &[Op1] {
+        &self.ops
+    }
+
+    /// Give the next op a source location for the module's debug section,
+    /// in a file from `ModuleBuilder::source_file`.
+    pub fn loc(&mut self, file: u32, line: u32, col: u32) -> &mut Self {
+        self.locations.retain(|(i, _)| *i != self.ops.len());
+        self.locations.push((self.ops.len(), SourceLoc { file, line, col }));
+        self
+    }
+
+    /// Name the type variable or region that the next op introduces, for the module's names section.
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        self.names.retain(|(i, _)| *i != self.ops.len());
+        self.names.push((self.ops.len(), name.to_string()));
+        self
+    }
+
+    fn push(&mut self, op: Op1) -> &mut Self {
+        self.ops.push(op);
+        self
+    }
+
+    pub fn unique(&mut self) -> &mut Self {
+        self.push(Op1::Unique)
+    }
+
+    pub fn handle(&mut self) -> &mut Self {
+        self.push(Op1::Handle)
+    }
+
+    pub fn i32(&mut self) -> &mut Self {
+        self.push(Op1::I32)
+    }
+
+    pub fn tuple(&mut self, n: u8) -> &mut Self {
+        self.push(Op1::Tuple(n))
+    }
+
+    pub fn some(&mut self) -> &mut Self {
+        self.push(Op1::Some)
+    }
+
+    pub fn all(&mut self) -> &mut Self {
+        self.push(Op1::All)
+    }
+
+    pub fn rgn(&mut self) -> &mut Self {
+        self.push(Op1::Rgn)
+    }
+
+    pub fn end(&mut self) -> &mut Self {
+        self.push(Op1::End)
+    }
+
+    pub fn app(&mut self) -> &mut Self {
+        self.push(Op1::App)
+    }
+
+    pub fn func(&mut self, n: u8) -> &mut Self {
+        self.push(Op1::Func(n))
+    }
+
+    pub fn ctget(&mut self, i: u8) -> &mut Self {
+        self.push(Op1::CTGet(i))
+    }
+
+    pub fn unpack(&mut self) -> &mut Self {
+        self.push(Op1::Unpack)
+    }
+
+    pub fn get(&mut self, i: u8) -> &mut Self {
+        self.push(Op1::Get(i))
+    }
+
+    pub fn init(&mut self, i: u8) -> &mut Self {
+        self.push(Op1::Init(i))
+    }
+
+    pub fn malloc(&mut self) -> &mut Self {
+        self.push(Op1::Malloc)
+    }
+
+    pub fn proj(&mut self, i: u8) -> &mut Self {
+        self.push(Op1::Proj(i))
+    }
+
+    pub fn call(&mut self) -> &mut Self {
+        self.push(Op1::Call)
+    }
+
+    pub fn lit(&mut self, lit: i32) -> &mut Self {
+        self.push(Op1::Lit(lit))
+    }
+
+    pub fn global_func(&mut self, label: Label) -> &mut Self {
+        self.push(Op1::GlobalFunc(label))
+    }
+
+    pub fn halt(&mut self) -> &mut Self {
+        self.push(Op1::Halt)
+    }
+
+    pub fn pack(&mut self) -> &mut Self {
+        self.push(Op1::Pack)
+    }
+
+    pub fn size(&mut self, s: u32) -> &mut Self {
+        self.push(Op1::Size(s))
+    }
+
+    pub fn new_rgn(&mut self, s: u32) -> &mut Self {
+        self.push(Op1::NewRgn(s))
+    }
+
+    pub fn free_rgn(&mut self) -> &mut Self {
+        self.push(Op1::FreeRgn)
+    }
+
+    pub fn ptr(&mut self) -> &mut Self {
+        self.push(Op1::Ptr)
+    }
+
+    pub fn deref(&mut self) -> &mut Self {
+        self.push(Op1::Deref)
+    }
+
+    pub fn arr(&mut self) -> &mut Self {
+        self.push(Op1::Arr)
+    }
+
+    pub fn arr_mut(&mut self) -> &mut Self {
+        self.push(Op1::ArrMut)
+    }
+
+    pub fn arr_proj(&mut self) -> &mut Self {
+        self.push(Op1::ArrProj)
+    }
+
+    pub fn add(&mut self) -> &mut Self {
+        self.push(Op1::Add)
+    }
+
+    pub fn mul(&mut self) -> &mut Self {
+        self.push(Op1::Mul)
+    }
+
+    pub fn div(&mut self) -> &mut Self {
+        self.push(Op1::Div)
+    }
+
+    pub fn call_nz(&mut self) -> &mut Self {
+        self.push(Op1::CallNZ)
+    }
+
+    pub fn data(&mut self, loc: u32) -> &mut Self {
+        self.push(Op1::Data(loc))
+    }
+
+    pub fn data_sec(&mut self) -> &mut Self {
+        self.push(Op1::DataSec)
+    }
+
+    pub fn u8(&mut self) -> &mut Self {
+        self.push(Op1::U8)
+    }
+
+    pub fn copy_n(&mut self) -> &mut Self {
+        self.push(Op1::CopyN)
+    }
+
+    pub fn u8_lit(&mut self, lit: u8) -> &mut Self {
+        self.push(Op1::U8Lit(lit))
+    }
+
+    pub fn u8_to_i32(&mut self) -> &mut Self {
+        self.push(Op1::U8ToI32)
+    }
+
+    pub fn modulo(&mut self) -> &mut Self {
+        self.push(Op1::Modulo)
+    }
+
+    pub fn i32_to_u8(&mut self) -> &mut Self {
+        self.push(Op1::I32ToU8)
+    }
+
+    pub fn read(&mut self, channel: u8) -> &mut Self {
+        self.push(Op1::Read(channel))
+    }
+
+    pub fn write(&mut self, channel: u8) -> &mut Self {
+        self.push(Op1::Write(channel))
+    }
+
+    pub fn catch(&mut self) -> &mut Self {
+        self.push(Op1::Catch)
+    }
+
+    pub fn checked_add(&mut self) -> &mut Self {
+        self.push(Op1::CheckedAdd)
+    }
+
+    pub fn checked_mul(&mut self) -> &mut Self {
+        self.push(Op1::CheckedMul)
+    }
+
+    pub fn checked_div(&mut self) -> &mut Self {
+        self.push(Op1::CheckedDiv)
+    }
+
+    pub fn saturating_add(&mut self) -> &mut Self {
+        self.push(Op1::SaturatingAdd)
+    }
+
+    pub fn saturating_mul(&mut self) -> &mut Self {
+        self.push(Op1::SaturatingMul)
+    }
+
+    pub fn saturating_div(&mut self) -> &mut Self {
+        self.push(Op1::SaturatingDiv)
+    }
+
+    pub fn sub(&mut self) -> &mut Self {
+        self.push(Op1::Sub)
+    }
+
+    pub fn neg(&mut self) -> &mut Self {
+        self.push(Op1::Neg)
+    }
+
+    pub fn eq(&mut self) -> &mut Self {
+        self.push(Op1::Eq)
+    }
+
+    pub fn lt(&mut self) -> &mut Self {
+        self.push(Op1::Lt)
+    }
+
+    pub fn and(&mut self) -> &mut Self {
+        self.push(Op1::And)
+    }
+
+    pub fn or(&mut self) -> &mut Self {
+        self.push(Op1::Or)
+    }
+
+    pub fn xor(&mut self) -> &mut Self {
+        self.push(Op1::Xor)
+    }
+
+    pub fn shl(&mut self) -> &mut Self {
+        self.push(Op1::Shl)
+    }
+
+    pub fn shr(&mut self) -> &mut Self {
+        self.push(Op1::Shr)
+    }
+
+    pub fn i64(&mut self) -> &mut Self {
+        self.push(Op1::I64)
+    }
+
+    pub fn u64(&mut self) -> &mut Self {
+        self.push(Op1::U64)
+    }
+
+    pub fn i64_lit(&mut self, lit: i64) -> &mut Self {
+        self.push(Op1::I64Lit(lit))
+    }
+
+    pub fn u64_lit(&mut self, lit: u64) -> &mut Self {
+        self.push(Op1::U64Lit(lit))
+    }
+
+    pub fn cast(&mut self) -> &mut Self {
+        self.push(Op1::Cast)
+    }
+
+    pub fn f32(&mut self) -> &mut Self {
+        self.push(Op1::F32)
+    }
+
+    pub fn f64(&mut self) -> &mut Self {
+        self.push(Op1::F64)
+    }
+
+    pub fn f32_lit(&mut self, lit: f32) -> &mut Self {
+        self.push(Op1::F32Lit(lit.to_bits()))
+    }
+
+    pub fn f64_lit(&mut self, lit: f64) -> &mut Self {
+        self.push(Op1::F64Lit(lit.to_bits()))
+    }
+
+    pub fn variant(&mut self, n: u8) -> &mut Self {
+        self.push(Op1::Variant(n))
+    }
+
+    pub fn inj(&mut self, i: u8) -> &mut Self {
+        self.push(Op1::Inj(i))
+    }
+
+    pub fn case(&mut self, n: u8) -> &mut Self {
+        self.push(Op1::Case(n))
+    }
+
+    pub fn switch(&mut self, n: u8) -> &mut Self {
+        self.push(Op1::Switch(n))
+    }
+}
+
+/// A function body whose ops are verified as they're added, for finding mistakes in generated code early.
+/// Positions in errors count the ops of the body from 0.
+pub struct CheckedOps<'a> {
+    ops: Ops,
+    verifier: FunctionVerifier<'a>,
+}
+
+impl CheckedOps<'_> {
+    pub fn ops(&self) -> &[Op1] {
+        self.ops.ops()
+    }
+
+    /// The compile-time stack after the ops so far, with the top at the end.
+    pub fn compile_time_stack(&self) -> &[CTStackVal] {
+        self.verifier.compile_time_stack()
+    }
+
+    /// The types of the runtime stack after the ops so far, with the top at the end.
+    pub fn stack_type(&self) -> &[Type] {
+        self.verifier.stack_type()
+    }
+
+    /// Give the next op a source location for the module's debug section.
+    pub fn loc(&mut self, file: u32, line: u32, col: u32) -> &mut Self {
+        self.ops.loc(file, line, col);
+        self
+    }
+
+    /// Name the type variable or region that the next op introduces, for the module's names section.
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        self.ops.name(name);
+        self
+    }
+
+    /// Finish the body, returning its ops to pass to `ModuleBuilder::define`.
+    pub fn finish(self) -> Result<Ops, Error> {
+        self.verifier.finish()?;
+        Ok(self.ops)
+    }
+
+    fn push(&mut self, op: Op1) -> Result<&mut Self, Error> {
+        self.verifier.step(&op)?;
+        self.ops.push(op);
+        Ok(self)
+    }
+
+    pub fn unique(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Unique)
+    }
+
+    pub fn handle(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Handle)
+    }
+
+    pub fn i32(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::I32)
+    }
+
+    pub fn tuple(&mut self, n: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Tuple(n))
+    }
+
+    pub fn some(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Some)
+    }
+
+    pub fn all(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::All)
+    }
+
+    pub fn rgn(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Rgn)
+    }
+
+    pub fn end(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::End)
+    }
+
+    pub fn app(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::App)
+    }
+
+    pub fn func(&mut self, n: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Func(n))
+    }
+
+    pub fn ctget(&mut self, i: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::CTGet(i))
+    }
+
+    pub fn unpack(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Unpack)
+    }
+
+    pub fn get(&mut self, i: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Get(i))
+    }
+
+    pub fn init(&mut self, i: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Init(i))
+    }
+
+    pub fn malloc(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Malloc)
+    }
+
+    pub fn proj(&mut self, i: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Proj(i))
+    }
+
+    pub fn call(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Call)
+    }
+
+    pub fn lit(&mut self, lit: i32) -> Result<&mut Self, Error> {
+        self.push(Op1::Lit(lit))
+    }
+
+    pub fn global_func(&mut self, label: Label) -> Result<&mut Self, Error> {
+        self.push(Op1::GlobalFunc(label))
+    }
+
+    pub fn halt(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Halt)
+    }
+
+    pub fn pack(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Pack)
+    }
+
+    pub fn size(&mut self, s: u32) -> Result<&mut Self, Error> {
+        self.push(Op1::Size(s))
+    }
+
+    pub fn new_rgn(&mut self, s: u32) -> Result<&mut Self, Error> {
+        self.push(Op1::NewRgn(s))
+    }
+
+    pub fn free_rgn(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::FreeRgn)
+    }
+
+    pub fn ptr(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Ptr)
+    }
+
+    pub fn deref(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Deref)
+    }
+
+    pub fn arr(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Arr)
+    }
+
+    pub fn arr_mut(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::ArrMut)
+    }
+
+    pub fn arr_proj(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::ArrProj)
+    }
+
+    pub fn add(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Add)
+    }
+
+    pub fn mul(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Mul)
+    }
+
+    pub fn div(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Div)
+    }
+
+    pub fn call_nz(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::CallNZ)
+    }
+
+    pub fn data(&mut self, loc: u32) -> Result<&mut Self, Error> {
+        self.push(Op1::Data(loc))
+    }
+
+    pub fn data_sec(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::DataSec)
+    }
+
+    pub fn u8(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::U8)
+    }
+
+    pub fn copy_n(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::CopyN)
+    }
+
+    pub fn u8_lit(&mut self, lit: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::U8Lit(lit))
+    }
+
+    pub fn u8_to_i32(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::U8ToI32)
+    }
+
+    pub fn modulo(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Modulo)
+    }
+
+    pub fn i32_to_u8(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::I32ToU8)
+    }
+
+    pub fn read(&mut self, channel: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Read(channel))
+    }
+
+    pub fn write(&mut self, channel: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Write(channel))
+    }
+
+    pub fn catch(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Catch)
+    }
+
+    pub fn checked_add(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::CheckedAdd)
+    }
+
+    pub fn checked_mul(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::CheckedMul)
+    }
+
+    pub fn checked_div(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::CheckedDiv)
+    }
+
+    pub fn saturating_add(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::SaturatingAdd)
+    }
+
+    pub fn saturating_mul(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::SaturatingMul)
+    }
+
+    pub fn saturating_div(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::SaturatingDiv)
+    }
+
+    pub fn sub(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Sub)
+    }
+
+    pub fn neg(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Neg)
+    }
+
+    pub fn eq(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Eq)
+    }
+
+    pub fn lt(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Lt)
+    }
+
+    pub fn and(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::And)
+    }
+
+    pub fn or(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Or)
+    }
+
+    pub fn xor(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Xor)
+    }
+
+    pub fn shl(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Shl)
+    }
+
+    pub fn shr(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Shr)
+    }
+
+    pub fn i64(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::I64)
+    }
+
+    pub fn u64(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::U64)
+    }
+
+    pub fn i64_lit(&mut self, lit: i64) -> Result<&mut Self, Error> {
+        self.push(Op1::I64Lit(lit))
+    }
+
+    pub fn u64_lit(&mut self, lit: u64) -> Result<&mut Self, Error> {
+        self.push(Op1::U64Lit(lit))
+    }
+
+    pub fn cast(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::Cast)
+    }
+
+    pub fn f32(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::F32)
+    }
+
+    pub fn f64(&mut self) -> Result<&mut Self, Error> {
+        self.push(Op1::F64)
+    }
+
+    pub fn f32_lit(&mut self, lit: f32) -> Result<&mut Self, Error> {
+        self.push(Op1::F32Lit(lit.to_bits()))
+    }
+
+    pub fn f64_lit(&mut self, lit: f64) -> Result<&mut Self, Error> {
+        self.push(Op1::F64Lit(lit.to_bits()))
+    }
+
+    pub fn variant(&mut self, n: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Variant(n))
+    }
+
+    pub fn inj(&mut self, i: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Inj(i))
+    }
+
+    pub fn case(&mut self, n: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Case(n))
+    }
+
+    pub fn switch(&mut self, n: u8) -> Result<&mut Self, Error> {
+        self.push(Op1::Switch(n))
+    }
+}
+
+/// A module under construction.
+/// Functions are labelled in the order they're declared, so the first declaration is the main function.
+#[derive(Debug, Default)]
+pub struct ModuleBuilder {
+    data_section: Vec<u8>,
+    files: Vec<String>,
+    func_names: BTreeMap<Label, String>,
+    forward_decs: Vec<(Visibility, Ops)>,
+    bodies: HashMap<Label, Ops>,
+    types: HashMap<Label, Type>,
+}
+
+impl ModuleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append bytes to the data section, returning their offset for `Ops::data`.
+    pub fn data(&mut self, bytes: &[u8]) -> u32 {
+        let loc = self.data_section.len() as u32;
+        self.data_section.extend(bytes);
+        loc
+    }
+
+    /// Add a source file to the debug section, returning its index for `Ops::loc`.
+    pub fn source_file(&mut self, path: &str) -> u32 {
+        match self.files.iter().position(|file| file == path) {
+            Some(i) => i as u32,
+            None => {
+                self.files.push(path.to_string());
+                self.files.len() as u32 - 1
+            }
+        }
+    }
+
+    /// Forward-declare a function with the type that the ops build, returning its label.
+    /// The ops must leave exactly one type on the compile-time stack, which the verifier checks.
+    pub fn declare(&mut self, visibility: Visibility, t: &Ops) -> Label {
+        self.forward_decs.push((visibility, t.clone()));
+        self.forward_decs.len() as Label - 1
+    }
+
+    /// Name a declared function, for the module's names section.
+    pub fn name_func(&mut self, label: Label, name: &str) -> Result<(), Error> {
+        if label as usize >= self.forward_decs.len() {
+            return Err(Error::BuilderErrorUnknownLabel(label));
+        }
+        self.func_names.insert(label, name.to_string());
+        Ok(())
+    }
+
+    /// Start a body for a declared function that verifies each op as it's added.
+    /// The module can't change until the body is finished.
+    pub fn checked_body(&mut self, label: Label) -> Result<CheckedOps<'_>, Error> {
+        self.definable(label)?;
+        self.types.clear();
+        let mut pos = 0;
+        let mut fresh_id = 0;
+        for (l, (visibility, decl_ops)) in self.forward_decs.iter().enumerate() {
+            let decl = ForwardDec::Func(l as Label, *visibility, decl_ops.ops.clone());
+            let (l, _, t, new_fresh_id) = type_pass(&decl, pos, fresh_id)?;
+            self.types.insert(l, t);
+            fresh_id = new_fresh_id;
+            pos += decl_ops.ops.len() as Pos + 1;
+        }
+        let verifier = FunctionVerifier::new(self.data_section.len(), &self.types, label, 0, fresh_id)?;
+        Ok(CheckedOps { ops: Ops::new(), verifier })
+    }
+
+    fn definable(&self, label: Label) -> Result<(), Error> {
+        match self.forward_decs.get(label as usize) {
+            None => Err(Error::BuilderErrorUnknownLabel(label)),
+            Some((Visibility::Import(_, _), _)) => Err(Error::BuilderErrorImportDefined(label)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Give a declared function its body, which must end with its only `call`, `call_nz`, `case`, `switch`, or `halt`.
+    pub fn define(&mut self, label: Label, body: &Ops) -> Result<(), Error> {
+        self.definable(label)?;
+        let terminators = body.ops.iter().filter(|op| matches!(op, Op1::Call | Op1::CallNZ | Op1::Case(_) | Op1::Switch(_) | Op1::Halt));
+        if terminators.count() != 1 || !matches!(body.ops.last(), Some(Op1::Call | Op1::CallNZ | Op1::Case(_) | Op1::Switch(_) | Op1::Halt)) {
+            return Err(Error::BuilderErrorBadTerminator(label));
+        }
+        if self.bodies.insert(label, body.clone()).is_some() {
+            return Err(Error::BuilderErrorRedefined(label));
+        }
+        Ok(())
+    }
+
+    /// Serialize the module, checking that every function that isn't imported has been defined.
+    pub fn build(&self) -> Result<ByteStream, Error> {
+        let mut op_bytes = vec![];
+        let mut locations = BTreeMap::new();
+        let mut var_names = BTreeMap::new();
+        let mut pos = 0;
+        let mut add = |ops: &Ops, terminator: Option<Op1>| {
+            for (i, loc) in &ops.locations {
+                locations.insert(pos + *i as Pos, *loc);
+            }
+            for (i, name) in &ops.names {
+                var_names.insert(pos + *i as Pos, name.clone());
+            }
+            for op in ops.ops.iter().chain(&terminator) {
+                op_bytes.extend(op_to_bytes(op));
+                pos += 1;
+            }
+        };
+        for (visibility, t) in &self.forward_decs {
+            let terminator = match visibility {
+                Visibility::Local => Op1::Lced,
+                Visibility::Export(a, b) => Op1::Export(*a, *b),
+                Visibility::Import(a, b) => Op1::Import(*a, *b),
+            };
+            add(t, Some(terminator));
+        }
+        // the definitions are in the same order as the non-imported forward declarations
+        for (label, (visibility, _)) in self.forward_decs.iter().enumerate() {
+            if let Visibility::Import(_, _) = visibility {
+                continue;
+            }
+            let label = label as Label;
+            let body = self.bodies.get(&label).ok_or(Error::BuilderErrorUndefined(label))?;
+            add(body, None);
+        }
+        let debug_info = (!locations.is_empty()).then(|| DebugInfo { files: self.files.clone(), locations });
+        let names = (!self.func_names.is_empty() || !var_names.is_empty()).then(|| Names {
+            funcs: self.func_names.clone(),
+            vars: var_names,
+        });
+        let n = self.forward_decs.len() as u32;
+        Ok(module_bytes(&self.data_section, debug_info.as_ref(), names.as_ref(), n, &op_bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_mode_rejects_an_ill_typed_op_where_it_is_emitted() -> Result<(), Error> {
+        let mut module = ModuleBuilder::new();
+        let main = module.declare(Visibility::Local, Ops::new().func(0));
+        let mut body = module.checked_body(main)?;
+        body.lit(7)?;
+        assert_eq!(body.halt().err(), Some(Error::TypeError(1, Op1::Halt, Type::U8, Type::I32)));
+        // the rejected op isn't added
+        assert_eq!(body.ops(), [Op1::Lit(7)]);
+        Ok(())
+    }
+
+    #[test]
+    fn checked_mode_accepts_a_valid_program() -> Result<(), Error> {
+        let mut module = ModuleBuilder::new();
+        let main = module.declare(Visibility::Local, Ops::new().func(0));
+        let add_one = module.declare(Visibility::Local, Ops::new().i32().func(1));
+
+        let mut body = module.checked_body(main)?;
+        body.lit(41)?.global_func(add_one)?;
+        assert_eq!(body.stack_type(), [Type::I32, Type::Func(vec![Type::I32])]);
+        body.call()?;
+        let main_body = body.finish()?;
+
+        let mut body = module.checked_body(add_one)?;
+        body.lit(1)?.add()?.i32_to_u8()?.halt()?;
+        let add_one_body = body.finish()?;
+
+        module.define(main, &main_body)?;
+        module.define(add_one, &add_one_body)?;
+        let bytes = module.build()?;
+        crate::load(&bytes)?;
+
+        // the same program built without checking
+        let mut unchecked = ModuleBuilder::new();
+        unchecked.declare(Visibility::Local, Ops::new().func(0));
+        unchecked.declare(Visibility::Local, Ops::new().i32().func(1));
+        unchecked.define(main, Ops::new().lit(41).global_func(add_one).call())?;
+        unchecked.define(add_one, Ops::new().lit(1).add().i32_to_u8().halt())?;
+        assert_eq!(unchecked.build()?, bytes);
+        Ok(())
+    }
+}
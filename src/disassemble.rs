@@ -0,0 +1,126 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// The disassembler turns a binary back into the assembly language of `assemble.rs`.
+// Every op gets its byte offset in the binary as a trailing comment,
+// and the output assembles back into exactly the same bytes
+// (as long as the debug section lists its files in the order the ops first use them, as the assembler does,
+// and the names section only names variables at the positions of ops).
+
+use crate::assemble::op_to_bytes;
+use crate::header::*;
+use crate::parse::{lex, parse_forward_decs};
+use crate::pretty::{escape_bytes, int_pair_to_str, Pretty};
+
+/// The number of data-section bytes printed per line.
+const DATA_LINE_LEN: usize = 32;
+
+fn line(out: &mut String, text: &str, offset: usize) {
+    *out += &format!("{:<31} # {}\n", text, offset);
+}
+
+fn data_section(out: &mut String, data_section: &[u8], mut offset: usize) {
+    *out += "data_section {\n";
+    let mut start = 0;
+    while start < data_section.len() {
+        // break lines after newlines, so text reads naturally
+        let end = match data_section[start..].iter().position(|b| *b == b'\n') {
+            Some(i) if i < DATA_LINE_LEN => start + i + 1,
+            _ => usize::min(start + DATA_LINE_LEN, data_section.len()),
+        };
+        let chunk = &data_section[start..end];
+        line(out, &("    \"".to_string() + &escape_bytes(chunk) + "\""), offset);
+        offset += chunk.len();
+        start = end;
+    }
+    *out += "}\n";
+}
+
+/// Print the ops, each preceded by its source location if the debug section has one,
+/// and the name of the variable it introduces if the names section has one.
+fn ops<'a>(
+    out: &mut String,
+    ops: impl Iterator<Item = &'a Op1>,
+    debug_info: Option<&DebugInfo>,
+    names: Option<&Names>,
+    pos: &mut Pos,
+    offset: &mut usize,
+) {
+    for op in ops {
+        if let Some(debug_info) = debug_info {
+            if let Some(loc) = debug_info.locations.get(pos) {
+                let file = escape_bytes(debug_info.files[loc.file as usize].as_bytes());
+                *out += &format!("loc \"{}\" {} {}\n", file, loc.line, loc.col);
+            }
+        }
+        if let Some(name) = names.and_then(|names| names.vars.get(pos)) {
+            *out += &format!("name \"{}\"\n", escape_bytes(name.as_bytes()));
+        }
+        line(out, &op.pretty(), *offset);
+        *pos += 1;
+        *offset += op_to_bytes(op).len();
+    }
+}
+
+/// Disassemble a binary into assembly text that reassembles losslessly.
+pub fn go(bytes: &[u8]) -> Result<String, Error> {
+    let (data, debug_info, names, tokens, n) = lex(bytes)?;
+    let (forward_decs, mut rest, _pos) = parse_forward_decs(&tokens, n)?;
+    let mut out = format!("# SaberVM module, format version {}\n\n", FORMAT_VERSION);
+    if !data.is_empty() {
+        out += &format!("# data section: {} bytes\n", data.len());
+        data_section(&mut out, &data, 12);
+        out += "\n";
+    }
+
+    let section_len = |offset: usize| u32::from_le_bytes(bytes[offset..][..4].try_into().unwrap()) as usize;
+    let debug_len = section_len(12 + data.len());
+    let names_len = section_len(12 + data.len() + 4 + debug_len);
+    let mut offset = 12 + data.len() + 4 + debug_len + 4 + names_len + 4;
+    let mut pos = 0;
+    out += &format!("# forward declarations: {} functions\n", n);
+    for ForwardDec::Func(label, visibility, decl_ops) in &forward_decs {
+        out += &match visibility {
+            Visibility::Local => format!("\n# function {}\n", label),
+            Visibility::Export(a, b) => format!("\n# function {}, exported as {}\n", label, int_pair_to_str(a, b)),
+            Visibility::Import(a, b) => format!("\n# function {}, imported as {}\n", label, int_pair_to_str(a, b)),
+        };
+        if let Some(name) = names.as_ref().and_then(|names| names.func(*label)) {
+            out += &format!("func_name {} \"{}\"\n", label, escape_bytes(name.as_bytes()));
+        }
+        let terminator = match visibility {
+            Visibility::Local => Op1::Lced,
+            Visibility::Export(a, b) => Op1::Export(*a, *b),
+            Visibility::Import(a, b) => Op1::Import(*a, *b),
+        };
+        ops(&mut out, decl_ops.iter().chain([&terminator]), debug_info.as_ref(), names.as_ref(), &mut pos, &mut offset);
+    }
+
+    // the definitions are in the same order as the non-imported forward declarations
+    let mut labels = forward_decs.iter().filter_map(|decl| match decl {
+        ForwardDec::Func(_, Visibility::Import(_, _), _) => None,
+        ForwardDec::Func(label, _, _) => Some(label),
+    });
+    out += "\n# definitions\n";
+    loop {
+        let mut body = vec![];
+        for op in rest.by_ref() {
+            body.push(*op);
+            if let Op1::Call | Op1::CallNZ | Op1::Case(_) | Op1::Switch(_) | Op1::Halt = op {
+                break;
+            }
+        }
+        if body.is_empty() {
+            break;
+        }
+        out += &match labels.next() {
+            Some(label) => format!("\n# function {}\n", label),
+            None => "\n# no forward declaration for this definition\n".to_string(),
+        };
+        ops(&mut out, body.iter(), debug_info.as_ref(), names.as_ref(), &mut pos, &mut offset);
+    }
+    Ok(out)
+}
@@ -5,9 +5,82 @@
  */
 
 use crate::header::*;
-use crate::pretty::Pretty;
+use crate::pretty::{
+    ctval_json, escape_bytes, int_pair_to_str, json_string, label_name, region_json, type_json, type_var_name, var_json,
+    Pretty,
+};
+use std::fmt;
 
-pub fn msg(e: Error) -> String {
+/// The message for an error, starting with the source location of the op it's about
+/// if the module has a debug section that gives one, then the error's code,
+/// and printing functions, type variables, and regions by name if it has a names section.
+pub fn msg(e: Error, debug_info: Option<&DebugInfo>, names: Option<&Names>) -> String {
+    let plain_msg = format!("[{}] {}", e.code(), plain_msg(e.clone(), names.unwrap_or(&Names::default())));
+    match e.pos().and_then(|pos| debug_info?.location(pos)) {
+        Some(location) => location + ": " + &plain_msg,
+        None => plain_msg,
+    }
+}
+
+/// The error as a JSON object, for tools that would otherwise have to pick apart the message:
+/// its code, its message, the function it's in if that's known (`label`),
+/// the position and source location of the op it's about and the op itself,
+/// and what the op expected and found, each as text and as structured data.
+/// Anything the error doesn't say is `null`.
+pub fn json(e: &Error, label: Option<Label>, debug_info: Option<&DebugInfo>, names: Option<&Names>) -> String {
+    let no_names = Names::default();
+    let names = names.unwrap_or(&no_names);
+    let or_null = |json: Option<String>| json.unwrap_or_else(|| "null".to_string());
+    let function = label.map(|label| match names.func(label) {
+        Some(name) => format!("{{\"label\":{},\"name\":{}}}", label, json_string(name)),
+        None => format!("{{\"label\":{}}}", label),
+    });
+    let location = e.pos().and_then(|pos| {
+        let debug_info = debug_info?;
+        let loc = debug_info.locations.get(&pos)?;
+        Some(format!(
+            "{{\"file\":{},\"line\":{},\"col\":{}}}",
+            json_string(debug_info.files.get(loc.file as usize)?),
+            loc.line,
+            loc.col
+        ))
+    });
+    format!(
+        "{{\"code\":{},\"message\":{},\"function\":{},\"pos\":{},\"location\":{},\"opcode\":{},\"expected\":{},\"found\":{}}}",
+        json_string(e.code()),
+        json_string(&plain_msg(e.clone(), names)),
+        or_null(function),
+        or_null(e.pos().map(|pos| pos.to_string())),
+        or_null(location),
+        or_null(e.op().map(|op| json_string(&op.pretty()))),
+        or_null(e.expected().map(|term| term_json(&term, names))),
+        or_null(e.found().map(|term| term_json(&term, names))),
+    )
+}
+
+/// Something an error expected or found as a JSON object, with its text and its structure.
+fn term_json(term: &Term, names: &Names) -> String {
+    let (text, key, value) = match term {
+        Term::Type(t) => (t.pretty_named(names), "type", type_json(t, names)),
+        Term::Types(ts) => (
+            ts.iter().map(|t| t.pretty_named(names)).collect::<Vec<_>>().join(", "),
+            "types",
+            "[".to_string() + &ts.iter().map(|t| type_json(t, names)).collect::<Vec<_>>().join(",") + "]",
+        ),
+        Term::Region(r) => (r.pretty_named(names), "region", region_json(r, names)),
+        Term::Kind(kind) => (kind.pretty(), "kind", json_string(&kind.pretty())),
+        Term::TypeVar(id) => (type_var_name(*id, names), "type_var", var_json(*id, names)),
+        Term::CompileTimeStack(ctvals) => (
+            ctvals.iter().map(|ctval| ctval.pretty_named(names)).collect::<Vec<_>>().join(", "),
+            "compile_time_stack",
+            "[".to_string() + &ctvals.iter().map(|ctval| ctval_json(ctval, names)).collect::<Vec<_>>().join(",") + "]",
+        ),
+        Term::Number(n) => (n.to_string(), "number", n.to_string()),
+    };
+    format!("{{\"text\":{},\"{}\":{}}}", json_string(&text), key, value)
+}
+
+fn plain_msg(e: Error, names: &Names) -> String {
     match e {
         Error::SyntaxErrorParamNeeded(pos, op) => {
             format!("Syntax Error: Parameter needed for opcode {:?} at pos {}", op, pos)
@@ -15,11 +88,23 @@ pub fn msg(e: Error) -> String {
         Error::SyntaxErrorUnknownOp(pos, op) => {
             format!("Syntax Error: Unknown opcode {:?} at pos {}", op, pos)
         },
+        Error::SyntaxErrorNotAModule => {
+            format!("Syntax Error: Not a SaberVM module (it doesn't start with the magic number \"{}\")", escape_bytes(&MAGIC))
+        },
+        Error::SyntaxErrorUnsupportedVersion(version) => {
+            format!("Syntax Error: Unsupported format version {}, this SaberVM reads version {}", version, FORMAT_VERSION)
+        },
+        Error::SyntaxErrorBadDebugSection => {
+            "Syntax Error: Malformed debug section".to_string()
+        },
+        Error::SyntaxErrorBadNamesSection => {
+            "Syntax Error: Malformed names section".to_string()
+        },
         Error::TypeErrorMainHasArgs => {
-            format!("Type Error: Main function cannot have arguments")
+            "Type Error: Main function cannot have arguments".to_string()
         },
         Error::TypeErrorNonEmptyQuantificationStack(label) => {
-            format!("Type Error: Non-empty quantification stack at label {}", label)
+            format!("Type Error: Non-empty quantification stack at label {}", label_name(label, names))
         },
         Error::TypeErrorEmptyQuantificationStack(pos, op) => {
             format!("Type Error: Empty quantification stack at pos {} for opcode {}", pos, op.pretty())
@@ -34,25 +119,25 @@ pub fn msg(e: Error) -> String {
             format!("Kind Error: Expected {} at pos {} for opcode {} but found {}", kind.pretty(), pos, op.pretty(), ctval.kind().pretty())
         },
         Error::RegionError(pos, op, r1, r2) => {
-            format!("Region Error: Expected region {} at pos {} for opcode {} but found {}", r1.pretty(), pos, op.pretty(), r2.pretty())
+            format!("Region Error: Expected region {} at pos {} for opcode {} but found {}", r1.pretty_named(names), pos, op.pretty(), r2.pretty_named(names))
         },
         Error::TypeError(pos, op, t1, t2) => {
-            format!("Type Error: Expected type {} at pos {} for opcode {} but found {}", t1.pretty(), pos, op.pretty(), t2.pretty())
+            format!("Type Error: Expected type {} at pos {} for opcode {} but found {}", t1.pretty_named(names), pos, op.pretty(), t2.pretty_named(names))
         },
         Error::SizeError(pos, op, s1, s2) => {
             format!("Size Error: Expected size {} at pos {} for opcode {} but found {}", s1, pos, op.pretty(), s2)
         },
         Error::UniquenessError(pos, op, r) => {
-            format!("Uniqueness Error: Expected unique region {} at pos {} for opcode {}", r.pretty(), pos, op.pretty())
+            format!("Uniqueness Error: Expected unique region {} at pos {} for opcode {}", r.pretty_named(names), pos, op.pretty())
         },
         Error::RegionAccessError(pos, op, r) => {
-            format!("Region Access Error: Expected access to region {} at pos {} for opcode {}", r.pretty(), pos, op.pretty())
+            format!("Region Access Error: Expected access to region {} at pos {} for opcode {}", r.pretty_named(names), pos, op.pretty())
         },
         Error::TypeErrorSpecificTypeVarExpected(pos, op, id1, id2) => {
-            format!("Type Error: Expected type variable a{} at pos {} for opcode {} but found a{}", id1.1, pos, op.pretty(), id2.1)
+            format!("Type Error: Expected type variable {} at pos {} for opcode {} but found {}", type_var_name(id1, names), pos, op.pretty(), type_var_name(id2, names))
         },
         Error::TypeErrorTypeVarExpected(pos, op, id, t) => {
-            format!("Type Error: Expected type variable a{} at pos {} for opcode {} but found {}", id.1, pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected type variable {} at pos {} for opcode {} but found {}", type_var_name(id, names), pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorCTGetOutOfRange(pos, i, max) => {
             format!("Type Error: ct_get out of range at pos {}: the compile-time stack depth is {} but got {}", pos, max, i)
@@ -67,37 +152,37 @@ pub fn msg(e: Error) -> String {
             format!("Type Error: proj out of range at pos {}: the stack depth is {} but got {}", pos, max, i)
         },
         Error::TypeErrorExistentialExpected(pos, op, t) => {
-            format!("Type Error: Expected existential type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected existential type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorInitTypeMismatch(pos, t1, t2) => {
-            format!("Type Error: Expected type {} at pos {} for init but found {}", t1.pretty(), pos, t2.pretty())
+            format!("Type Error: Expected type {} at pos {} for init but found {}", t1.pretty_named(names), pos, t2.pretty_named(names))
         },
         Error::TypeErrorTupleExpected(pos, op, t) => {
-            format!("Type Error: Expected tuple type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected tuple type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorFunctionExpected(pos, op, t) => {
-            format!("Type Error: Expected function type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected function type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorRegionHandleExpected(pos, op, t) => {
-            format!("Type Error: Expected region handle type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected region handle type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorNotEnoughRuntimeArgs(pos, s1, s2) => {
             format!("Type Error: Not enough runtime arguments at pos {}: expected {} but got {}", pos, s1, s2)
         },
         Error::TypeErrorCallArgTypesMismatch(pos, ts1, ts2) => {
-            format!("Type Error: Call argument types mismatch at pos {}: expected {} but got {}", pos, ts1.iter().map(|t| t.pretty()).collect::<Vec<_>>().join(", "), ts2.iter().map(|t| t.pretty()).collect::<Vec<_>>().join(", "))
+            format!("Type Error: Call argument types mismatch at pos {}: expected {} but got {}", pos, ts1.iter().map(|t| t.pretty_named(names)).collect::<Vec<_>>().join(", "), ts2.iter().map(|t| t.pretty_named(names)).collect::<Vec<_>>().join(", "))
         },
         Error::TypeErrorMallocNonTuple(pos, op, t) => {
-            format!("Type Error: Expected tuple type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected tuple type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorPtrExpected(pos, op, t) => {
-            format!("Type Error: Expected pointer type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected pointer type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorForallExpected(pos, op, t) => {
-            format!("Type Error: Expected forall type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected forall type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::TypeErrorForallRegionExpected(pos, op, t) => {
-            format!("Type Error: Expected forall region type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected forall region type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::KindErrorBadApp(pos, op, ctval) => {
             format!("Kind Error: Expected type at pos {} for opcode {} but found {}", pos, op.pretty(), ctval.kind().pretty())
@@ -109,10 +194,10 @@ pub fn msg(e: Error) -> String {
             format!("Type Error: Uninitialized read at pos {} for opcode {}: component {} has not been initialized", pos, op.pretty(), n)
         },
         Error::TooBigForStack(pos, op, t) => {
-            format!("Type Error: Too big for stack at pos {} for opcode {}: {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Too big for stack at pos {} for opcode {}: {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::ForwardDeclNotType(t) => {
-            format!("Forward declaration of non-type: {}", t.pretty())
+            format!("Forward declaration of non-type: {}", t.pretty_named(names))
         },
         Error::ForwardDeclRuntimeOp(op) => {
             format!("Forward declaration of runtime opcode: {}", op.pretty())
@@ -127,22 +212,345 @@ pub fn msg(e: Error) -> String {
             "Unexpected end of file".to_string()
         },
         Error::TypeErrorArrayExpected(pos, op, t) => {
-            format!("Type Error: Expected array type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty())
+            format!("Type Error: Expected array type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::ReadOnlyRegionError(pos, op, r) => {
-            format!("Region Error: region is read-only at pos {} for opcode {}: {}", pos, op.pretty(), r.pretty())
+            format!("Region Error: region is read-only at pos {} for opcode {}: {}", pos, op.pretty(), r.pretty_named(names))
         },
         Error::DataSectionLoadOutOfBounds(pos, op, loc, max) => {
             format!("Data section load out of bounds at pos {} for opcode {}: loading from {} but the data section ends at {}", pos, op.pretty(), loc, max)
         },
         Error::InvalidDataSectionType(pos, op, t) => {
-            format!("Data section type error at pos {} for opcode {}: invalid data section type {}", pos, op.pretty(), t.pretty())
+            format!("Data section type error at pos {} for opcode {}: invalid data section type {}", pos, op.pretty(), t.pretty_named(names))
         },
         Error::CannotMutateDataSection(pos, op) => {
             format!("Data section mutation error at pos {} for opcode {}: cannot mutate data section", pos, op.pretty())
         },
         Error::UnknownChannel(pos, op, c) => {
             format!("Unknown channel {} at pos {} for opcode {}", c, pos, op.pretty())
+        },
+        Error::AssemblyErrorUnknownMnemonic(line, mnemonic) => {
+            format!("Assembly Error: Unknown mnemonic `{}` on line {}", mnemonic, line)
+        },
+        Error::AssemblyErrorParamNeeded(line, mnemonic) => {
+            format!("Assembly Error: Parameter needed for `{}` on line {}", mnemonic, line)
+        },
+        Error::AssemblyErrorBadParam(line, mnemonic, param) => {
+            format!("Assembly Error: Invalid parameter `{}` for `{}` on line {}", param, mnemonic, line)
+        },
+        Error::AssemblyErrorBadEscape(line, escape) => {
+            format!("Assembly Error: Invalid escape sequence `\\{}` on line {}", escape, line)
+        },
+        Error::AssemblyErrorUnterminatedString(line) => {
+            format!("Assembly Error: Unterminated string literal on line {}", line)
+        },
+        Error::AssemblyErrorUnexpectedToken(line, token) => {
+            format!("Assembly Error: Unexpected `{}` on line {}", token, line)
+        },
+        Error::AssemblyErrorUnexpectedEOF(line) => {
+            format!("Assembly Error: Unexpected end of file in the block starting on line {}", line)
+        },
+        Error::LinkErrorUnresolvedImport(a, b) => {
+            format!("Link Error: No module exports the import {}", int_pair_to_str(&a, &b))
+        },
+        Error::LinkErrorDuplicateExport(a, b) => {
+            format!("Link Error: More than one module exports {}", int_pair_to_str(&a, &b))
+        },
+        Error::LinkErrorImportTypeMismatch(a, b, t1, t2) => {
+            format!("Link Error: {} is exported with type {} but imported with type {}", int_pair_to_str(&a, &b), t1.pretty_named(names), t2.pretty_named(names))
+        },
+        Error::BuilderErrorUnknownLabel(label) => {
+            format!("Builder Error: Function {} was never declared", label)
+        },
+        Error::BuilderErrorImportDefined(label) => {
+            format!("Builder Error: Function {} is imported, so it can't be defined", label)
+        },
+        Error::BuilderErrorBadTerminator(label) => {
+            format!("Builder Error: The body of function {} must end with its only `call`, `call_nz`, `case`, `switch`, or `halt`", label)
+        },
+        Error::BuilderErrorRedefined(label) => {
+            format!("Builder Error: Function {} is defined more than once", label)
+        },
+        Error::BuilderErrorUndefined(label) => {
+            format!("Builder Error: Function {} is declared but never defined", label)
+        },
+        Error::TooBigForHandler(pos, op, size) => {
+            format!("Type Error: Too big for an exception handler at pos {} for opcode {}: the arguments take {} bytes but at most 16 fit", pos, op.pretty(), size)
+        },
+        Error::RegionUsedByHandler(pos, op, r) => {
+            format!("Region Error: region {} can't be freed at pos {} for opcode {}, because the exception handler's arguments use it", r.pretty_named(names), pos, op.pretty())
+        },
+        Error::TypeErrorVariantExpected(pos, op, t) => {
+            format!("Type Error: Expected variant type at pos {} for opcode {} but found {}", pos, op.pretty(), t.pretty_named(names))
+        },
+        Error::TypeErrorInjOutOfRange(pos, i, max) => {
+            format!("Type Error: inj out of range at pos {}: the variant has {} components but got {}", pos, max, i)
+        },
+        Error::TypeErrorCaseArityMismatch(pos, n, max) => {
+            format!("Type Error: case arity mismatch at pos {}: the variant has {} components but got {} functions", pos, max, n)
+        },
+        Error::NoHandler(pos, op) => {
+            format!("Handler Error: opcode {} at pos {} can fault, but the function hasn't installed an exception handler with `catch` yet", op.pretty(), pos)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", msg(self.clone(), None, None))
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// The code of the error, which stays the same across SaberVM versions,
+    /// unlike the wording of the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::SyntaxErrorParamNeeded(..) => "E0001",
+            Error::SyntaxErrorUnknownOp(..) => "E0002",
+            Error::SyntaxErrorNotAModule => "E0003",
+            Error::SyntaxErrorUnsupportedVersion(..) => "E0004",
+            Error::SyntaxErrorBadDebugSection => "E0005",
+            Error::SyntaxErrorBadNamesSection => "E0006",
+            Error::TypeErrorMainHasArgs => "E0007",
+            Error::TypeErrorNonEmptyQuantificationStack(..) => "E0008",
+            Error::TypeErrorEmptyQuantificationStack(..) => "E0009",
+            Error::TypeErrorEmptyCTStack(..) => "E0010",
+            Error::TypeErrorEmptyStack(..) => "E0011",
+            Error::KindError(..) => "E0012",
+            Error::RegionError(..) => "E0013",
+            Error::TypeError(..) => "E0014",
+            Error::SizeError(..) => "E0015",
+            Error::UniquenessError(..) => "E0016",
+            Error::RegionAccessError(..) => "E0017",
+            Error::TypeErrorSpecificTypeVarExpected(..) => "E0018",
+            Error::TypeErrorTypeVarExpected(..) => "E0019",
+            Error::TypeErrorCTGetOutOfRange(..) => "E0020",
+            Error::TypeErrorGetOutOfRange(..) => "E0021",
+            Error::TypeErrorInitOutOfRange(..) => "E0022",
+            Error::TypeErrorProjOutOfRange(..) => "E0023",
+            Error::TypeErrorExistentialExpected(..) => "E0024",
+            Error::TypeErrorInitTypeMismatch(..) => "E0025",
+            Error::TypeErrorTupleExpected(..) => "E0026",
+            Error::TypeErrorFunctionExpected(..) => "E0027",
+            Error::TypeErrorRegionHandleExpected(..) => "E0028",
+            Error::TypeErrorNotEnoughRuntimeArgs(..) => "E0029",
+            Error::TypeErrorCallArgTypesMismatch(..) => "E0030",
+            Error::TypeErrorMallocNonTuple(..) => "E0031",
+            Error::TypeErrorPtrExpected(..) => "E0032",
+            Error::TypeErrorForallExpected(..) => "E0033",
+            Error::TypeErrorForallRegionExpected(..) => "E0034",
+            Error::KindErrorBadApp(..) => "E0035",
+            Error::TypeErrorDoubleInit(..) => "E0036",
+            Error::TypeErrorUninitializedRead(..) => "E0037",
+            Error::TooBigForStack(..) => "E0038",
+            Error::ForwardDeclNotType(..) => "E0039",
+            Error::ForwardDeclRuntimeOp(..) => "E0040",
+            Error::ForwardDeclBadStack(..) => "E0041",
+            Error::UnknownGlobalFunc(..) => "E0042",
+            Error::UnexpectedEOF => "E0043",
+            Error::TypeErrorArrayExpected(..) => "E0044",
+            Error::ReadOnlyRegionError(..) => "E0045",
+            Error::DataSectionLoadOutOfBounds(..) => "E0046",
+            Error::InvalidDataSectionType(..) => "E0047",
+            Error::CannotMutateDataSection(..) => "E0048",
+            Error::UnknownChannel(..) => "E0049",
+            Error::AssemblyErrorUnknownMnemonic(..) => "E0050",
+            Error::AssemblyErrorParamNeeded(..) => "E0051",
+            Error::AssemblyErrorBadParam(..) => "E0052",
+            Error::AssemblyErrorBadEscape(..) => "E0053",
+            Error::AssemblyErrorUnterminatedString(..) => "E0054",
+            Error::AssemblyErrorUnexpectedToken(..) => "E0055",
+            Error::AssemblyErrorUnexpectedEOF(..) => "E0056",
+            Error::LinkErrorUnresolvedImport(..) => "E0057",
+            Error::LinkErrorDuplicateExport(..) => "E0058",
+            Error::LinkErrorImportTypeMismatch(..) => "E0059",
+            Error::BuilderErrorUnknownLabel(..) => "E0060",
+            Error::BuilderErrorImportDefined(..) => "E0061",
+            Error::BuilderErrorBadTerminator(..) => "E0062",
+            Error::BuilderErrorRedefined(..) => "E0063",
+            Error::BuilderErrorUndefined(..) => "E0064",
+            Error::TooBigForHandler(..) => "E0065",
+            Error::RegionUsedByHandler(..) => "E0066",
+            Error::TypeErrorVariantExpected(..) => "E0067",
+            Error::TypeErrorInjOutOfRange(..) => "E0068",
+            Error::TypeErrorCaseArityMismatch(..) => "E0069",
+            Error::NoHandler(..) => "E0070",
+        }
+    }
+
+    /// The op the error is about, if it's about one.
+    pub fn op(&self) -> Option<Op1> {
+        match self {
+            Error::TypeErrorEmptyQuantificationStack(_, op) |
+            Error::TypeErrorEmptyCTStack(_, op) |
+            Error::TypeErrorEmptyStack(_, op) |
+            Error::KindError(_, op, ..) |
+            Error::RegionError(_, op, ..) |
+            Error::TypeError(_, op, ..) |
+            Error::SizeError(_, op, ..) |
+            Error::UniquenessError(_, op, ..) |
+            Error::RegionAccessError(_, op, ..) |
+            Error::TypeErrorSpecificTypeVarExpected(_, op, ..) |
+            Error::TypeErrorTypeVarExpected(_, op, ..) |
+            Error::TypeErrorExistentialExpected(_, op, ..) |
+            Error::TypeErrorTupleExpected(_, op, ..) |
+            Error::TypeErrorFunctionExpected(_, op, ..) |
+            Error::TypeErrorRegionHandleExpected(_, op, ..) |
+            Error::TypeErrorMallocNonTuple(_, op, ..) |
+            Error::TypeErrorPtrExpected(_, op, ..) |
+            Error::TypeErrorForallExpected(_, op, ..) |
+            Error::TypeErrorForallRegionExpected(_, op, ..) |
+            Error::KindErrorBadApp(_, op, ..) |
+            Error::TypeErrorDoubleInit(_, op, ..) |
+            Error::TypeErrorUninitializedRead(_, op, ..) |
+            Error::TooBigForStack(_, op, ..) |
+            Error::ForwardDeclRuntimeOp(op) |
+            Error::UnknownGlobalFunc(_, op, ..) |
+            Error::TypeErrorArrayExpected(_, op, ..) |
+            Error::ReadOnlyRegionError(_, op, ..) |
+            Error::DataSectionLoadOutOfBounds(_, op, ..) |
+            Error::InvalidDataSectionType(_, op, ..) |
+            Error::CannotMutateDataSection(_, op) |
+            Error::UnknownChannel(_, op, ..) |
+            Error::TooBigForHandler(_, op, ..) |
+            Error::RegionUsedByHandler(_, op, ..) |
+            Error::TypeErrorVariantExpected(_, op, ..) |
+            Error::NoHandler(_, op) => Some(*op),
+            Error::TypeErrorCTGetOutOfRange(_, i, _) => Some(Op1::CTGet(*i)),
+            Error::TypeErrorGetOutOfRange(_, i, _) => Some(Op1::Get(*i)),
+            Error::TypeErrorInitOutOfRange(_, i, _) => Some(Op1::Init(*i)),
+            Error::TypeErrorProjOutOfRange(_, i, _) => Some(Op1::Proj(*i)),
+            Error::TypeErrorInjOutOfRange(_, i, _) => Some(Op1::Inj(*i)),
+            Error::TypeErrorCaseArityMismatch(_, n, _) => Some(Op1::Case(*n)),
+            _ => None,
+        }
+    }
+
+    /// What the op needed, if the error says.
+    pub fn expected(&self) -> Option<Term> {
+        match self {
+            Error::SyntaxErrorUnsupportedVersion(_) => Some(Term::Number(FORMAT_VERSION.into())),
+            Error::KindError(_, _, kind, _) => Some(Term::Kind(kind.clone())),
+            Error::RegionError(_, _, r, _) |
+            Error::UniquenessError(_, _, r) |
+            Error::RegionAccessError(_, _, r) => Some(Term::Region(*r)),
+            Error::TypeError(_, _, t, _) |
+            Error::TypeErrorInitTypeMismatch(_, t, _) |
+            Error::LinkErrorImportTypeMismatch(_, _, t, _) => Some(Term::Type(t.clone())),
+            Error::SizeError(_, _, s, _) |
+            Error::TypeErrorCTGetOutOfRange(_, _, s) |
+            Error::TypeErrorGetOutOfRange(_, _, s) |
+            Error::TypeErrorInitOutOfRange(_, _, s) |
+            Error::TypeErrorProjOutOfRange(_, _, s) |
+            Error::TypeErrorInjOutOfRange(_, _, s) |
+            Error::TypeErrorCaseArityMismatch(_, _, s) |
+            Error::TypeErrorNotEnoughRuntimeArgs(_, s, _) |
+            Error::DataSectionLoadOutOfBounds(_, _, _, s) => Some(Term::Number(*s as u64)),
+            Error::TypeErrorSpecificTypeVarExpected(_, _, id, _) |
+            Error::TypeErrorTypeVarExpected(_, _, id, _) => Some(Term::TypeVar(*id)),
+            Error::TypeErrorCallArgTypesMismatch(_, ts, _) => Some(Term::Types(ts.clone())),
+            Error::TooBigForHandler(..) => Some(Term::Number(16)),
+            _ => None,
+        }
+    }
+
+    /// What the op found instead, if the error says.
+    pub fn found(&self) -> Option<Term> {
+        match self {
+            Error::SyntaxErrorUnknownOp(_, byte) => Some(Term::Number((*byte).into())),
+            Error::SyntaxErrorUnsupportedVersion(version) => Some(Term::Number((*version).into())),
+            Error::KindError(_, _, _, ctval) |
+            Error::KindErrorBadApp(_, _, ctval) => Some(Term::Kind(ctval.kind())),
+            Error::RegionError(_, _, _, r) => Some(Term::Region(*r)),
+            Error::TypeError(_, _, _, t) |
+            Error::TypeErrorTypeVarExpected(_, _, _, t) |
+            Error::TypeErrorExistentialExpected(_, _, t) |
+            Error::TypeErrorInitTypeMismatch(_, _, t) |
+            Error::TypeErrorTupleExpected(_, _, t) |
+            Error::TypeErrorFunctionExpected(_, _, t) |
+            Error::TypeErrorRegionHandleExpected(_, _, t) |
+            Error::TypeErrorMallocNonTuple(_, _, t) |
+            Error::TypeErrorPtrExpected(_, _, t) |
+            Error::TypeErrorForallExpected(_, _, t) |
+            Error::TypeErrorForallRegionExpected(_, _, t) |
+            Error::TooBigForStack(_, _, t) |
+            Error::ForwardDeclNotType(t) |
+            Error::TypeErrorArrayExpected(_, _, t) |
+            Error::InvalidDataSectionType(_, _, t) |
+            Error::TypeErrorVariantExpected(_, _, t) |
+            Error::LinkErrorImportTypeMismatch(_, _, _, t) => Some(Term::Type(t.clone())),
+            Error::SizeError(_, _, _, s) |
+            Error::TypeErrorNotEnoughRuntimeArgs(_, _, s) |
+            Error::DataSectionLoadOutOfBounds(_, _, s, _) |
+            Error::TooBigForHandler(_, _, s) => Some(Term::Number(*s as u64)),
+            Error::RegionUsedByHandler(_, _, r) => Some(Term::Region(*r)),
+            Error::TypeErrorCTGetOutOfRange(_, i, _) |
+            Error::TypeErrorGetOutOfRange(_, i, _) |
+            Error::TypeErrorInitOutOfRange(_, i, _) |
+            Error::TypeErrorProjOutOfRange(_, i, _) |
+            Error::TypeErrorInjOutOfRange(_, i, _) |
+            Error::TypeErrorCaseArityMismatch(_, i, _) |
+            Error::UnknownChannel(_, _, i) => Some(Term::Number((*i).into())),
+            Error::UnknownGlobalFunc(_, _, label) => Some(Term::Number((*label).into())),
+            Error::TypeErrorSpecificTypeVarExpected(_, _, _, id) => Some(Term::TypeVar(*id)),
+            Error::TypeErrorCallArgTypesMismatch(_, _, ts) => Some(Term::Types(ts.clone())),
+            Error::ForwardDeclBadStack(ctvals) => Some(Term::CompileTimeStack(ctvals.clone())),
+            _ => None,
+        }
+    }
+
+    /// The position of the op the error is about, if it's about one.
+    pub fn pos(&self) -> Option<Pos> {
+        match self {
+            Error::SyntaxErrorParamNeeded(pos, ..) |
+            Error::SyntaxErrorUnknownOp(pos, ..) |
+            Error::TypeErrorEmptyQuantificationStack(pos, ..) |
+            Error::TypeErrorEmptyCTStack(pos, ..) |
+            Error::TypeErrorEmptyStack(pos, ..) |
+            Error::KindError(pos, ..) |
+            Error::RegionError(pos, ..) |
+            Error::TypeError(pos, ..) |
+            Error::SizeError(pos, ..) |
+            Error::UniquenessError(pos, ..) |
+            Error::RegionAccessError(pos, ..) |
+            Error::TypeErrorSpecificTypeVarExpected(pos, ..) |
+            Error::TypeErrorTypeVarExpected(pos, ..) |
+            Error::TypeErrorCTGetOutOfRange(pos, ..) |
+            Error::TypeErrorGetOutOfRange(pos, ..) |
+            Error::TypeErrorInitOutOfRange(pos, ..) |
+            Error::TypeErrorProjOutOfRange(pos, ..) |
+            Error::TypeErrorExistentialExpected(pos, ..) |
+            Error::TypeErrorInitTypeMismatch(pos, ..) |
+            Error::TypeErrorTupleExpected(pos, ..) |
+            Error::TypeErrorFunctionExpected(pos, ..) |
+            Error::TypeErrorRegionHandleExpected(pos, ..) |
+            Error::TypeErrorNotEnoughRuntimeArgs(pos, ..) |
+            Error::TypeErrorCallArgTypesMismatch(pos, ..) |
+            Error::TypeErrorMallocNonTuple(pos, ..) |
+            Error::TypeErrorPtrExpected(pos, ..) |
+            Error::TypeErrorForallExpected(pos, ..) |
+            Error::TypeErrorForallRegionExpected(pos, ..) |
+            Error::KindErrorBadApp(pos, ..) |
+            Error::TypeErrorDoubleInit(pos, ..) |
+            Error::TypeErrorUninitializedRead(pos, ..) |
+            Error::TooBigForStack(pos, ..) |
+            Error::UnknownGlobalFunc(pos, ..) |
+            Error::TypeErrorArrayExpected(pos, ..) |
+            Error::ReadOnlyRegionError(pos, ..) |
+            Error::DataSectionLoadOutOfBounds(pos, ..) |
+            Error::InvalidDataSectionType(pos, ..) |
+            Error::CannotMutateDataSection(pos, ..) |
+            Error::UnknownChannel(pos, ..) |
+            Error::TooBigForHandler(pos, ..) |
+            Error::RegionUsedByHandler(pos, ..) |
+            Error::TypeErrorVariantExpected(pos, ..) |
+            Error::TypeErrorInjOutOfRange(pos, ..) |
+            Error::TypeErrorCaseArityMismatch(pos, ..) |
+            Error::NoHandler(pos, ..) => Some(*pos),
+            _ => None,
         }
     }
-}
\ No newline at end of file
+}
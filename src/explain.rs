@@ -0,0 +1,1384 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Longer explanations of the errors, by their codes, for `sabervm explain`.
+//!
+//! Every explanation has a module with the error and the same module fixed.
+//! They're in the text format `assemble::go` reads, except for the errors that format can't make:
+//! malformed binaries are bytes in hexadecimal, and builder errors are Rust.
+
+use std::fmt;
+
+/// What an explanation's examples are written in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExampleKind {
+    /// A module in the text format.
+    Assembly,
+    /// Several modules in the text format, each after a `# <file>` line, to link together.
+    Modules,
+    /// A module in the binary format, as bytes in hexadecimal.
+    Bytes,
+    /// Rust that builds a module with the `builder` module.
+    Rust,
+}
+
+/// The longer explanation of an error.
+pub struct Explanation {
+    pub code: &'static str,
+    /// What the error means and what usually causes it.
+    pub description: &'static str,
+    pub kind: ExampleKind,
+    /// Something with the error.
+    pub example: &'static str,
+    /// The example without the error.
+    pub fixed: &'static str,
+}
+
+/// The explanation of the error with the given code, like `E0012` or `e0012`.
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS.iter().find(|explanation| explanation.code.eq_ignore_ascii_case(code))
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (example, fixed) = match self.kind {
+            ExampleKind::Assembly => ("This module has the error:", "This one doesn't:"),
+            ExampleKind::Modules => ("Linking these modules has the error:", "Linking these doesn't:"),
+            ExampleKind::Bytes => ("This module, in hexadecimal, has the error:", "This one doesn't:"),
+            ExampleKind::Rust => ("This Rust has the error:", "This doesn't:"),
+        };
+        let indent = |text: &str| {
+            text.lines()
+                .map(|line| if line.is_empty() { String::new() } else { "    ".to_string() + line })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        writeln!(f, "{}\n", self.code)?;
+        writeln!(f, "{}\n", self.description)?;
+        writeln!(f, "{}\n", example)?;
+        writeln!(f, "{}\n", indent(self.example))?;
+        writeln!(f, "{}\n", fixed)?;
+        writeln!(f, "{}", indent(self.fixed))
+    }
+}
+
+/// The explanations, in the order of their codes.
+pub static EXPLANATIONS: [Explanation; 70] = [
+    Explanation {
+        code: "E0001",
+        description: r#"An op that takes a parameter is the last thing in the module, so its parameter is missing.
+This usually means the module was cut off, or a parameter was written with fewer bytes than the op takes."#,
+        kind: ExampleKind::Bytes,
+        example: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09"#,
+        fixed: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09 00 0b 27 00 15"#,
+    },
+    Explanation {
+        code: "E0002",
+        description: r#"A byte where an op should start isn't any op's opcode.
+This usually means a parameter was written with the wrong number of bytes, so the ops after it are out of step,
+or the module was made for a different version of the VM."#,
+        kind: ExampleKind::Bytes,
+        example: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09 00 0b 27 00 ff"#,
+        fixed: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09 00 0b 27 00 15"#,
+    },
+    Explanation {
+        code: "E0003",
+        description: r#"The bytes don't start with `SVM\0`, so they aren't a SaberVM module.
+Every module starts with those four bytes, then the format version."#,
+        kind: ExampleKind::Bytes,
+        example: r#"00 00 00 00 00 00 00 00 00 00 00 00 01 00 00 00
+09 00 0b 27 00 15"#,
+        fixed: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09 00 0b 27 00 15"#,
+    },
+    Explanation {
+        code: "E0004",
+        description: r#"The module is in a format version this VM can't read.
+The version is the little-endian 32-bit number after `SVM\0`; this VM reads version 4.
+Make the module again with this version's assembler or compiler."#,
+        kind: ExampleKind::Bytes,
+        example: r#"53 56 4d 00 03 00 00 00 00 00 00 00 00 00 00 00
+01 00 00 00 09 00 0b 27 00 15"#,
+        fixed: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09 00 0b 27 00 15"#,
+    },
+    Explanation {
+        code: "E0005",
+        description: r#"The debug section is malformed.
+It's the number of file names and each file name, then the number of source locations and each as
+the op's position, the file's index, the line and the column, with the positions in increasing order.
+The section has to be exactly as long as its length says, and every file index has to name a file."#,
+        kind: ExampleKind::Bytes,
+        example: r#"53 56 4d 00 04 00 00 00 00 00 00 00 04 00 00 00
+01 00 00 00 00 00 00 00 01 00 00 00 09 00 0b 27
+00 15"#,
+        fixed: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09 00 0b 27 00 15"#,
+    },
+    Explanation {
+        code: "E0006",
+        description: r#"The names section is malformed.
+It's the number of function names and each as the function's label and the name,
+then the number of variable names and each as the position of the op that introduces the variable and the name.
+The labels and positions have to be in increasing order, every label has to be one of the module's functions,
+every name has to be UTF-8, and the section has to be exactly as long as its length says."#,
+        kind: ExampleKind::Bytes,
+        example: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+10 00 00 00 01 00 00 00 01 00 00 00 00 00 00 00
+00 00 00 00 01 00 00 00 09 00 0b 27 00 15"#,
+        fixed: r#"53 56 4d 00 04 00 00 00 00 00 00 00 00 00 00 00
+14 00 00 00 01 00 00 00 00 00 00 00 04 00 00 00
+6d 61 69 6e 00 00 00 00 01 00 00 00 09 00 0b 27
+00 15"#,
+    },
+    Explanation {
+        code: "E0007",
+        description: r#"The first function in the module is the program's entry point, so it's called with nothing on the stack,
+and its type can't take any arguments."#,
+        kind: ExampleKind::Assembly,
+        example: r#"i32; func 1; lced
+
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+lit 0; u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0008",
+        description: r#"A function body introduced a type variable or region with `all`, `some`, or `rgn`
+and never closed it with `end`.
+Every quantifier opened in a body has to be closed before the body ends."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+size 4; all
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+size 4; all; ctget 0; func 1; end
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0009",
+        description: r#"An `end` has no `all`, `some`, or `rgn` left to close.
+Each `end` closes the innermost open quantifier, so there can't be more `end`s than quantifiers."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+size 4; all; ctget 0; func 1; end; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+size 4; all; ctget 0; func 1; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0010",
+        description: r#"An op needed more types, regions, or sizes than there were on the compile-time stack.
+Ops like `tuple n` and `func n` take their components from the compile-time stack,
+so everything they take has to be pushed first."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; tuple 2; malloc
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; i32; tuple 2; malloc
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0011",
+        description: r#"An op needed more values than there were on the stack.
+A function's stack starts with its arguments, and every value an op takes has to be pushed first."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+halt"#,
+        fixed: r#"func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0012",
+        description: r#"Something on the compile-time stack is of the wrong kind: a type where a region was needed, for example.
+The compile-time stack holds types, regions, and sizes, and each op says which kinds it takes."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; handle; func 1; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+rgn; ctget 0; handle; func 1; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0013",
+        description: r#"Two regions that have to be the same aren't.
+For example, `malloc` allocates in the region of the handle on the stack,
+so the pointer type it's given has to be in that same region."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+new_rgn 64; new_rgn 64
+ctget 1; i32; tuple 1; ptr; malloc
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 64; new_rgn 64
+ctget 0; i32; tuple 1; ptr; malloc
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0014",
+        description: r#"A value on the stack has the wrong type for the op that uses it.
+The message says which type was expected and which was found."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0015",
+        description: r#"A type has the wrong size.
+Type variables are declared with a size, so a type can only stand in for a variable of the same size.
+`i32` is 4 bytes, `u8` is 1, handles are 8, pointers and arrays are 16, and functions are 4."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+size 8; all; ctget 0; func 1; end; lced
+
+lit 5; i32; global_func 1; call
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+size 4; all; ctget 0; func 1; end; lced
+
+lit 5; i32; global_func 1; call
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0016",
+        description: r#"An op needs a unique region, but the region isn't unique.
+Only a unique region, one nothing else can refer to, can be freed.
+Regions made with `new_rgn` are unique, and a `rgn` is unique if it comes right after `unique`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+rgn; ctget 0; handle; func 1; end; lced
+
+u8_lit 0; halt
+free_rgn; u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+unique; rgn; ctget 0; handle; func 1; end; lced
+
+u8_lit 0; halt
+free_rgn; u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0017",
+        description: r#"A region is used where it can't be accessed, most often after it was freed with `free_rgn`.
+A function can only access the regions it was given, the regions it made with `new_rgn`, and the data section."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+new_rgn 64; get 0; free_rgn
+ctget 0; i32; tuple 1; ptr; malloc
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 64
+ctget 0; i32; tuple 1; ptr; get 0; malloc
+get 1; free_rgn
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0018",
+        description: r#"An `end` closed a type variable, but the variable under the quantified type is a different one.
+`end` expects the compile-time stack to have the variable its `all` or `some` introduced,
+with the type to quantify over it on top; this is usually a `ctget` with the wrong index."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+size 4; all; size 4; all; ctget 1; ctget 1; func 1; end; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+size 4; all; size 4; all; ctget 1; ctget 1; func 2; end; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0019",
+        description: r#"An `end` closed a type variable, but there's a type under the quantified type instead of the variable.
+`end` expects the compile-time stack to have the variable its `all` or `some` introduced,
+with the type to quantify over it on top, so every type pushed in between has to be used up first."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+size 4; all; i32; ctget 1; func 1; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+size 4; all; i32; ctget 1; func 2; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0020",
+        description: r#"A `ctget` reaches past the bottom of the compile-time stack.
+`ctget 0` copies the top of the compile-time stack, `ctget 1` the one under it, and so on."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; ctget 1; func 2; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+i32; ctget 0; func 2; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0021",
+        description: r#"A `get` reaches past the bottom of the stack.
+`get 0` copies the top of the stack, `get 1` the value under it, and so on."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+lit 1; get 1
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+lit 1; get 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0022",
+        description: r#"An `init` names a component past the end of the tuple.
+The components of a tuple with `n` components are numbered from 0 to `n - 1`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 3; init 1
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 3; init 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0023",
+        description: r#"A `proj` names a component past the end of the tuple.
+The components of a tuple with `n` components are numbered from 0 to `n - 1`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 3; init 0; proj 1
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 3; init 0; proj 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0024",
+        description: r#"An op needs an existential package, made with `pack`, but found something else.
+`unpack` only works on packages, and `read` and `write` take their continuation as one."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 3; init 0
+unpack
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 3; init 0
+size 4; some; ctget 0; tuple 1; end; i32; pack
+unpack
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0025",
+        description: r#"An `init` stores a value of the wrong type in a tuple component.
+The value has to have the type the tuple was allocated with."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; tuple 1; malloc; u8_lit 3; init 0
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 3; init 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0026",
+        description: r#"An op needs a tuple, or a pointer to a tuple, but found something else.
+`init` and `proj` only work on tuples, and `malloc` only allocates tuples and arrays behind pointers."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+lit 1; proj 0
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 1; init 0; proj 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0027",
+        description: r#"A `call`, `call_nz`, `case`, or `switch` found something other than a function on top of the stack.
+Functions are pushed with `global_func`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+func 0; lced
+
+lit 1; call
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; call
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0028",
+        description: r#"An op needs a region handle but found something else.
+Handles come from `new_rgn`, or from a function's arguments."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+lit 1; free_rgn
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+new_rgn 64; free_rgn
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0029",
+        description: r#"A function was called with fewer arguments than its type takes.
+The arguments go on the stack before the function, with the last argument right under it."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; func 1; lced
+
+global_func 1; call
+lit 0; u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+i32; func 1; lced
+
+lit 1; global_func 1; call
+lit 0; u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0030",
+        description: r#"A function was called with arguments of the wrong types.
+The message lists the types the function takes and the types that were on the stack."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; func 1; lced
+
+u8_lit 1; global_func 1; call
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+i32; func 1; lced
+
+lit 1; global_func 1; call
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0031",
+        description: r#"A `malloc` was given a pointer to something other than a tuple.
+Values in a region are always tuples, so a pointer to a single value has to be a pointer to a tuple of one."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+new_rgn 64; ctget 0; i32; ptr; malloc
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 64; ctget 0; i32; tuple 1; ptr; malloc
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0032",
+        description: r#"A `deref` found something other than a pointer on top of the stack."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+lit 1; deref
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 64; ctget 0; i32; tuple 1; ptr; malloc; lit 1; init 0; deref
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0033",
+        description: r#"An `app`, or a `call` given a type argument, found a function that isn't polymorphic over types.
+Only a function whose type starts with `all` can be applied to a type."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; func 1; lced
+
+lit 1; i32; global_func 1; app; call
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+size 4; all; ctget 0; func 1; end; lced
+
+lit 1; i32; global_func 1; app; call
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0034",
+        description: r#"An `app` was given a region, but the function isn't polymorphic over regions.
+Only a function whose type starts with `rgn` can be applied to a region."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+func 0; lced
+
+new_rgn 64; global_func 1; app; call
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+rgn; func 0; end; lced
+
+new_rgn 64; global_func 1; app; call
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0035",
+        description: r#"An `app` found something on the compile-time stack that a function can't be applied to.
+Functions can be applied to types and regions, but not to sizes."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+size 4; all; ctget 0; func 1; end; lced
+
+lit 1; size 4; global_func 1; app; call
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+size 4; all; ctget 0; func 1; end; lced
+
+lit 1; i32; global_func 1; app; call
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0036",
+        description: r#"A tuple component was initialized twice.
+Each component of a newly allocated tuple is initialized exactly once, with `init`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 1; init 0; lit 2; init 0
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; i32; tuple 2; malloc; lit 1; init 0; lit 2; init 1
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0037",
+        description: r#"A `proj` reads a tuple component before it's initialized.
+A newly allocated tuple's components have to be set with `init` before they can be read."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; tuple 1; malloc; proj 0
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; tuple 1; malloc; lit 1; init 0; proj 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0038",
+        description: r#"A value is too big to keep on the stack: the limit is 4096 bytes.
+Bigger tuples have to be allocated in a region and used through a pointer.
+Array elements have the same limit, since `arr_proj` puts one on the stack."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; tuple 2; malloc
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 16384
+i32; ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; ctget 0; ctget 0; tuple 4
+ctget 0; tuple 2; ptr; malloc
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0039",
+        description: r#"A forward declaration's type isn't a function type.
+Every function is declared with a `func` type, possibly under `all` and `rgn` quantifiers."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; lced
+
+u8_lit 0; halt
+lit 0; u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+i32; func 1; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0040",
+        description: r#"A forward declaration has an op that only makes sense at runtime.
+Forward declarations only build the function's type, so they can only have compile-time ops."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+u8_lit 0; func 1; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+u8; func 1; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0041",
+        description: r#"A forward declaration doesn't leave exactly one type on the compile-time stack.
+The declaration's ops build the function's type, so everything else they push has to be used up."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; func 0; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+i32; func 1; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0042",
+        description: r#"A `global_func` names a label that no function in the module has.
+Functions are labeled from 0 in the order of their forward declarations."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+global_func 1; call"#,
+        fixed: r#"func 0; lced
+
+global_func 0; call"#,
+    },
+    Explanation {
+        code: "E0043",
+        description: r#"The module ended in the middle of a function.
+Every forward declaration ends with `lced`, `export`, or `import`,
+and every function body ends with `call`, `call_nz`, `case`, `switch`, or `halt`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+u8_lit 0"#,
+        fixed: r#"func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0044",
+        description: r#"An op needs an array but found something else.
+Arrays are allocated with `malloc` on an `arr` type, or come from the data section."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+lit 0; lit 0; arr_proj
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 64; ctget 0; u8; arr; get 0; lit 4; malloc
+lit 0; arr_proj
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0045",
+        description: r#"The data section is read-only, so it can't be used where a region has to be writable:
+it has no handle, and the values in it can't be read through pointers with `proj`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+data_sec; handle; func 1; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+rgn; ctget 0; handle; func 1; end; lced
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0046",
+        description: r#"A `data` loads a value that doesn't fit in the data section.
+The value starts at the given byte offset and takes the size of its type, and an array has to start within the data section."#,
+        kind: ExampleKind::Assembly,
+        example: r#"data_section { 1 0 0 0 }
+
+func 0; lced
+
+i32; tuple 1; data 4
+u8_lit 0; halt"#,
+        fixed: r#"data_section { 1 0 0 0 }
+
+func 0; lced
+
+i32; tuple 1; data 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0047",
+        description: r#"A `data` loads a type that can't be in the data section.
+The data section can have numbers and tuples of them, and `data` can give an array of those in the data section.
+Pointers, handles, and functions can't be made up from its bytes."#,
+        kind: ExampleKind::Assembly,
+        example: r#"data_section { "hi" }
+
+func 0; lced
+
+data_sec; u8; func 1; arr; data 0
+u8_lit 0; halt"#,
+        fixed: r#"data_section { "hi" }
+
+func 0; lced
+
+data_sec; u8; arr; data 0
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0048",
+        description: r#"An op writes to an array in the data section, which is read-only.
+Copy the array into a region with `copy_n` to change it."#,
+        kind: ExampleKind::Assembly,
+        example: r#"data_section { "hi" }
+
+func 0; lced
+
+data_sec; u8; arr; data 0
+u8_lit 72; lit 0; arr_mut
+u8_lit 0; halt"#,
+        fixed: r#"data_section { "hi" }
+
+func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 64; ctget 0; u8; arr; get 0; lit 2; malloc
+u8_lit 72; lit 0; arr_mut
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0049",
+        description: r#"A `read` or `write` names a channel that doesn't exist.
+The only channel is 0, the console."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+rgn; ctget 0; tuple 0; ptr; func 1; end; lced
+func 0; lced
+
+global_func 2; catch
+new_rgn 64; get 0
+ctget 0; u8; arr; lit 2; malloc
+u8_lit 72; lit 0; arr_mut
+u8_lit 10; lit 1; arr_mut
+ctget 0; tuple 0; ptr; ctget 1; tuple 0; ptr; func 1; tuple 2; malloc
+get 2; ctget 0; tuple 0; ptr; malloc; init 1
+ctget 0; global_func 1; app; init 0
+size 16; some; ctget 0; ctget 1; func 1; tuple 2; end
+ctget 1; tuple 0; ptr; pack
+u8_lit 0; get 3
+write 1
+u8_lit 0; halt
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+        fixed: r#"func 0; lced
+rgn; ctget 0; tuple 0; ptr; func 1; end; lced
+func 0; lced
+
+global_func 2; catch
+new_rgn 64; get 0
+ctget 0; u8; arr; lit 2; malloc
+u8_lit 72; lit 0; arr_mut
+u8_lit 10; lit 1; arr_mut
+ctget 0; tuple 0; ptr; ctget 1; tuple 0; ptr; func 1; tuple 2; malloc
+get 2; ctget 0; tuple 0; ptr; malloc; init 1
+ctget 0; global_func 1; app; init 0
+size 16; some; ctget 0; ctget 1; func 1; tuple 2; end
+ctget 1; tuple 0; ptr; pack
+u8_lit 0; get 3
+write 0
+u8_lit 0; halt
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0050",
+        description: r#"The assembler doesn't know the op on this line.
+Mnemonics are the op names in lower case, with words separated by underscores."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+u8lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0051",
+        description: r#"An op that takes a parameter doesn't have one."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+u8_lit; halt"#,
+        fixed: r#"func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0052",
+        description: r#"An op's parameter isn't valid for it: a number that doesn't fit in the parameter, for example,
+or a string where a number is needed."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+u8_lit 256; halt"#,
+        fixed: r#"func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0053",
+        description: r#"A string has an escape the assembler doesn't know.
+The escapes are `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, and `\x` followed by two hexadecimal digits."#,
+        kind: ExampleKind::Assembly,
+        example: r#"data_section { "C:\data" }
+
+func 0; lced
+
+u8_lit 0; halt"#,
+        fixed: r#"data_section { "C:\\data" }
+
+func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0054",
+        description: r#"A string doesn't end before the end of its line.
+Strings can't span lines; use `\n` for a line break."#,
+        kind: ExampleKind::Assembly,
+        example: r#"data_section { "hello }
+
+func 0; lced
+
+u8_lit 0; halt"#,
+        fixed: r#"data_section { "hello" }
+
+func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0055",
+        description: r#"The assembler found something it didn't expect, like a second parameter or a `{` outside the data section.
+Ops are separated by `;` or line breaks."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+u8_lit 0 halt"#,
+        fixed: r#"func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0056",
+        description: r#"The text ended in the middle of the data section.
+The data section is closed with `}`."#,
+        kind: ExampleKind::Assembly,
+        example: r#"data_section { 1 2 3"#,
+        fixed: r#"data_section { 1 2 3 }
+
+func 0; lced
+
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0057",
+        description: r#"A module imports a function that no module exports.
+Imports and exports are matched by name when the modules are linked."#,
+        kind: ExampleKind::Modules,
+        example: r#"# a.sasm
+func 0; lced
+func 0; import "greet"
+
+global_func 1; call
+
+# b.sasm
+func 0; lced
+func 0; export "great"
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"# a.sasm
+func 0; lced
+func 0; import "greet"
+
+global_func 1; call
+
+# b.sasm
+func 0; lced
+func 0; export "greet"
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0058",
+        description: r#"Two modules export functions with the same name, so an import of it would be ambiguous."#,
+        kind: ExampleKind::Modules,
+        example: r#"# a.sasm
+func 0; lced
+func 0; export "greet"
+
+u8_lit 0; halt
+u8_lit 0; halt
+
+# b.sasm
+func 0; lced
+func 0; export "greet"
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"# a.sasm
+func 0; lced
+func 0; export "greet"
+
+u8_lit 0; halt
+u8_lit 0; halt
+
+# b.sasm
+func 0; lced
+func 0; export "greet_all"
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0059",
+        description: r#"A module imports a function with a different type than the module exporting it declares.
+The import's declaration has to build the same type as the export's."#,
+        kind: ExampleKind::Modules,
+        example: r#"# a.sasm
+func 0; lced
+i32; func 1; import "greet"
+
+lit 1; global_func 1; call
+
+# b.sasm
+func 0; lced
+u8; func 1; export "greet"
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+        fixed: r#"# a.sasm
+func 0; lced
+i32; func 1; import "greet"
+
+lit 1; global_func 1; call
+
+# b.sasm
+func 0; lced
+i32; func 1; export "greet"
+
+u8_lit 0; halt
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0060",
+        description: r#"A builder method was given a label that no function in the module has.
+Labels are what `ModuleBuilder::declare` returns."#,
+        kind: ExampleKind::Rust,
+        example: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main + 1, Ops::new().u8_lit(0).halt())?;"#,
+        fixed: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main, Ops::new().u8_lit(0).halt())?;"#,
+    },
+    Explanation {
+        code: "E0061",
+        description: r#"An imported function was given a body.
+Imported functions are defined in the module that exports them."#,
+        kind: ExampleKind::Rust,
+        example: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+let greet = module.declare(Visibility::Import(0, 1), Ops::new().func(0));
+module.define(main, Ops::new().global_func(greet).call())?;
+module.define(greet, Ops::new().u8_lit(0).halt())?;"#,
+        fixed: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+let greet = module.declare(Visibility::Import(0, 1), Ops::new().func(0));
+module.define(main, Ops::new().global_func(greet).call())?;"#,
+    },
+    Explanation {
+        code: "E0062",
+        description: r#"A function body doesn't end with its only `call`, `call_nz`, `case`, `switch`, or `halt`.
+Functions never return, so every body ends by calling another function or halting, and has no ops after that."#,
+        kind: ExampleKind::Rust,
+        example: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main, Ops::new().u8_lit(0))?;"#,
+        fixed: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main, Ops::new().u8_lit(0).halt())?;"#,
+    },
+    Explanation {
+        code: "E0063",
+        description: r#"A function was given a body twice."#,
+        kind: ExampleKind::Rust,
+        example: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main, Ops::new().u8_lit(0).halt())?;
+module.define(main, Ops::new().u8_lit(1).halt())?;"#,
+        fixed: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main, Ops::new().u8_lit(1).halt())?;"#,
+    },
+    Explanation {
+        code: "E0064",
+        description: r#"A function was declared but never given a body.
+Every function that isn't imported needs one before the module is built."#,
+        kind: ExampleKind::Rust,
+        example: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+let helper = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main, Ops::new().u8_lit(0).halt())?;
+module.build()?;"#,
+        fixed: r#"let mut module = ModuleBuilder::new();
+let main = module.declare(Visibility::Local, Ops::new().func(0));
+let helper = module.declare(Visibility::Local, Ops::new().func(0));
+module.define(main, Ops::new().global_func(helper).call())?;
+module.define(helper, Ops::new().u8_lit(0).halt())?;
+module.build()?;"#,
+    },
+    Explanation {
+        code: "E0065",
+        description: r#"A `catch` installed an exception handler whose arguments take more than 16 bytes.
+The runtime keeps a handler's arguments aside until a runtime error jumps to it,
+and there's room for 16 bytes; bigger data can go in a region and be passed by pointer."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+i32; i32; i32; i32; i32; func 5; lced
+
+lit 1; lit 2; lit 3; lit 4; lit 5; global_func 1; catch
+u8_lit 0; halt
+
+i32_to_u8; halt"#,
+        fixed: r#"func 0; lced
+i32; i32; i32; i32; func 4; lced
+
+lit 1; lit 2; lit 3; lit 4; global_func 1; catch
+u8_lit 0; halt
+
+i32_to_u8; halt"#,
+    },
+    Explanation {
+        code: "E0066",
+        description: r#"A region was freed while the installed exception handler's arguments use it.
+The handler can run at any runtime error until the function calls another one,
+so everything it was given has to stay alive until then."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+rgn; ctget 0; handle; func 1; end; lced
+
+new_rgn 256
+ctget 0; get 0; global_func 1; catch
+free_rgn
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+        fixed: r#"func 0; lced
+rgn; ctget 0; handle; func 1; end; lced
+
+new_rgn 256
+ctget 0; get 0; global_func 1; catch
+u8_lit 0; halt
+
+u8_lit 1; halt"#,
+    },
+    Explanation {
+        code: "E0067",
+        description: r#"An op needs a variant type, made with `variant`, but found something else.
+`inj` takes the variant type to inject into from the compile-time stack,
+and `case` takes the variant to branch on from the stack, below its functions."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+u8; func 1; lced
+
+u8; tuple 1; u8_lit 7; inj 0
+global_func 1; case 1
+
+halt"#,
+        fixed: r#"func 0; lced
+u8; func 1; lced
+
+u8; variant 1; u8_lit 7; inj 0
+global_func 1; case 1
+
+halt"#,
+    },
+    Explanation {
+        code: "E0068",
+        description: r#"An `inj` names a component past the end of the variant.
+The components of a variant with `n` components are numbered from 0 to `n - 1`,
+with 0 being the type that was on top of the compile-time stack."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+
+i32; u8; variant 2; lit 7; inj 2
+u8_lit 0; halt"#,
+        fixed: r#"func 0; lced
+
+i32; u8; variant 2; lit 7; inj 1
+u8_lit 0; halt"#,
+    },
+    Explanation {
+        code: "E0069",
+        description: r#"A `case` was given a different number of functions than its variant has components.
+It needs one function per component, with the function for component 0 on top,
+so that every value the variant can hold has a function to go to."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+u8; func 1; lced
+i32; func 1; lced
+
+i32; u8; variant 2; u8_lit 7; inj 0
+global_func 1; case 1
+
+halt
+
+i32_to_u8; halt"#,
+        fixed: r#"func 0; lced
+u8; func 1; lced
+i32; func 1; lced
+
+i32; u8; variant 2; u8_lit 7; inj 0
+global_func 2; global_func 1; case 2
+
+halt
+
+i32_to_u8; halt"#,
+    },
+    Explanation {
+        code: "E0070",
+        description: r#"An op that can fault at runtime came before the function installed an exception handler.
+Ops like `malloc`, `arr_proj`, `div`, and the `checked_` arithmetic can fail at runtime,
+and a runtime error jumps to the handler that the function being evaluated installed with `catch`,
+so every function has to `catch` before its first such op."#,
+        kind: ExampleKind::Assembly,
+        example: r#"func 0; lced
+func 0; lced
+
+lit 7; lit 2; div; i32_to_u8; halt
+
+u8_lit 1; halt"#,
+        fixed: r#"func 0; lced
+func 0; lced
+
+global_func 1; catch
+lit 7; lit 2; div; i32_to_u8; halt
+
+u8_lit 1; halt"#,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ModuleBuilder, Ops};
+    use crate::header::{Error, Visibility};
+
+    /// Assemble or decode the modules of an example, then load and link them, like `sabervm run` does.
+    fn check(kind: ExampleKind, text: &str) -> Result<(), Error> {
+        let modules = match kind {
+            ExampleKind::Assembly => vec![crate::assemble::go(text)?],
+            ExampleKind::Modules => {
+                let mut srcs: Vec<String> = vec![];
+                for line in text.lines() {
+                    match line.strip_prefix("# ") {
+                        Some(_file) => srcs.push(String::new()),
+                        None => srcs.last_mut().unwrap().push_str(&(line.to_string() + "\n")),
+                    }
+                }
+                srcs.iter().map(|src| crate::assemble::go(src)).collect::<Result<_, _>>()?
+            }
+            ExampleKind::Bytes => vec![text
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+                .collect()],
+            ExampleKind::Rust => unreachable!("Rust examples are run by `rust_examples`"),
+        };
+        let ir_programs = modules.iter().map(|bytes| crate::load(bytes)).collect::<Result<_, _>>()?;
+        crate::link(ir_programs).map(|_| ())
+    }
+
+    /// Run the Rust, after checking that it's the text of the example, give or take whitespace.
+    macro_rules! run {
+        ($text:expr, $($code:tt)*) => {{
+            let strip = |s: &str| s.split_whitespace().collect::<String>();
+            assert_eq!(strip(stringify!($($code)*)), strip($text));
+            (|| -> Result<(), Error> {
+                $($code)*
+                Ok(())
+            })()
+        }};
+    }
+
+    /// Run the Rust examples of an explanation.
+    // E0064's example declares a function that it never uses
+    #[allow(unused_variables)]
+    fn rust_examples(explanation: &Explanation) -> (Result<(), Error>, Result<(), Error>) {
+        let Explanation { example, fixed, .. } = explanation;
+        match explanation.code {
+            "E0060" => (
+                run!(example,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main + 1, Ops::new().u8_lit(0).halt())?;
+                ),
+                run!(fixed,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main, Ops::new().u8_lit(0).halt())?;
+                ),
+            ),
+            "E0061" => (
+                run!(example,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    let greet = module.declare(Visibility::Import(0, 1), Ops::new().func(0));
+                    module.define(main, Ops::new().global_func(greet).call())?;
+                    module.define(greet, Ops::new().u8_lit(0).halt())?;
+                ),
+                run!(fixed,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    let greet = module.declare(Visibility::Import(0, 1), Ops::new().func(0));
+                    module.define(main, Ops::new().global_func(greet).call())?;
+                ),
+            ),
+            "E0062" => (
+                run!(example,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main, Ops::new().u8_lit(0))?;
+                ),
+                run!(fixed,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main, Ops::new().u8_lit(0).halt())?;
+                ),
+            ),
+            "E0063" => (
+                run!(example,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main, Ops::new().u8_lit(0).halt())?;
+                    module.define(main, Ops::new().u8_lit(1).halt())?;
+                ),
+                run!(fixed,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main, Ops::new().u8_lit(1).halt())?;
+                ),
+            ),
+            "E0064" => (
+                run!(example,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    let helper = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main, Ops::new().u8_lit(0).halt())?;
+                    module.build()?;
+                ),
+                run!(fixed,
+                    let mut module = ModuleBuilder::new();
+                    let main = module.declare(Visibility::Local, Ops::new().func(0));
+                    let helper = module.declare(Visibility::Local, Ops::new().func(0));
+                    module.define(main, Ops::new().global_func(helper).call())?;
+                    module.define(helper, Ops::new().u8_lit(0).halt())?;
+                    module.build()?;
+                ),
+            ),
+            code => panic!("no Rust to run for {}", code),
+        }
+    }
+
+    #[test]
+    fn examples_have_the_error_and_fixed_examples_dont() {
+        for (i, explanation) in EXPLANATIONS.iter().enumerate() {
+            assert_eq!(explanation.code, format!("E{:04}", i + 1));
+            let (example, fixed) = match explanation.kind {
+                ExampleKind::Rust => rust_examples(explanation),
+                kind => (check(kind, explanation.example), check(kind, explanation.fixed)),
+            };
+            assert_eq!(example.map_err(|e| e.code()), Err(explanation.code), "the example of {}", explanation.code);
+            assert_eq!(fixed, Ok(()), "the fixed example of {}", explanation.code);
+        }
+    }
+}
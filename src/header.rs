@@ -4,21 +4,56 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// The input type for SaberVM.
 pub type ByteStream = Vec<u8>;
 
+/// The position of an op in a module, counting every op after the function count from 0.
 pub type Pos = u32;
 pub type Label = u32;
 
+/// The bytes every SaberVM module starts with.
+pub const MAGIC: [u8; 4] = *b"SVM\0";
+
+/// The version of the binary format, which follows the magic number.
+/// It goes up whenever a change to the format or to what verifies means old modules would be mis-parsed or rejected.
+pub const FORMAT_VERSION: u32 = 4;
+
 /// The type for identifiers.
 /// As SaberVM is stack-based, this really just means compile-time stuff, like type variables.
+/// The first field is the position of the op that introduced the variable, which is how the names section refers to it.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Id(pub Pos, pub u32);
 
 /// The type of unverified ops.
 /// This includes all the static analysis ops, which disappear after verification.
+///
+/// Arithmetic on integers wraps around on overflow, including `i32::MIN / -1`,
+/// except that the `Checked` ops fault and the `Saturating` ops clamp to the type's range instead.
+/// Dividing by zero or taking a modulo by zero faults, whichever op does it.
+/// Shifts take the shift amount modulo the bit width, and `Shr` is arithmetic on `i32`s.
+/// `Eq` and `Lt` give an `i32` that's 1 or 0, for `CallNZ` to branch on.
+/// `Cast` converts a number to the type on top of the compile-time stack,
+/// sign- or zero-extending it by its own type's signedness and then truncating it.
+/// Float arithmetic is IEEE 754, except that every NaN it gives is the canonical quiet NaN,
+/// and comparisons with NaN give 0. `Cast` rounds floats toward zero and saturates them to integers,
+/// with NaN giving 0, and rounds integers to the nearest float.
+///
+/// `Malloc` of an array takes a region handle and a length. The array starts out zeroed
+/// if zero bytes are a valid value of its element type, which is the case for numbers
+/// and tuples and variants of them. Otherwise an element to fill it with goes between the handle and the length.
+/// `ArrProj` and `ArrMut` index by whole elements and `CopyN` counts them, faulting when out of bounds or negative.
+///
+/// `App` instantiates the outermost quantifier of the function on top of the stack
+/// with the type or region on top of the compile-time stack, leaving a function of the residual type.
+/// The function can then be stored, applied further, or called, in which case `Call` instantiates the rest.
+///
+/// `Inj` wraps the value on top of the stack in a variant as its component with the given index,
+/// and `Case` pops that many functions and a variant of as many components,
+/// and calls the function with the variant's index with the component on top of the stack.
+/// `Switch` pops that many functions, a default function, and an `i32` or `u8`,
+/// and calls the function with that number as its index, or the default function if there's none.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Op1 {
     Unique,
@@ -67,11 +102,40 @@ pub enum Op1 {
     I32ToU8,
     Read(u8),
     Write(u8),
+    Catch,
+    CheckedAdd,
+    CheckedMul,
+    CheckedDiv,
+    SaturatingAdd,
+    SaturatingMul,
+    SaturatingDiv,
+    Sub,
+    Neg,
+    Eq,
+    Lt,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    I64,
+    U64,
+    I64Lit(i64),
+    U64Lit(u64),
+    Cast,
+    F32,
+    F64,
+    F32Lit(u32),
+    F64Lit(u64),
+    Variant(u8),
+    Inj(u8),
+    Case(u8),
+    Switch(u8),
 }
 
 /// The type of unverified ops.
 /// This includes all the static analysis ops, which disappear after verification.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Op2 {
     Get(usize, usize),
     Init(usize, usize, usize),
@@ -108,6 +172,146 @@ pub enum Op2 {
     I32ToU8,
     Read(u8),
     Write(u8),
+    Catch(usize),
+    CheckedAddI32,
+    CheckedAddU8,
+    CheckedMulI32,
+    CheckedMulU8,
+    CheckedDivI32,
+    SaturatingAddI32,
+    SaturatingAddU8,
+    SaturatingMulI32,
+    SaturatingMulU8,
+    SaturatingDivI32,
+    SubI32,
+    SubU8,
+    NegI32,
+    NegU8,
+    EqI32,
+    EqU8,
+    LtI32,
+    LtU8,
+    AndI32,
+    AndU8,
+    OrI32,
+    OrU8,
+    XorI32,
+    XorU8,
+    ShlI32,
+    ShlU8,
+    ShrI32,
+    ShrU8,
+    I64Lit(i64),
+    U64Lit(u64),
+    Cast(NumType, NumType),
+    AddI64,
+    MulI64,
+    DivI64,
+    ModuloI64,
+    CheckedAddI64,
+    CheckedMulI64,
+    CheckedDivI64,
+    SaturatingAddI64,
+    SaturatingMulI64,
+    SaturatingDivI64,
+    SubI64,
+    NegI64,
+    EqI64,
+    LtI64,
+    AndI64,
+    OrI64,
+    XorI64,
+    ShlI64,
+    ShrI64,
+    AddU64,
+    MulU64,
+    DivU64,
+    ModuloU64,
+    CheckedAddU64,
+    CheckedMulU64,
+    SaturatingAddU64,
+    SaturatingMulU64,
+    SubU64,
+    NegU64,
+    EqU64,
+    LtU64,
+    AndU64,
+    OrU64,
+    XorU64,
+    ShlU64,
+    ShrU64,
+    F32Lit(u32),
+    F64Lit(u64),
+    AddF32,
+    AddF64,
+    SubF32,
+    SubF64,
+    MulF32,
+    MulF64,
+    DivF32,
+    DivF64,
+    NegF32,
+    NegF64,
+    EqF32,
+    EqF64,
+    LtF32,
+    LtF64,
+    Inj(u8, usize, usize),
+    Case(usize, Vec<usize>),
+    SwitchI32(u8),
+    SwitchU8(u8),
+    NewArrFill(usize),
+}
+
+impl Op2 {
+    /// Whether the op can fault at runtime, like an index out of bounds or a division by zero,
+    /// so the function has to install an exception handler before it.
+    pub fn can_fault(&self) -> bool {
+        matches!(
+            self,
+            Op2::InitIP(..)
+                | Op2::Malloc(..)
+                | Op2::ProjIP(..)
+                | Op2::Deref(..)
+                | Op2::NewArr(..)
+                | Op2::ArrMut(..)
+                | Op2::ArrProj(..)
+                | Op2::DataIndex(..)
+                | Op2::CopyN(..)
+                | Op2::NewArrFill(..)
+                | Op2::DivI32
+                | Op2::DivU8
+                | Op2::ModuloI32
+                | Op2::ModuloU8
+                | Op2::CheckedAddI32
+                | Op2::CheckedAddU8
+                | Op2::CheckedMulI32
+                | Op2::CheckedMulU8
+                | Op2::CheckedDivI32
+                | Op2::SaturatingDivI32
+                | Op2::DivI64
+                | Op2::ModuloI64
+                | Op2::CheckedAddI64
+                | Op2::CheckedMulI64
+                | Op2::CheckedDivI64
+                | Op2::SaturatingDivI64
+                | Op2::DivU64
+                | Op2::ModuloU64
+                | Op2::CheckedAddU64
+                | Op2::CheckedMulU64
+        )
+    }
+}
+
+/// The types that `cast` converts between, numbered as in the verified bytecode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumType {
+    U8 = 0,
+    I32 = 1,
+    I64 = 2,
+    U64 = 3,
+    F32 = 4,
+    F64 = 5,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -130,18 +334,66 @@ pub enum Stmt1 {
 }
 
 /// Statements produced by the verification pass.
+/// Alongside the ops is the position of the op each one was verified from.
 #[derive(Debug)]
 pub enum Stmt2 {
-    Func(Pos, Type, Vec<Op2>),
+    Func(Label, Type, Vec<Op2>, Vec<Pos>),
 }
 
 pub struct IRProgram {
     pub data_section: Vec<u8>,
+    pub debug_info: Option<DebugInfo>,
+    pub names: Option<Names>,
     pub imports: HashMap<u32, (u64, u64)>,
     pub exports: HashMap<(u64, u64), u32>,
+    /// The types the forward declarations give the functions, imported ones included.
+    pub types: HashMap<Label, Type>,
     pub funcs: Vec<Stmt2>,
 }
 
+/// The optional debug section of a module,
+/// which maps the positions of some of its ops to where they came from in the source program.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    pub files: Vec<String>,
+    pub locations: BTreeMap<Pos, SourceLoc>,
+}
+
+/// A line and column in one of the files of a debug section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceLoc {
+    pub file: u32,
+    pub line: u32,
+    pub col: u32,
+}
+
+impl DebugInfo {
+    /// Where the op at the given position came from, as `file:line:col`.
+    pub fn location(&self, pos: Pos) -> Option<String> {
+        let loc = self.locations.get(&pos)?;
+        let file = self.files.get(loc.file as usize)?;
+        Some(format!("{}:{}:{}", file, loc.line, loc.col))
+    }
+}
+
+/// The optional names section of a module, which gives human-readable names to functions,
+/// and to the type variables and regions introduced by the ops at some positions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Names {
+    pub funcs: BTreeMap<Label, String>,
+    pub vars: BTreeMap<Pos, String>,
+}
+
+impl Names {
+    pub fn func(&self, label: Label) -> Option<&str> {
+        self.funcs.get(&label).map(String::as_str)
+    }
+
+    pub fn var(&self, id: Id) -> Option<&str> {
+        self.vars.get(&id.0).map(String::as_str)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum RgnId {
     Var(Id),
@@ -158,6 +410,10 @@ pub struct Region {
 pub enum Type {
     I32,
     U8,
+    I64,
+    U64,
+    F32,
+    F64,
     Handle(Region),
     Tuple(Vec<(bool, Type)>),
     Ptr(Box<Type>, Region),
@@ -167,6 +423,7 @@ pub enum Type {
     ForallRegion(Region, Box<Type>, Vec<Region>),
     Exists(Id, usize, Box<Type>),
     Array(Box<Type>, Region),
+    Variant(Vec<Type>),
 }
 
 impl Type {
@@ -174,6 +431,10 @@ impl Type {
         match self {
             Self::I32 => 4,
             Self::U8 => 1,
+            Self::I64 => 8,
+            Self::U64 => 8,
+            Self::F32 => 4,
+            Self::F64 => 8,
             Self::Handle(_r) => 8,
             Self::Tuple(ts) => ts.iter().map(|(_, t)| t.size()).sum(),
             Self::Ptr(_t, _r) => 16,
@@ -183,6 +444,8 @@ impl Type {
             Self::ForallRegion(_r, t, _captured_rgns) => t.size(),
             Self::Exists(_id, _size, t) => t.size(),
             Self::Array(_t, _r) => 16,
+            // the largest component, then the index of the component it holds
+            Self::Variant(ts) => ts.iter().map(Type::size).max().unwrap_or(0) + 4,
         }
     }
 }
@@ -219,11 +482,37 @@ pub enum Quantification {
     Exist(Id, usize),
 }
 
+/// An error from checking every function of a module instead of stopping at the first error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The function whose forward declaration or definition has the error,
+    /// or `None` if the error is about the whole module, like a syntax error.
+    pub label: Option<Label>,
+    pub error: Error,
+}
+
+/// Something an error expected or found, for tools that want more than the message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    Type(Type),
+    Types(Vec<Type>),
+    Region(Region),
+    Kind(Kind),
+    TypeVar(Id),
+    CompileTimeStack(Vec<CTStackVal>),
+    Number(u64),
+}
+
 /// The type for user-facing errors (as opposed to internal SaberVM errors, which are panics).
-#[derive(Debug, PartialEq, Eq)]
+/// Each variant has a stable code (see `Error::code`), so new variants go at the end.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
     SyntaxErrorParamNeeded(Pos, u8),
     SyntaxErrorUnknownOp(Pos, u8),
+    SyntaxErrorNotAModule,
+    SyntaxErrorUnsupportedVersion(u32),
+    SyntaxErrorBadDebugSection,
+    SyntaxErrorBadNamesSection,
     TypeErrorMainHasArgs,
     TypeErrorNonEmptyQuantificationStack(Label),
     TypeErrorEmptyQuantificationStack(Pos, Op1),
@@ -266,5 +555,26 @@ pub enum Error {
     DataSectionLoadOutOfBounds(Pos, Op1, usize, usize),
     InvalidDataSectionType(Pos, Op1, Type),
     CannotMutateDataSection(Pos, Op1),
-    UnknownChannel(Pos, Op1, u8)
+    UnknownChannel(Pos, Op1, u8),
+    AssemblyErrorUnknownMnemonic(u32, String),
+    AssemblyErrorParamNeeded(u32, String),
+    AssemblyErrorBadParam(u32, String, String),
+    AssemblyErrorBadEscape(u32, String),
+    AssemblyErrorUnterminatedString(u32),
+    AssemblyErrorUnexpectedToken(u32, String),
+    AssemblyErrorUnexpectedEOF(u32),
+    LinkErrorUnresolvedImport(u64, u64),
+    LinkErrorDuplicateExport(u64, u64),
+    LinkErrorImportTypeMismatch(u64, u64, Type, Type),
+    BuilderErrorUnknownLabel(Label),
+    BuilderErrorImportDefined(Label),
+    BuilderErrorBadTerminator(Label),
+    BuilderErrorRedefined(Label),
+    BuilderErrorUndefined(Label),
+    TooBigForHandler(Pos, Op1, usize),
+    RegionUsedByHandler(Pos, Op1, Region),
+    TypeErrorVariantExpected(Pos, Op1, Type),
+    TypeErrorInjOutOfRange(Pos, u8, usize),
+    TypeErrorCaseArityMismatch(Pos, u8, usize),
+    NoHandler(Pos, Op1),
 }
@@ -0,0 +1,1509 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! The runtime system in Rust, running the same linked code as `vm.c`, op for op, with the same output.
+//!
+//! Memory is simulated: a pointer's reference or a region's handle is an address whose upper 32 bits
+//! say which region it's in (0 for the code, which holds the data section) and whose lower 32 bits are
+//! the offset in that region, so the bytes of an address differ from `vm.c`'s but nothing can observe that.
+//! Regions have the same layout as in `vm.c`, with the generation and size of every object before it,
+//! and the stack is one contiguous buffer rather than 4096-byte chunks.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io::{self, Read, Write};
+use crate::vm::RuntimeError;
+
+/// The bytes before every object in a region: its generation and its size.
+const METADATA_OFFSET: u64 = 8 + 8;
+
+/// The number of bits of an address that are the offset in its region.
+const OFFSET_BITS: u32 = 32;
+
+/// How many tasks can wait to run at once.
+const SCHEDULER_CAPACITY: usize = 255;
+
+/// How much of the input one read takes.
+const STDIN_CHUNK_SIZE: usize = 1024;
+
+/// Why a task stopped before halting.
+enum Stop {
+    /// A runtime error, with its message, which jumps to the exception handler of the function being evaluated,
+    /// or stops the program with the message if it hasn't installed one.
+    Fault(String),
+    /// A bug in SaberVM itself, which ends the program.
+    Internal,
+}
+
+/// A number on its way through `cast`.
+#[derive(Clone, Copy)]
+enum Num {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+/// The float, or the canonical quiet NaN if it's any NaN, as `canonical_f32` in `vm.c`.
+fn canonical_f32(x: f32) -> f32 {
+    if x.is_nan() {
+        f32::from_bits(0x7FC0_0000)
+    } else {
+        x
+    }
+}
+
+fn canonical_f64(x: f64) -> f64 {
+    if x.is_nan() {
+        f64::from_bits(0x7FF8_0000_0000_0000)
+    } else {
+        x
+    }
+}
+
+/// A pointer into a region, laid out on the stack as `Pointer` in `vm.h`.
+#[derive(Clone, Copy)]
+struct Pointer {
+    generation: i64,
+    reference: u64,
+}
+
+impl Pointer {
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.generation.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.reference.to_le_bytes());
+        bytes
+    }
+}
+
+/// A growable, nonmoving arena, as `Region` in `vm.c`.
+struct Region {
+    offset: u64,
+    data: Vec<u8>,
+}
+
+/// A function waiting to run, with the argument and environment to run it with.
+#[derive(Clone, Copy)]
+struct Handler {
+    f: u32,
+    param_size: usize,
+    param: [u8; 16],
+    env: Pointer,
+}
+
+impl Handler {
+    fn new(f: u32) -> Self {
+        Handler {
+            f,
+            param_size: 0,
+            param: [0; 16],
+            env: Pointer {
+                generation: 0,
+                reference: 0,
+            },
+        }
+    }
+}
+
+struct Machine<'a> {
+    code: &'a [u8],
+    data_section_size: u32,
+    source_locations: &'a HashMap<u32, CString>,
+    /// The regions by their index, which is one less than the upper bits of their addresses.
+    /// Freed regions are `None`.
+    regions: Vec<Option<Region>>,
+    stack: Vec<u8>,
+    /// The tasks waiting to run; the last one runs next.
+    scheduler: Vec<Handler>,
+    waiting_for_stdin: bool,
+    stdin_handler: Handler,
+    stdin_rgn: u64,
+    stdout_handler: Handler,
+    stderr_handler: Handler,
+    /// The position of the op being evaluated, for locating runtime errors.
+    current_op: u32,
+    /// The exception handler of the function being evaluated, with the arguments `catch` gave it.
+    /// `f` is 0 if the function hasn't installed one, since no function starts at position 0.
+    exception_handler: Handler,
+}
+
+/// Run linked code, in the format `vm::link` produces, returning the status the program passed to `halt`.
+pub fn go(code: &[u8], source_locations: &HashMap<u32, CString>) -> Result<u8, RuntimeError> {
+    let data_section_size = u32::from_le_bytes(code[..4].try_into().unwrap());
+    let mut machine = Machine {
+        code,
+        data_section_size,
+        source_locations,
+        regions: vec![],
+        stack: vec![],
+        scheduler: vec![],
+        waiting_for_stdin: false,
+        stdin_handler: Handler::new(0),
+        stdin_rgn: 0,
+        stdout_handler: Handler::new(0),
+        stderr_handler: Handler::new(0),
+        current_op: 0,
+        exception_handler: Handler::new(0),
+    };
+    let result = machine.run(4 + data_section_size);
+    io::stdout().flush().ok();
+    result
+}
+
+impl Machine<'_> {
+    /// Run the tasks until one halts with a nonzero status or there's nothing left to do.
+    fn run(&mut self, start: u32) -> Result<u8, RuntimeError> {
+        self.post_task(Handler::new(start));
+        loop {
+            while let Some(h) = self.scheduler.pop() {
+                self.stack.clear();
+                self.stack.extend(h.env.to_bytes());
+                self.stack.extend(&h.param[..h.param_size]);
+                self.exception_handler.f = 0;
+                match self.eval(h.f) {
+                    Ok(0) => {}
+                    result => return result,
+                }
+            }
+            if self.waiting_for_stdin {
+                match self.handle_stdin() {
+                    Ok(()) => {}
+                    Err(Stop::Fault(msg)) => {
+                        // no function is being evaluated, so there's no exception handler to jump to
+                        println!("Runtime Error! {}", msg);
+                        return Err(RuntimeError::Fault);
+                    }
+                    Err(Stop::Internal) => return Err(RuntimeError::Internal),
+                }
+            }
+            if !self.waiting_for_stdin && self.scheduler.is_empty() {
+                return Ok(0);
+            }
+        }
+    }
+
+    fn post_task(&mut self, h: Handler) -> bool {
+        if self.scheduler.len() == SCHEDULER_CAPACITY {
+            return false;
+        }
+        self.scheduler.push(h);
+        true
+    }
+
+    /// Read the next chunk of the input into the region `read` gave, and post its handler with it.
+    fn handle_stdin(&mut self) -> Result<(), Stop> {
+        // flush what the program has written first, in case it's a prompt for this input
+        io::stdout().flush().ok();
+        let mut buffer = [0; STDIN_CHUNK_SIZE];
+        let bytes = io::stdin().read(&mut buffer).unwrap_or(0);
+        self.waiting_for_stdin = false;
+        if bytes == 0 {
+            // the end of the input, so the handler never runs
+            return Ok(());
+        }
+        let len = bytes as u64;
+        let ptr = self.alloc_object(self.stdin_rgn, len + 8)?;
+        self.write_mem(ptr.reference, &len.to_le_bytes())?;
+        self.write_mem(ptr.reference + 8, &buffer[..bytes])?;
+        let mut h = self.stdin_handler;
+        h.param = ptr.to_bytes();
+        h.param_size = 16;
+        if !self.post_task(h) {
+            println!("failed to post stdin handler to scheduler");
+            return Err(Stop::Internal);
+        }
+        Ok(())
+    }
+
+    fn print_source_location(&self) {
+        if let Some(location) = self.source_locations.get(&self.current_op) {
+            println!("At {}", location.to_string_lossy());
+        }
+    }
+
+    fn fault(&self, msg: &str) -> Stop {
+        Stop::Fault(msg.to_string())
+    }
+
+    fn internal_error(&self, msg: &str) -> Stop {
+        println!("Internal SaberVM Error! {}", msg);
+        Stop::Internal
+    }
+
+    fn new_region(&mut self, size: u64) -> u64 {
+        self.regions.push(Some(Region {
+            offset: 0,
+            data: vec![0; size as usize],
+        }));
+        (self.regions.len() as u64) << OFFSET_BITS
+    }
+
+    fn region(&self, handle: u64) -> Result<&Region, Stop> {
+        let index = (handle >> OFFSET_BITS) as usize;
+        match index.checked_sub(1).and_then(|i| self.regions.get(i)) {
+            Some(Some(r)) => Ok(r),
+            _ => Err(self.internal_error("Access to a region that doesn't exist.")),
+        }
+    }
+
+    fn region_mut(&mut self, handle: u64) -> Result<&mut Region, Stop> {
+        let index = (handle >> OFFSET_BITS) as usize;
+        if !matches!(
+            index.checked_sub(1).and_then(|i| self.regions.get(i)),
+            Some(Some(_))
+        ) {
+            return Err(self.internal_error("Access to a region that doesn't exist."));
+        }
+        Ok(self.regions[index - 1].as_mut().unwrap())
+    }
+
+    fn free_region(&mut self, handle: u64) -> Result<(), Stop> {
+        self.region(handle)?;
+        self.regions[(handle >> OFFSET_BITS) as usize - 1] = None;
+        Ok(())
+    }
+
+    /// The bytes at an address: in the code if the upper bits are 0, or else in a region.
+    fn read_mem(&self, addr: u64, len: u64) -> Result<&[u8], Stop> {
+        let offset = addr & ((1 << OFFSET_BITS) - 1);
+        let bytes: &[u8] = if addr >> OFFSET_BITS == 0 {
+            self.code
+        } else {
+            &self.region(addr)?.data
+        };
+        let range = offset
+            .checked_add(len)
+            .filter(|end| *end <= bytes.len() as u64)
+            .map(|end| offset..end);
+        match range {
+            Some(range) => Ok(&bytes[range.start as usize..range.end as usize]),
+            None => Err(self.internal_error("Access out of the bounds of a region.")),
+        }
+    }
+
+    fn write_mem(&mut self, addr: u64, bytes: &[u8]) -> Result<(), Stop> {
+        let offset = addr & ((1 << OFFSET_BITS) - 1);
+        if addr >> OFFSET_BITS == 0 {
+            return Err(self.internal_error("Write to the data section."));
+        }
+        let len = bytes.len() as u64;
+        let r = self.region_mut(addr)?;
+        match offset
+            .checked_add(len)
+            .filter(|end| *end <= r.data.len() as u64)
+        {
+            Some(end) => {
+                r.data[offset as usize..end as usize].copy_from_slice(bytes);
+                Ok(())
+            }
+            None => Err(self.internal_error("Access out of the bounds of a region.")),
+        }
+    }
+
+    fn read_u64(&self, addr: u64) -> Result<u64, Stop> {
+        Ok(u64::from_le_bytes(
+            self.read_mem(addr, 8)?.try_into().unwrap(),
+        ))
+    }
+
+    /// Allocate an object in a region, reusing the space of a freed object if the region is full,
+    /// the same way `alloc_object` in `vm.c` does.
+    fn alloc_object(&mut self, handle: u64, size: u64) -> Result<Pointer, Stop> {
+        let r = self.region_mut(handle)?;
+        let capacity = r.data.len() as u64;
+        // reads past the end of the region see zeros
+        let word = |data: &[u8], i: u64| -> [u8; 8] {
+            let mut bytes = [0; 8];
+            for (j, byte) in bytes.iter_mut().enumerate() {
+                *byte = *data.get(i as usize + j).unwrap_or(&0);
+            }
+            bytes
+        };
+        if r.offset.wrapping_add(METADATA_OFFSET).wrapping_add(size) > capacity {
+            let mut offset = 0;
+            while offset < r.offset {
+                // negative generation means free
+                let local_generation = i64::from_le_bytes(word(&r.data, offset));
+                let local_size = u64::from_le_bytes(word(&r.data, offset + 8));
+                if local_generation < 0 && local_size <= size {
+                    let new_generation = -local_generation + 1;
+                    r.data[offset as usize..offset as usize + 8]
+                        .copy_from_slice(&new_generation.to_le_bytes());
+                    r.data[offset as usize + 8..offset as usize + 16]
+                        .copy_from_slice(&size.to_le_bytes());
+                    r.offset = offset + METADATA_OFFSET + size;
+                    return Ok(Pointer {
+                        generation: new_generation,
+                        reference: handle + offset + METADATA_OFFSET,
+                    });
+                }
+                offset += METADATA_OFFSET + u64::from(*r.data.get(offset as usize).unwrap_or(&0));
+            }
+            return Err(self.fault("Allocation too big for region!"));
+        }
+        let offset = r.offset;
+        let first_generation: i64 = 1;
+        r.data[offset as usize..offset as usize + 8]
+            .copy_from_slice(&first_generation.to_le_bytes());
+        r.data[offset as usize + 8..offset as usize + 16].copy_from_slice(&size.to_le_bytes());
+        r.offset += METADATA_OFFSET + size;
+        Ok(Pointer {
+            generation: first_generation,
+            reference: handle + offset + METADATA_OFFSET,
+        })
+    }
+
+    /// Fail if the object the pointer points at has been freed.
+    fn check_ptr(&self, ptr: Pointer) -> Result<(), Stop> {
+        if ptr.generation < 0 {
+            // negative generation in a pointer means the referent is unfreeable
+            // and therefore doesn't have a generation tag in the preceding memory
+            return Ok(());
+        }
+        let g = i64::from_le_bytes(
+            self.read_mem(ptr.reference.wrapping_sub(METADATA_OFFSET), 8)?
+                .try_into()
+                .unwrap(),
+        );
+        if ptr.generation != g {
+            return Err(
+                self.fault("The program is trying to access memory that's already been freed!")
+            );
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.stack.extend_from_slice(bytes);
+    }
+
+    fn pop(&mut self, size: usize) -> Result<Vec<u8>, Stop> {
+        match self.stack.len().checked_sub(size) {
+            Some(sp) => Ok(self.stack.split_off(sp)),
+            None => Err(self.internal_error("Stack underflow.")),
+        }
+    }
+
+    fn pop_u8(&mut self) -> Result<u8, Stop> {
+        Ok(self.pop(1)?[0])
+    }
+
+    fn pop_i32(&mut self) -> Result<i32, Stop> {
+        Ok(i32::from_le_bytes(self.pop(4)?.try_into().unwrap()))
+    }
+
+    fn pop_u32(&mut self) -> Result<u32, Stop> {
+        Ok(u32::from_le_bytes(self.pop(4)?.try_into().unwrap()))
+    }
+
+    fn pop_u64(&mut self) -> Result<u64, Stop> {
+        Ok(u64::from_le_bytes(self.pop(8)?.try_into().unwrap()))
+    }
+
+    fn pop_i64(&mut self) -> Result<i64, Stop> {
+        Ok(i64::from_le_bytes(self.pop(8)?.try_into().unwrap()))
+    }
+
+    fn pop_f32(&mut self) -> Result<f32, Stop> {
+        Ok(f32::from_le_bytes(self.pop(4)?.try_into().unwrap()))
+    }
+
+    fn pop_f64(&mut self) -> Result<f64, Stop> {
+        Ok(f64::from_le_bytes(self.pop(8)?.try_into().unwrap()))
+    }
+
+    fn pop_ptr(&mut self) -> Result<Pointer, Stop> {
+        let bytes = self.pop(16)?;
+        Ok(Pointer {
+            generation: i64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            reference: u64::from_le_bytes(bytes[8..].try_into().unwrap()),
+        })
+    }
+
+    /// The next `N` bytes of the code, moving `pc` past them.
+    fn param_bytes<const N: usize>(&self, pc: &mut u32) -> Result<[u8; N], Stop> {
+        let start = *pc as usize;
+        match self.code.get(start..start + N) {
+            Some(bytes) => {
+                *pc += N as u32;
+                Ok(bytes.try_into().unwrap())
+            }
+            None => Err(self.internal_error("Ran past the end of the code.")),
+        }
+    }
+
+    fn param_u8(&self, pc: &mut u32) -> Result<u8, Stop> {
+        Ok(u8::from_le_bytes(self.param_bytes(pc)?))
+    }
+
+    fn param_i32(&self, pc: &mut u32) -> Result<i32, Stop> {
+        Ok(i32::from_le_bytes(self.param_bytes(pc)?))
+    }
+
+    fn param_u32(&self, pc: &mut u32) -> Result<u32, Stop> {
+        Ok(u32::from_le_bytes(self.param_bytes(pc)?))
+    }
+
+    fn param_i64(&self, pc: &mut u32) -> Result<i64, Stop> {
+        Ok(i64::from_le_bytes(self.param_bytes(pc)?))
+    }
+
+    fn param_u64(&self, pc: &mut u32) -> Result<u64, Stop> {
+        Ok(u64::from_le_bytes(self.param_bytes(pc)?))
+    }
+
+    /// A `size_t` parameter.
+    fn param_size(&self, pc: &mut u32) -> Result<u64, Stop> {
+        Ok(u64::from_le_bytes(self.param_bytes(pc)?))
+    }
+
+    /// Print at most `len` bytes of a string, stopping at a zero byte like `printf("%.*s")`.
+    fn print_str(&self, ptr: Pointer, to_stderr: bool) -> Result<(), Stop> {
+        let len = self.read_u64(ptr.reference)? as i32;
+        let bytes = self.read_mem(ptr.reference + 8, len.max(0) as u64)?;
+        let bytes = bytes.split(|byte| *byte == 0).next().unwrap();
+        if to_stderr {
+            io::stderr().write_all(bytes).ok();
+        } else {
+            io::stdout().write_all(bytes).ok();
+        }
+        Ok(())
+    }
+
+    /// Run a task until it halts, returning the status it halts with,
+    /// or why it stopped the program first.
+    fn eval(&mut self, mut pc: u32) -> Result<u8, RuntimeError> {
+        loop {
+            self.current_op = pc;
+            match self.step(&mut pc) {
+                Ok(None) => {}
+                Ok(Some(status)) => return Ok(status),
+                Err(Stop::Fault(msg)) => {
+                    if self.exception_handler.f == 0 {
+                        println!("Runtime Error! {}", msg);
+                        self.print_source_location();
+                        return Err(RuntimeError::Fault);
+                    }
+                    // jump to the handler with a fresh stack holding just its arguments
+                    let h = self.exception_handler;
+                    self.stack.clear();
+                    self.stack.extend(&h.param[..h.param_size]);
+                    pc = h.f;
+                    self.exception_handler.f = 0;
+                }
+                Err(Stop::Internal) => return Err(RuntimeError::Internal),
+            }
+        }
+    }
+
+    /// Evaluate the op at `pc`, returning the status if it halts.
+    fn step(&mut self, pc: &mut u32) -> Result<Option<u8>, Stop> {
+        let op = self.param_u8(pc)?;
+        match op {
+            // get
+            0 => {
+                let offset = self.param_size(pc)? as usize;
+                let size = self.param_size(pc)? as usize;
+                let sp = self.stack.len();
+                let start = sp - offset - size;
+                self.stack.extend_from_within(start..start + size);
+            }
+            // init
+            1 => {
+                let offset = self.param_size(pc)? as usize;
+                let size = self.param_size(pc)? as usize;
+                let tpl_size = self.param_size(pc)? as usize;
+                let val = self.pop(size)?;
+                let start = self.stack.len() - tpl_size + offset;
+                self.stack[start..start + size].copy_from_slice(&val);
+            }
+            // init in-place
+            2 => {
+                let offset = self.param_size(pc)?;
+                let size = self.param_size(pc)? as usize;
+                let val = self.pop(size)?;
+                let ptr = self.pop_ptr()?;
+                self.check_ptr(ptr)?;
+                self.write_mem(ptr.reference + offset, &val)?;
+                self.push(&ptr.to_bytes());
+            }
+            // malloc
+            3 => {
+                let size = self.param_size(pc)?;
+                let handle = self.pop_u64()?;
+                let ptr = self.alloc_object(handle, size)?;
+                self.push(&ptr.to_bytes());
+            }
+            // alloca
+            4 => {
+                let size = self.param_size(pc)? as usize;
+                self.stack.resize(self.stack.len() + size, 0);
+            }
+            // projection
+            5 => {
+                let offset = self.param_size(pc)? as usize;
+                let size = self.param_size(pc)? as usize;
+                let tpl_size = self.param_size(pc)? as usize;
+                let sp = self.stack.len() - tpl_size;
+                self.stack.copy_within(sp + offset..sp + offset + size, sp);
+                self.stack.truncate(sp + size);
+            }
+            // projection in-place
+            6 => {
+                let offset = self.param_size(pc)?;
+                let size = self.param_size(pc)?;
+                let ptr = self.pop_ptr()?;
+                self.check_ptr(ptr)?;
+                let val = self.read_mem(ptr.reference + offset, size)?.to_vec();
+                self.push(&val);
+            }
+            // call
+            7 => {
+                *pc = self.pop_u32()?;
+                // the handler was the last function's
+                self.exception_handler.f = 0;
+            }
+            // literal
+            9 => {
+                let lit = self.param_i32(pc)?;
+                self.push(&lit.to_le_bytes());
+            }
+            // global function
+            10 => {
+                let f = self.param_u32(pc)?;
+                self.push(&f.to_le_bytes());
+            }
+            // halt
+            11 => return Ok(Some(self.pop_u8()?)),
+            // new region
+            12 => {
+                let size = self.param_size(pc)?;
+                let handle = self.new_region(size);
+                self.push(&handle.to_le_bytes());
+            }
+            // free region
+            13 => {
+                let handle = self.pop_u64()?;
+                self.free_region(handle)?;
+            }
+            // dereference pointer
+            14 => {
+                let size = self.param_size(pc)?;
+                let ptr = self.pop_ptr()?;
+                self.check_ptr(ptr)?;
+                let val = self.read_mem(ptr.reference, size)?.to_vec();
+                self.push(&val);
+            }
+            // new array
+            15 => {
+                let elem_size = self.param_size(pc)?;
+                let len = self.pop_i32()?;
+                if len < 0 {
+                    return Err(self.fault(&format!("Negative array length ({}).", len)));
+                }
+                let handle = self.pop_u64()?;
+                let size = elem_size.wrapping_mul(len as u64);
+                let ptr = self.alloc_object(handle, size.wrapping_add(8))?;
+                self.write_mem(ptr.reference, &size.to_le_bytes())?;
+                self.write_mem(ptr.reference + 8, &vec![0; size as usize])?;
+                self.push(&ptr.to_bytes());
+            }
+            // mutate array component
+            16 => {
+                let elem_size = self.param_size(pc)?;
+                let i = self.pop_i32()?;
+                let val = self.pop(elem_size as usize)?;
+                let ptr = self.pop_ptr()?;
+                self.check_ptr(ptr)?;
+                let n = elem_size.wrapping_mul(i as u64);
+                let array_len = self.read_u64(ptr.reference)?;
+                if i < 0 || n.wrapping_add(elem_size) > array_len {
+                    return Err(self.fault("Array index out of bounds during an initialization."));
+                }
+                self.write_mem(ptr.reference.wrapping_add(8).wrapping_add(n), &val)?;
+                self.push(&ptr.to_bytes());
+            }
+            // project from array
+            17 => {
+                let elem_size = self.param_size(pc)?;
+                let i = self.pop_i32()?;
+                let n = elem_size.wrapping_mul(i as u64);
+                let ptr = self.pop_ptr()?;
+                self.check_ptr(ptr)?;
+                let array_len = self.read_u64(ptr.reference)?;
+                if i < 0 || n.wrapping_add(elem_size) > array_len {
+                    return Err(self.fault("Array index out of bounds during a projection."));
+                }
+                let val = self
+                    .read_mem(ptr.reference.wrapping_add(8).wrapping_add(n), elem_size)?
+                    .to_vec();
+                self.push(&val);
+            }
+            // add two i32s
+            18 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&a.wrapping_add(b).to_le_bytes());
+            }
+            // multiply two i32s
+            19 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&a.wrapping_mul(b).to_le_bytes());
+            }
+            // divide two i32s
+            20 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.wrapping_div(a).to_le_bytes());
+            }
+            // call if not zero
+            21 => {
+                let f = self.pop_u32()?;
+                let g = self.pop_u32()?;
+                let cond = self.pop_i32()?;
+                *pc = if cond != 0 { g } else { f };
+                self.exception_handler.f = 0;
+            }
+            // load from data section
+            22 => {
+                let offset = self.param_size(pc)?;
+                // negative generation in a pointer means the referent is unfreeable.
+                // In this case, the referent is in the data section.
+                let ptr = Pointer {
+                    generation: -1,
+                    reference: 4 + offset,
+                };
+                self.push(&ptr.to_bytes());
+            }
+            // project from data-section array
+            23 => {
+                let elem_size = self.param_size(pc)?;
+                let i = self.pop_i32()?;
+                let n = elem_size.wrapping_mul(i as u64);
+                let ptr = self.pop_ptr()?;
+                let data_section_end = 4 + u64::from(self.data_section_size);
+                if ptr.reference > data_section_end {
+                    return Err(self.fault(
+                        "Array index out of bounds during a projection from the data section.",
+                    ));
+                }
+                let rest_of_data_section = data_section_end - ptr.reference;
+                if i < 0 || n.wrapping_add(elem_size) > rest_of_data_section {
+                    return Err(self.fault(
+                        "Array index out of bounds during a projection from the data section.",
+                    ));
+                }
+                let val = self
+                    .read_mem(ptr.reference.wrapping_add(n), elem_size)?
+                    .to_vec();
+                self.push(&val);
+            }
+            // copy n elements
+            24 => {
+                let n = self.pop_i32()?;
+                let src_array = self.pop_ptr()?;
+                let dest_array = self.pop_ptr()?;
+                let elem_size = self.param_size(pc)?;
+                if n < 0 {
+                    return Err(self.fault(&format!("Negative size ({}) during a copy.", n)));
+                }
+                // array lengths are in bytes, and the copy stops at the end of the source
+                let mut size = (n as u64) * elem_size;
+                let src_ref;
+                if src_array.generation == -1 {
+                    // -1 generation means data section string
+                    let rest_of_data_section =
+                        (4 + u64::from(self.data_section_size)).saturating_sub(src_array.reference);
+                    if size > rest_of_data_section {
+                        size = rest_of_data_section - rest_of_data_section % elem_size;
+                    }
+                    src_ref = src_array.reference;
+                } else {
+                    self.check_ptr(src_array)?;
+                    let array_len = self.read_u64(src_array.reference)?;
+                    size = size.min(array_len);
+                    src_ref = src_array.reference + 8;
+                }
+                self.check_ptr(dest_array)?;
+                let dest_array_len = self.read_u64(dest_array.reference)?;
+                if dest_array_len < (n as u64) * elem_size {
+                    return Err(self.fault(&format!(
+                        "Copy ({}) out of bounds for array of size {}.",
+                        n,
+                        dest_array_len / elem_size
+                    )));
+                }
+                let val = self.read_mem(src_ref, size)?.to_vec();
+                self.write_mem(dest_array.reference + 8, &val)?;
+                self.push(&dest_array.to_bytes());
+            }
+            // u8 literal
+            25 => {
+                let val = self.param_u8(pc)?;
+                self.push(&[val]);
+            }
+            // add u8
+            26 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[a.wrapping_add(b)]);
+            }
+            // multiply u8
+            27 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[a.wrapping_mul(b)]);
+            }
+            // divide u8
+            28 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&[b / a]);
+            }
+            // u8 to i32
+            29 => {
+                let a = self.pop_u8()?;
+                self.push(&i32::from(a).to_le_bytes());
+            }
+            // modulo i32
+            30 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.wrapping_rem(a).to_le_bytes());
+            }
+            // modulo u8
+            31 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&[b % a]);
+            }
+            // i32 to u8
+            32 => {
+                let a = self.pop_i32()?;
+                self.push(&[a as u8]);
+            }
+            // read
+            33 => {
+                let c = self.param_u8(pc)?;
+                if c == 0 {
+                    let r = self.pop_u64()?;
+                    let env = self.pop_ptr()?;
+                    let handler = self.pop_u32()?;
+                    self.stdin_handler.f = handler;
+                    self.stdin_handler.env = env;
+                    self.stdin_rgn = r;
+                    self.waiting_for_stdin = true;
+                }
+            }
+            // write
+            34 => {
+                let c = self.param_u8(pc)?;
+                if c == 0 {
+                    let _r = self.pop_u64()?;
+                    let write_mode = self.pop_u8()?;
+                    let env = self.pop_ptr()?;
+                    let handler = self.pop_u32()?;
+                    let str_ptr = self.pop_ptr()?;
+                    let h = match write_mode {
+                        0 => &mut self.stdout_handler,
+                        1 => &mut self.stderr_handler,
+                        _ => {
+                            return Err(
+                                self.internal_error(&format!("Unknown write mode {}.", write_mode))
+                            )
+                        }
+                    };
+                    h.f = handler;
+                    h.env = env;
+                    let h = *h;
+                    self.print_str(str_ptr, write_mode == 1)?;
+                    self.post_task(h);
+                }
+            }
+            // catch
+            35 => {
+                let size = self.param_size(pc)? as usize;
+                let f = self.pop_u32()?;
+                let param = self.pop(size)?;
+                let h = &mut self.exception_handler;
+                h.param[..size].copy_from_slice(&param);
+                h.param_size = size;
+                h.f = f;
+            }
+            // checked add i32
+            36 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                let Some(result) = b.checked_add(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // checked add u8
+            37 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                let Some(result) = b.checked_add(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&[result]);
+            }
+            // checked multiply i32
+            38 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                let Some(result) = b.checked_mul(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // checked multiply u8
+            39 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                let Some(result) = b.checked_mul(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&[result]);
+            }
+            // checked divide i32
+            40 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                let Some(result) = b.checked_div(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // saturating add i32
+            41 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&b.saturating_add(a).to_le_bytes());
+            }
+            // saturating add u8
+            42 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b.saturating_add(a)]);
+            }
+            // saturating multiply i32
+            43 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&b.saturating_mul(a).to_le_bytes());
+            }
+            // saturating multiply u8
+            44 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b.saturating_mul(a)]);
+            }
+            // saturating divide i32
+            45 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.saturating_div(a).to_le_bytes());
+            }
+            // subtract i32
+            46 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&b.wrapping_sub(a).to_le_bytes());
+            }
+            // subtract u8
+            47 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b.wrapping_sub(a)]);
+            }
+            // negate i32
+            48 => {
+                let a = self.pop_i32()?;
+                self.push(&a.wrapping_neg().to_le_bytes());
+            }
+            // negate u8
+            49 => {
+                let a = self.pop_u8()?;
+                self.push(&[a.wrapping_neg()]);
+            }
+            // equal i32
+            50 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&i32::from(b == a).to_le_bytes());
+            }
+            // equal u8
+            51 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&i32::from(b == a).to_le_bytes());
+            }
+            // less than i32
+            52 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&i32::from(b < a).to_le_bytes());
+            }
+            // less than u8
+            53 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&i32::from(b < a).to_le_bytes());
+            }
+            // bitwise and i32
+            54 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&(b & a).to_le_bytes());
+            }
+            // bitwise and u8
+            55 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b & a]);
+            }
+            // bitwise or i32
+            56 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&(b | a).to_le_bytes());
+            }
+            // bitwise or u8
+            57 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b | a]);
+            }
+            // bitwise xor i32
+            58 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&(b ^ a).to_le_bytes());
+            }
+            // bitwise xor u8
+            59 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b ^ a]);
+            }
+            // shift left i32
+            60 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&b.wrapping_shl(a as u32).to_le_bytes());
+            }
+            // shift left u8
+            61 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b.wrapping_shl(u32::from(a))]);
+            }
+            // shift right i32
+            62 => {
+                let a = self.pop_i32()?;
+                let b = self.pop_i32()?;
+                self.push(&b.wrapping_shr(a as u32).to_le_bytes());
+            }
+            // shift right u8
+            63 => {
+                let a = self.pop_u8()?;
+                let b = self.pop_u8()?;
+                self.push(&[b.wrapping_shr(u32::from(a))]);
+            }
+            // i64 literal
+            64 => {
+                let lit = self.param_i64(pc)?;
+                self.push(&lit.to_le_bytes());
+            }
+            // u64 literal
+            65 => {
+                let lit = self.param_u64(pc)?;
+                self.push(&lit.to_le_bytes());
+            }
+            // cast, between the types numbered as in `NumType`
+            66 => {
+                let from = self.param_u8(pc)?;
+                let to = self.param_u8(pc)?;
+                // integers convert through their 64-bit two's complement bits,
+                // extended by the signedness of the type they come from and then truncated,
+                // and floats convert to integers like `as` does, rounding toward zero and saturating
+                let num = match from {
+                    0 => Num::Unsigned(u64::from(self.pop_u8()?)),
+                    1 => Num::Signed(i64::from(self.pop_i32()?)),
+                    2 => Num::Signed(self.pop_i64()?),
+                    3 => Num::Unsigned(self.pop_u64()?),
+                    4 => Num::Float(f64::from(self.pop_f32()?)),
+                    _ => Num::Float(self.pop_f64()?),
+                };
+                let bits = match num {
+                    Num::Signed(n) => n as u64,
+                    Num::Unsigned(n) => n,
+                    Num::Float(x) => match to {
+                        0 => u64::from(x as u8),
+                        1 => x as i32 as u64,
+                        2 => x as i64 as u64,
+                        _ => x as u64,
+                    },
+                };
+                match to {
+                    0 => self.push(&[bits as u8]),
+                    1 => self.push(&(bits as u32).to_le_bytes()),
+                    4 => {
+                        let x = match num {
+                            Num::Signed(n) => n as f32,
+                            Num::Unsigned(n) => n as f32,
+                            Num::Float(x) => x as f32,
+                        };
+                        self.push(&canonical_f32(x).to_le_bytes());
+                    }
+                    5 => {
+                        let x = match num {
+                            Num::Signed(n) => n as f64,
+                            Num::Unsigned(n) => n as f64,
+                            Num::Float(x) => x,
+                        };
+                        self.push(&canonical_f64(x).to_le_bytes());
+                    }
+                    _ => self.push(&bits.to_le_bytes()),
+                }
+            }
+            // add i64s
+            67 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&b.wrapping_add(a).to_le_bytes());
+            }
+            // multiply i64s
+            68 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&b.wrapping_mul(a).to_le_bytes());
+            }
+            // divide i64s
+            69 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.wrapping_div(a).to_le_bytes());
+            }
+            // modulo i64
+            70 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.wrapping_rem(a).to_le_bytes());
+            }
+            // checked add i64
+            71 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                let Some(result) = b.checked_add(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // checked multiply i64
+            72 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                let Some(result) = b.checked_mul(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // checked divide i64
+            73 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                let Some(result) = b.checked_div(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // saturating add i64
+            74 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&b.saturating_add(a).to_le_bytes());
+            }
+            // saturating multiply i64
+            75 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&b.saturating_mul(a).to_le_bytes());
+            }
+            // saturating divide i64
+            76 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.saturating_div(a).to_le_bytes());
+            }
+            // subtract i64
+            77 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&b.wrapping_sub(a).to_le_bytes());
+            }
+            // negate i64
+            78 => {
+                let a = self.pop_i64()?;
+                self.push(&a.wrapping_neg().to_le_bytes());
+            }
+            // equal i64
+            79 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&i32::from(b == a).to_le_bytes());
+            }
+            // less than i64
+            80 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&i32::from(b < a).to_le_bytes());
+            }
+            // bitwise and i64
+            81 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&(b & a).to_le_bytes());
+            }
+            // bitwise or i64
+            82 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&(b | a).to_le_bytes());
+            }
+            // bitwise xor i64
+            83 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&(b ^ a).to_le_bytes());
+            }
+            // shift left i64
+            84 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&b.wrapping_shl(a as u32).to_le_bytes());
+            }
+            // shift right i64
+            85 => {
+                let a = self.pop_i64()?;
+                let b = self.pop_i64()?;
+                self.push(&b.wrapping_shr(a as u32).to_le_bytes());
+            }
+            // add u64s
+            86 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&b.wrapping_add(a).to_le_bytes());
+            }
+            // multiply u64s
+            87 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&b.wrapping_mul(a).to_le_bytes());
+            }
+            // divide u64s
+            88 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.wrapping_div(a).to_le_bytes());
+            }
+            // modulo u64
+            89 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                if a == 0 {
+                    return Err(self.fault("Division by zero!"));
+                }
+                self.push(&b.wrapping_rem(a).to_le_bytes());
+            }
+            // checked add u64
+            90 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                let Some(result) = b.checked_add(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // checked multiply u64
+            91 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                let Some(result) = b.checked_mul(a) else {
+                    return Err(self.fault("Arithmetic overflow!"));
+                };
+                self.push(&result.to_le_bytes());
+            }
+            // saturating add u64
+            92 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&b.saturating_add(a).to_le_bytes());
+            }
+            // saturating multiply u64
+            93 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&b.saturating_mul(a).to_le_bytes());
+            }
+            // subtract u64
+            94 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&b.wrapping_sub(a).to_le_bytes());
+            }
+            // negate u64
+            95 => {
+                let a = self.pop_u64()?;
+                self.push(&a.wrapping_neg().to_le_bytes());
+            }
+            // equal u64
+            96 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&i32::from(b == a).to_le_bytes());
+            }
+            // less than u64
+            97 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&i32::from(b < a).to_le_bytes());
+            }
+            // bitwise and u64
+            98 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&(b & a).to_le_bytes());
+            }
+            // bitwise or u64
+            99 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&(b | a).to_le_bytes());
+            }
+            // bitwise xor u64
+            100 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&(b ^ a).to_le_bytes());
+            }
+            // shift left u64
+            101 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&b.wrapping_shl(a as u32).to_le_bytes());
+            }
+            // shift right u64
+            102 => {
+                let a = self.pop_u64()?;
+                let b = self.pop_u64()?;
+                self.push(&b.wrapping_shr(a as u32).to_le_bytes());
+            }
+            // f32 literal
+            103 => {
+                let bits = self.param_u32(pc)?;
+                self.push(&bits.to_le_bytes());
+            }
+            // f64 literal
+            104 => {
+                let bits = self.param_u64(pc)?;
+                self.push(&bits.to_le_bytes());
+            }
+            // add f32
+            105 => {
+                let a = self.pop_f32()?;
+                let b = self.pop_f32()?;
+                self.push(&canonical_f32(b + a).to_le_bytes());
+            }
+            // add f64
+            106 => {
+                let a = self.pop_f64()?;
+                let b = self.pop_f64()?;
+                self.push(&canonical_f64(b + a).to_le_bytes());
+            }
+            // subtract f32
+            107 => {
+                let a = self.pop_f32()?;
+                let b = self.pop_f32()?;
+                self.push(&canonical_f32(b - a).to_le_bytes());
+            }
+            // subtract f64
+            108 => {
+                let a = self.pop_f64()?;
+                let b = self.pop_f64()?;
+                self.push(&canonical_f64(b - a).to_le_bytes());
+            }
+            // multiply f32
+            109 => {
+                let a = self.pop_f32()?;
+                let b = self.pop_f32()?;
+                self.push(&canonical_f32(b * a).to_le_bytes());
+            }
+            // multiply f64
+            110 => {
+                let a = self.pop_f64()?;
+                let b = self.pop_f64()?;
+                self.push(&canonical_f64(b * a).to_le_bytes());
+            }
+            // divide f32
+            111 => {
+                let a = self.pop_f32()?;
+                let b = self.pop_f32()?;
+                self.push(&canonical_f32(b / a).to_le_bytes());
+            }
+            // divide f64
+            112 => {
+                let a = self.pop_f64()?;
+                let b = self.pop_f64()?;
+                self.push(&canonical_f64(b / a).to_le_bytes());
+            }
+            // negate f32
+            113 => {
+                let a = self.pop_f32()?;
+                self.push(&canonical_f32(-a).to_le_bytes());
+            }
+            // negate f64
+            114 => {
+                let a = self.pop_f64()?;
+                self.push(&canonical_f64(-a).to_le_bytes());
+            }
+            // equal f32
+            115 => {
+                let a = self.pop_f32()?;
+                let b = self.pop_f32()?;
+                self.push(&i32::from(b == a).to_le_bytes());
+            }
+            // equal f64
+            116 => {
+                let a = self.pop_f64()?;
+                let b = self.pop_f64()?;
+                self.push(&i32::from(b == a).to_le_bytes());
+            }
+            // less than f32
+            117 => {
+                let a = self.pop_f32()?;
+                let b = self.pop_f32()?;
+                self.push(&i32::from(b < a).to_le_bytes());
+            }
+            // less than f64
+            118 => {
+                let a = self.pop_f64()?;
+                let b = self.pop_f64()?;
+                self.push(&i32::from(b < a).to_le_bytes());
+            }
+            // inject
+            119 => {
+                let tag = self.param_u8(pc)?;
+                let size = self.param_size(pc)? as usize;
+                let max = self.param_size(pc)? as usize;
+                // the payload, then zeros up to the size of the largest component, then the tag
+                self.stack.resize(self.stack.len() + max - size, 0);
+                self.push(&i32::from(tag).to_le_bytes());
+            }
+            // case
+            120 => {
+                let max = self.param_size(pc)? as usize;
+                let n = self.param_u8(pc)?;
+                let mut fs = vec![];
+                for _ in 0..n {
+                    fs.push(self.pop_u32()?);
+                }
+                let tag = self.pop_i32()? as u32;
+                // the sizes of the components follow, and the padding after this one goes
+                let mut size_pc = *pc + tag * 8;
+                let size = self.param_size(&mut size_pc)? as usize;
+                self.pop(max - size)?;
+                *pc = fs[tag as usize];
+                self.exception_handler.f = 0;
+            }
+            // switch i32
+            121 => {
+                let n = self.param_u8(pc)?;
+                let mut fs = vec![];
+                for _ in 0..n {
+                    fs.push(self.pop_u32()?);
+                }
+                let default_f = self.pop_u32()?;
+                let x = self.pop_i32()?;
+                *pc = usize::try_from(x)
+                    .ok()
+                    .and_then(|x| fs.get(x).copied())
+                    .unwrap_or(default_f);
+                self.exception_handler.f = 0;
+            }
+            // switch u8
+            122 => {
+                let n = self.param_u8(pc)?;
+                let mut fs = vec![];
+                for _ in 0..n {
+                    fs.push(self.pop_u32()?);
+                }
+                let default_f = self.pop_u32()?;
+                let x = self.pop_u8()?;
+                *pc = fs.get(usize::from(x)).copied().unwrap_or(default_f);
+                self.exception_handler.f = 0;
+            }
+            // new filled array
+            123 => {
+                let elem_size = self.param_size(pc)?;
+                let len = self.pop_i32()?;
+                if len < 0 {
+                    return Err(self.fault(&format!("Negative array length ({}).", len)));
+                }
+                let elem = self.pop(elem_size as usize)?;
+                let handle = self.pop_u64()?;
+                let size = elem_size * len as u64;
+                let ptr = self.alloc_object(handle, size + 8)?;
+                self.write_mem(ptr.reference, &size.to_le_bytes())?;
+                self.write_mem(ptr.reference + 8, &elem.repeat(len as usize))?;
+                self.push(&ptr.to_bytes());
+            }
+            _ => {
+                print!(
+                    "internal error!! Unknown IR op {}, please let the SaberVM team know!!",
+                    op
+                );
+                return Err(Stop::Internal);
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,111 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! SaberVM as a library, for hosts that want to verify and run SaberVM bytecode in-process.
+//!
+//! The entry points are [`load`], which parses and verifies a module
+//! (or [`load_all`], which reports every function's errors instead of just the first),
+//! [`link()`], which checks that a set of loaded modules can run together,
+//! and [`run`], which runs linked modules.
+//! Errors are [`Error`]s, which print as the same messages the `sabervm` command line tool shows,
+//! except that the tool also gives source locations from the module's [`debug_info`]
+//! and the human-readable names from its [`names`].
+//! Tools can take errors apart with [`Error::code`], [`Error::pos`], [`Error::op`], [`Error::expected`],
+//! and [`Error::found`] instead of parsing messages, or get all of that as JSON from `error_msgs::json`.
+//! Every code has a longer explanation in the [`explain`] module.
+//!
+//! ```no_run
+//! let bytes = std::fs::read("bin.svm").unwrap();
+//! let runtime = std::fs::read("runtime.svm").unwrap();
+//! let programs = vec![sabervm::load(&bytes)?, sabervm::load(&runtime)?];
+//! let linked = sabervm::link(programs)?;
+//! match sabervm::run(&linked, &sabervm::Config::default()) {
+//!     Ok(status) => println!("the program halted with status {}", status),
+//!     Err(e) => println!("the program stopped early: {:?}", e),
+//! }
+//! # Ok::<(), sabervm::Error>(())
+//! ```
+//!
+//! The passes are also available individually, as the `parse`, `verify`, and `vm` modules,
+//! and the runtime system is also available in Rust, as the `interp` module.
+//! Compilers can generate modules with the [`builder`] module instead of writing bytes by hand.
+
+pub mod header;
+pub mod assemble;
+pub mod builder;
+pub mod disassemble;
+pub mod explain;
+pub mod interp;
+pub mod link;
+pub mod pretty;
+pub mod error_msgs;
+pub mod parse;
+pub mod verify;
+pub mod vm;
+
+pub use header::{ByteStream, DebugInfo, Diagnostic, Error, IRProgram, Names};
+pub use vm::RuntimeError;
+
+/// The options for running programs.
+#[derive(Default)]
+pub struct Config {
+    /// Dump the linked IR before running, if set.
+    pub dump: Option<vm::Dump>,
+    /// The runtime system to run on, `vm.c` by default.
+    pub backend: vm::Backend,
+}
+
+/// Verified modules whose imports are all exported by one of them, so they can run together.
+pub struct Linked {
+    ir_programs: Vec<IRProgram>,
+}
+
+impl Linked {
+    pub fn ir_programs(&self) -> &[IRProgram] {
+        &self.ir_programs
+    }
+}
+
+/// Parse and verify a module.
+pub fn load(bytes: &[u8]) -> Result<IRProgram, Error> {
+    let (data_section, debug_info, names, types_instrs, unverified_stmts) = parse::go(bytes)?;
+    verify::go(data_section, debug_info, names, types_instrs, unverified_stmts)
+}
+
+/// Parse and verify a module, checking every function instead of stopping at the first error.
+pub fn load_all(bytes: &[u8]) -> Result<IRProgram, Vec<Diagnostic>> {
+    let (data_section, debug_info, names, types_instrs, unverified_stmts) =
+        parse::go(bytes).map_err(|error| vec![Diagnostic { label: None, error }])?;
+    verify::go_all(data_section, debug_info, names, types_instrs, unverified_stmts)
+}
+
+/// The debug section of a module, if it has one, for giving the source locations of errors from `load`
+/// with `error_msgs::msg`.
+pub fn debug_info(bytes: &[u8]) -> Option<DebugInfo> {
+    parse::debug_section(bytes).ok().flatten()
+}
+
+/// The names section of a module, if it has one, for naming the functions, type variables, and regions
+/// in errors from `load` with `error_msgs::msg`.
+pub fn names(bytes: &[u8]) -> Option<Names> {
+    parse::names_section(bytes).ok().flatten()
+}
+
+/// Check that the modules can run together.
+/// The main function of the first module will be the entry point.
+pub fn link(ir_programs: Vec<IRProgram>) -> Result<Linked, Error> {
+    link::check_imports(&ir_programs)?;
+    Ok(Linked { ir_programs })
+}
+
+/// Run linked modules, returning the status the program passed to `halt`,
+/// or a [`RuntimeError`] if it stopped without halting, after the runtime system printed the error.
+///
+/// On the default backend, `vm.c`, the runtime system's state is global, so runs on it
+/// from different threads wait for each other. The Rust backend keeps its state per run.
+pub fn run(linked: &Linked, config: &Config) -> Result<u8, RuntimeError> {
+    vm::go(&linked.ir_programs, config.dump.as_ref(), config.backend)
+}
@@ -0,0 +1,345 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::assemble::{module_bytes, op_to_bytes};
+use crate::header::*;
+use crate::parse;
+use crate::verify::{self, type_eq, type_pass};
+use std::collections::{BTreeMap, HashMap};
+
+/// Check that every import of every program is exported by one of the programs, with the same type,
+/// and that no two programs export the same ID, like `go` does when it links them.
+pub fn check_imports(ir_programs: &[IRProgram]) -> Result<(), Error> {
+    check(ir_programs, false)
+}
+
+/// Check that the imports that are exported by one of the programs have the export's type,
+/// and that no two programs export the same ID, but allow imports that none of them export.
+pub fn check_compatible(ir_programs: &[IRProgram]) -> Result<(), Error> {
+    check(ir_programs, true)
+}
+
+fn check(ir_programs: &[IRProgram], allow_unresolved: bool) -> Result<(), Error> {
+    let mut exports = HashMap::new();
+    for prog in ir_programs {
+        let mut ids: Vec<_> = prog.exports.iter().collect();
+        ids.sort();
+        for ((a, b), label) in ids {
+            if exports.insert((*a, *b), &prog.types[label]).is_some() {
+                return Err(Error::LinkErrorDuplicateExport(*a, *b));
+            }
+        }
+    }
+    for prog in ir_programs {
+        let mut imports: Vec<_> = prog.imports.iter().collect();
+        imports.sort();
+        for (label, (a, b)) in imports {
+            match exports.get(&(*a, *b)) {
+                None if allow_unresolved => {}
+                None => return Err(Error::LinkErrorUnresolvedImport(*a, *b)),
+                Some(t2) => {
+                    if !type_eq(t2, &prog.types[label]) {
+                        return Err(Error::LinkErrorImportTypeMismatch(
+                            *a,
+                            *b,
+                            (*t2).clone(),
+                            prog.types[label].clone(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Verify several modules and link them into a single module.
+/// Imports that another of the modules exports become references to that function,
+/// after checking that the forward declarations agree on its type.
+/// Imports that none of the modules export stay imports, so the result can be linked further.
+/// The main function of the first module is the main function of the result.
+pub fn go(modules: &[ByteStream]) -> Result<ByteStream, Error> {
+    let mut parsed = vec![];
+    for bytes in modules {
+        let (data_section, debug_info, names, forward_decs, stmts) = parse::go(bytes)?;
+        verify::go(data_section, debug_info, names, forward_decs, stmts)?;
+        // verification consumes the module, so parse it again for linking
+        let (data_section, debug_info, names, forward_decs, stmts) = parse::go(bytes)?;
+        let mut types = HashMap::new();
+        let mut pos = 0;
+        let mut fresh_id = 0;
+        for decl in &forward_decs {
+            let (label, _vis, t, new_fresh_id) = type_pass(decl, pos, fresh_id)?;
+            types.insert(label, t);
+            fresh_id = new_fresh_id;
+            let ForwardDec::Func(_, _, ops) = decl;
+            pos += ops.len() as Pos + 1;
+        }
+        parsed.push((data_section, debug_info, names, forward_decs, stmts, types));
+    }
+
+    // give every function of every module its label in the linked module,
+    // except the imports that will be resolved to some module's export
+    let mut exports = HashMap::new();
+    for (m, (_, _, _, forward_decs, _, types)) in parsed.iter().enumerate() {
+        for ForwardDec::Func(label, vis, _) in forward_decs {
+            if let Visibility::Export(a, b) = vis {
+                if exports.insert((*a, *b), (m, *label, &types[label])).is_some() {
+                    return Err(Error::LinkErrorDuplicateExport(*a, *b));
+                }
+            }
+        }
+    }
+    let mut labels = HashMap::new();
+    let mut next_label = 0;
+    for (m, (_, _, _, forward_decs, _, _)) in parsed.iter().enumerate() {
+        for ForwardDec::Func(label, vis, _) in forward_decs {
+            match vis {
+                Visibility::Import(a, b) if exports.contains_key(&(*a, *b)) => {}
+                _ => {
+                    labels.insert((m, *label), next_label);
+                    next_label += 1;
+                }
+            }
+        }
+    }
+    for (m, (_, _, _, forward_decs, _, types)) in parsed.iter().enumerate() {
+        for ForwardDec::Func(label, vis, _) in forward_decs {
+            if let Visibility::Import(a, b) = vis {
+                if let Some((m2, label2, t2)) = exports.get(&(*a, *b)) {
+                    if !type_eq(t2, &types[label]) {
+                        return Err(Error::LinkErrorImportTypeMismatch(
+                            *a,
+                            *b,
+                            (*t2).clone(),
+                            types[label].clone(),
+                        ));
+                    }
+                    labels.insert((m, *label), labels[&(*m2, *label2)]);
+                }
+            }
+        }
+    }
+
+    // the forward declarations of every module come before all the definitions,
+    // so ops move, and their source locations and the names of the variables they introduce have to move with them
+    let mut data_section: Vec<u8> = vec![];
+    let mut files = vec![];
+    let mut func_names = BTreeMap::new();
+    let mut decl_ops = vec![];
+    let mut body_ops = vec![];
+    let mut decl_locations = vec![];
+    let mut body_locations = vec![];
+    let mut decl_var_names = vec![];
+    let mut body_var_names = vec![];
+    for (m, (module_data_section, module_debug_info, module_names, forward_decs, stmts, _)) in parsed.iter().enumerate() {
+        let data_offset = data_section.len() as u32;
+        data_section.extend(module_data_section);
+        let file_offset = files.len() as u32;
+        if let Some(module_debug_info) = module_debug_info {
+            files.extend(module_debug_info.files.iter().cloned());
+        }
+        let location = |pos: Pos| {
+            let loc = module_debug_info.as_ref()?.locations.get(&pos)?;
+            Some(SourceLoc { file: file_offset + loc.file, ..*loc })
+        };
+        let var_name = |pos: Pos| module_names.as_ref()?.vars.get(&pos).cloned();
+        if let Some(module_names) = module_names {
+            for (label, name) in &module_names.funcs {
+                let resolved_import = forward_decs.iter().any(|ForwardDec::Func(label2, vis, _)| {
+                    label2 == label && matches!(vis, Visibility::Import(a, b) if exports.contains_key(&(*a, *b)))
+                });
+                if resolved_import {
+                    // a resolved import gets the name of the export it resolves to, if that has one
+                    func_names.entry(labels[&(m, *label)]).or_insert_with(|| name.clone());
+                } else {
+                    func_names.insert(labels[&(m, *label)], name.clone());
+                }
+            }
+        }
+        let mut pos = 0;
+        for ForwardDec::Func(_, vis, ops) in forward_decs {
+            let terminator = match vis {
+                Visibility::Local => Op1::Lced,
+                Visibility::Export(a, b) => Op1::Export(*a, *b),
+                Visibility::Import(a, b) if exports.contains_key(&(*a, *b)) => {
+                    pos += ops.len() as Pos + 1;
+                    continue;
+                }
+                Visibility::Import(a, b) => Op1::Import(*a, *b),
+            };
+            for op in ops.iter().chain([&terminator]) {
+                if let Some(loc) = location(pos) {
+                    decl_locations.push((decl_ops.len(), loc));
+                }
+                if let Some(name) = var_name(pos) {
+                    decl_var_names.push((decl_ops.len(), name));
+                }
+                decl_ops.push(*op);
+                pos += 1;
+            }
+        }
+        for Stmt1::Func(_, _, ops) in stmts {
+            for op in ops {
+                let op = match op {
+                    Op1::GlobalFunc(label) => Op1::GlobalFunc(labels[&(m, *label)]),
+                    Op1::Data(loc) => Op1::Data(data_offset + loc),
+                    op => *op,
+                };
+                if let Some(loc) = location(pos) {
+                    body_locations.push((body_ops.len(), loc));
+                }
+                if let Some(name) = var_name(pos) {
+                    body_var_names.push((body_ops.len(), name));
+                }
+                body_ops.push(op);
+                pos += 1;
+            }
+        }
+    }
+    let debug_info = parsed.iter().any(|(_, debug_info, _, _, _, _)| debug_info.is_some()).then(|| DebugInfo {
+        files,
+        locations: decl_locations
+            .into_iter()
+            .chain(body_locations.into_iter().map(|(i, loc)| (decl_ops.len() + i, loc)))
+            .map(|(i, loc)| (i as Pos, loc))
+            .collect(),
+    });
+    let names = parsed.iter().any(|(_, _, names, _, _, _)| names.is_some()).then(|| Names {
+        funcs: func_names,
+        vars: decl_var_names
+            .into_iter()
+            .chain(body_var_names.into_iter().map(|(i, name)| (decl_ops.len() + i, name)))
+            .map(|(i, name)| (i as Pos, name))
+            .collect(),
+    });
+    let op_bytes: Vec<u8> = decl_ops.iter().chain(&body_ops).flat_map(op_to_bytes).collect();
+    Ok(module_bytes(&data_section, debug_info.as_ref(), names.as_ref(), next_label, &op_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(srcs: &[&str]) -> Result<ByteStream, Error> {
+        let modules: Vec<ByteStream> = srcs.iter().map(|src| crate::assemble::go(src).unwrap()).collect();
+        go(&modules)
+    }
+
+    /// A module whose main function calls the function it imports as "f".
+    const IMPORTER: &str = "func 0; lced
+func 0; import \"f\"
+func_name 1 \"alias\"
+
+global_func 1; call
+";
+
+    /// A module that exports a function halting with 7 as "f".
+    const EXPORTER: &str = "func 0; lced
+func 0; export \"f\"
+func_name 1 \"real\"
+
+u8_lit 0; halt
+
+u8_lit 7; halt
+";
+
+    #[test]
+    fn repo_modules_link() {
+        let bin = include_bytes!("../bin.svm").to_vec();
+        let runtime = include_bytes!("../runtime.svm").to_vec();
+        let linked = crate::load(&go(&[bin, runtime]).unwrap()).unwrap();
+        assert!(linked.imports.is_empty());
+    }
+
+    #[test]
+    fn import_resolves_to_the_export() {
+        let linked = link(&[IMPORTER, EXPORTER]).unwrap();
+        let prog = crate::load(&linked).unwrap();
+        assert!(prog.imports.is_empty());
+        // the import is gone, so the importer's main calls the exporter's second function
+        let text = crate::disassemble::go(&linked).unwrap();
+        assert!(text.contains("global_func 2"), "{}", text);
+    }
+
+    #[test]
+    fn resolved_import_takes_the_name_of_the_export() {
+        let names = crate::names(&link(&[IMPORTER, EXPORTER]).unwrap()).unwrap();
+        assert_eq!(names.funcs[&2], "real");
+        let names = crate::names(&link(&[EXPORTER, IMPORTER]).unwrap()).unwrap();
+        assert_eq!(names.funcs[&1], "real");
+    }
+
+    #[test]
+    fn import_of_the_first_modules_main_function() {
+        let main = "func 0; export \"main\"
+
+u8_lit 7; halt
+";
+        let importer = "func 0; import \"main\"
+func 0; lced
+
+global_func 0; call
+";
+        let linked = link(&[main, importer]).unwrap();
+        let prog = crate::load(&linked).unwrap();
+        assert!(prog.imports.is_empty());
+        let text = crate::disassemble::go(&linked).unwrap();
+        // the importer's local function is the linked module's second, and calls the main function
+        assert!(text.contains("# forward declarations: 2 functions"), "{}", text);
+        assert!(text.contains("global_func 0"), "{}", text);
+    }
+
+    #[test]
+    fn unresolved_imports_stay_imports() {
+        let prog = crate::load(&link(&[IMPORTER]).unwrap()).unwrap();
+        assert_eq!(prog.imports.len(), 1);
+    }
+
+    fn load_and_link(srcs: &[&str]) -> Result<crate::Linked, Error> {
+        let progs = srcs.iter().map(|src| crate::load(&crate::assemble::go(src).unwrap()).unwrap()).collect();
+        crate::link(progs)
+    }
+
+    #[test]
+    fn loaded_modules_link() {
+        assert!(load_and_link(&[IMPORTER, EXPORTER]).is_ok());
+        assert!(matches!(load_and_link(&[IMPORTER]), Err(Error::LinkErrorUnresolvedImport(..))));
+    }
+
+    #[test]
+    fn loaded_modules_with_an_import_of_the_wrong_type_dont_link() {
+        let wrong_type = EXPORTER.replace("func 0; export", "i32; func 1; export");
+        assert!(matches!(
+            load_and_link(&[IMPORTER, &wrong_type]),
+            Err(Error::LinkErrorImportTypeMismatch(..))
+        ));
+        let prog = |src: &str| crate::load(&crate::assemble::go(src).unwrap()).unwrap();
+        assert!(matches!(
+            check_compatible(&[prog(IMPORTER), prog(&wrong_type)]),
+            Err(Error::LinkErrorImportTypeMismatch(..))
+        ));
+        assert!(check_compatible(&[prog(IMPORTER)]).is_ok());
+    }
+
+    #[test]
+    fn loaded_modules_exporting_the_same_id_dont_link() {
+        assert!(matches!(
+            load_and_link(&[IMPORTER, EXPORTER, EXPORTER]),
+            Err(Error::LinkErrorDuplicateExport(..))
+        ));
+    }
+
+    #[test]
+    fn link_errors() {
+        assert!(matches!(link(&[EXPORTER, EXPORTER]), Err(Error::LinkErrorDuplicateExport(..))));
+        let wrong_type = EXPORTER.replace("func 0; export", "i32; func 1; export");
+        assert!(matches!(
+            link(&[IMPORTER, &wrong_type]),
+            Err(Error::LinkErrorImportTypeMismatch(..))
+        ));
+    }
+}
@@ -4,37 +4,362 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-mod header;
-mod pretty;
-mod error_msgs;
-mod parse;
-mod verify;
-mod vm;
+use sabervm::{assemble, disassemble, error_msgs, explain, header, pretty, vm};
 
 use std::fs;
 use std::env;
 use std::process::exit;
+use std::sync::OnceLock;
 
-fn go(bytes: Vec<header::ByteStream>) -> Result<(), header::Error> {
+// Exit statuses for failures before the program runs, and for a program that stops without halting.
+// Otherwise SaberVM exits with the status the program passed to `halt`. That can be any byte, these included,
+// so only the error message SaberVM prints tells them apart.
+const EXIT_RUNTIME_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 64;
+const EXIT_INVALID: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+const EXIT_CANT_CREATE: i32 = 73;
+
+const USAGE: &str = "\
+Usage: sabervm <command> [<args>]
+
+Commands:
+    run [<options>] <file.svm>...       verify the modules and run the main function of the first one
+    check [<options>] <file.svm>...     parse and verify the modules without running them,
+                                        checking the imports they export to each other
+    dump-ir [<options>] <file.svm>...   print the linked IR of the modules without running them
+    link <file.svm>... -o <out.svm>     link the modules into a single module
+    asm <file.sasm> -o <out.svm>        assemble a text module
+    disasm <file.svm>                   print a module as text
+    explain <code>                      explain the error with the code, like `E0014`
+    help [<command>]                    print this message, or the part of it about a command
+
+Options:
+    --all-errors                        (run, check, dump-ir) report the errors in every function, not just the first
+    --error-format <text|json>          (all) how to print errors, `text` by default;
+                                        `json` prints one object per line with the module and the error
+    --dump-ir <file>                    (run) write the linked IR to a file, or to stdout if the file is `-`
+    --dump-format <text|json>           (run, dump-ir) the format of the IR listing, `text` by default
+    --backend <c|rust>                  (run) the runtime system to run on, `c` (vm.c) by default;
+                                        `rust` is an interpreter in Rust that behaves the same
+
+Exit status:
+    0 or the program's own status       the program ran
+    1                                   the program hit a runtime error
+    64                                  bad command line arguments
+    65                                  the input failed to assemble, parse, verify, or link
+    66                                  an input file couldn't be read
+    73                                  the output file couldn't be written
+
+A program can halt with any status from 0 to 255, so its status can be one of the others too;
+SaberVM prints an error message whenever it fails itself, which is how to tell them apart.
+Every command takes `--help` to print the part of this message about it.
+";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+static ERROR_FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}\n\n{}", msg, USAGE);
+    exit(EXIT_USAGE);
+}
+
+/// The entries of a section of `USAGE`, each a line and the lines indented under it.
+fn usage_entries(section: &str) -> Vec<String> {
+    let lines = USAGE.lines().skip_while(|line| *line != section).skip(1);
+    let mut entries: Vec<String> = vec![];
+    for line in lines.take_while(|line| !line.is_empty()) {
+        match entries.last_mut() {
+            Some(entry) if line.starts_with("     ") => {
+                entry.push('\n');
+                entry.push_str(line);
+            }
+            _ => entries.push(line.to_string()),
+        }
+    }
+    entries
+}
+
+/// The part of `USAGE` about a command: its own entry and the options it takes,
+/// or `None` if there's no such command.
+fn command_usage(cmd: &str) -> Option<String> {
+    let entry = usage_entries("Commands:")
+        .into_iter()
+        .find(|entry| entry.split_whitespace().next() == Some(cmd))?;
+    let options: Vec<String> = usage_entries("Options:")
+        .into_iter()
+        .filter(|entry| {
+            let cmds = entry.split_once('(').and_then(|(_, rest)| rest.split_once(')'));
+            cmds.is_some_and(|(cmds, _)| cmds.split(", ").any(|c| c == cmd || c == "all"))
+        })
+        .collect();
+    let mut usage = format!("Usage: sabervm {} [<args>]\n\nCommand:\n{}\n", cmd, entry);
+    if !options.is_empty() {
+        usage.push_str(&format!("\nOptions:\n{}\n", options.join("\n")));
+    }
+    Some(usage)
+}
+
+/// Fail on any argument left that looks like an option, once the command has taken its own.
+fn reject_options<'a>(args: impl IntoIterator<Item = &'a String>) {
+    if let Some(arg) = args.into_iter().find(|arg| arg.starts_with('-')) {
+        usage_error(&format!("Unknown option `{}`.", arg));
+    }
+}
+
+/// Print an error in the function with the given label, if that's known,
+/// with source locations and names from the module it's about, if it's about a single module.
+fn report(path: &str, e: header::Error, label: Option<header::Label>, module: Option<&[u8]>) {
+    let debug_info = module.and_then(sabervm::debug_info);
+    let names = module.and_then(sabervm::names);
+    match ERROR_FORMAT.get().copied().unwrap_or(ErrorFormat::Text) {
+        ErrorFormat::Text => {
+            let msg = error_msgs::msg(e, debug_info.as_ref(), names.as_ref());
+            match label {
+                Some(label) => {
+                    let label = pretty::label_name(label, &names.unwrap_or_default());
+                    eprintln!("{}: function {}: {}", path, label, msg)
+                }
+                None => eprintln!("{}: {}", path, msg),
+            }
+        }
+        ErrorFormat::Json => {
+            let error = error_msgs::json(&e, label, debug_info.as_ref(), names.as_ref());
+            eprintln!("{{\"module\":{},\"error\":{}}}", pretty::json_string(path), error);
+        }
+    }
+}
+
+fn invalid(path: &str, e: header::Error, module: Option<&[u8]>) -> ! {
+    report(path, e, None, module);
+    exit(EXIT_INVALID);
+}
+
+fn read(path: &str) -> Vec<u8> {
+    match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            exit(EXIT_NO_INPUT);
+        }
+    }
+}
+
+fn write(path: &str, bytes: Vec<u8>) {
+    if let Err(e) = fs::write(path, bytes) {
+        eprintln!("{}: {}", path, e);
+        exit(EXIT_CANT_CREATE);
+    }
+}
+
+/// Parse and verify each module, exiting on the first error,
+/// or after reporting the errors in every function of every module if `all_errors` is set.
+fn load(paths: &[String], all_errors: bool) -> Vec<header::IRProgram> {
+    reject_options(paths);
+    if paths.is_empty() {
+        usage_error("Expected at least one module.");
+    }
     let mut ir_programs = vec![];
-    for prog in bytes {
-        let (data_section, types_instrs, unverified_stmts) = parse::go(&prog)?;
-        // println!("{}", unverified_stmts.iter().map(|f|f.pretty() + "\n").collect::<String>());
-        let ir_program = verify::go(data_section, types_instrs, unverified_stmts)?;
-        ir_programs.push(ir_program);
+    let mut any_invalid = false;
+    for path in paths {
+        let bytes = read(path);
+        if !all_errors {
+            match sabervm::load(&bytes) {
+                Ok(ir_program) => ir_programs.push(ir_program),
+                Err(e) => invalid(path, e, Some(&bytes)),
+            }
+            continue;
+        }
+        match sabervm::load_all(&bytes) {
+            Ok(ir_program) => ir_programs.push(ir_program),
+            Err(diagnostics) => {
+                for header::Diagnostic { label, error } in diagnostics {
+                    report(path, error, label, Some(&bytes));
+                }
+                any_invalid = true;
+            }
+        }
+    }
+    if any_invalid {
+        exit(EXIT_INVALID);
+    }
+    ir_programs
+}
+
+/// Remove `flag` from the arguments, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != len
+}
+
+/// Remove `<flag> <value>` from the arguments, returning the value.
+fn take_option(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == flag)?;
+    if i + 1 == args.len() {
+        usage_error(&format!("Expected a value after `{}`.", flag));
+    }
+    args.remove(i);
+    Some(args.remove(i))
+}
+
+fn backend(args: &mut Vec<String>) -> vm::Backend {
+    match take_option(args, "--backend").as_deref() {
+        None | Some("c") => vm::Backend::C,
+        Some("rust") => vm::Backend::Rust,
+        Some(backend) => usage_error(&format!("Unknown backend `{}`.", backend)),
+    }
+}
+
+fn dump_format(args: &mut Vec<String>) -> vm::DumpFormat {
+    match take_option(args, "--dump-format").as_deref() {
+        None | Some("text") => vm::DumpFormat::Text,
+        Some("json") => vm::DumpFormat::Json,
+        Some(format) => usage_error(&format!("Unknown IR dump format `{}`.", format)),
     }
-    let status = vm::go(ir_programs);
-    if status != 0 {
-        exit(status.into());
+}
+
+/// Split the arguments into the inputs and the path after `-o`.
+fn inputs_and_output(args: &[String]) -> (&[String], &str) {
+    reject_options(args.iter().filter(|arg| *arg != "-o"));
+    match args {
+        [inputs @ .., flag, out_path] if flag == "-o" && !inputs.is_empty() => (inputs, out_path),
+        _ => usage_error("Expected input files followed by `-o <output file>`."),
+    }
+}
+
+/// Parse and verify the modules, and check that they can be linked together.
+fn load_linked(paths: &[String], all_errors: bool) -> sabervm::Linked {
+    match sabervm::link(load(paths, all_errors)) {
+        Ok(linked) => linked,
+        Err(e) => invalid(&paths.join(", "), e, None),
+    }
+}
+
+fn run(mut args: Vec<String>) {
+    let all_errors = take_flag(&mut args, "--all-errors");
+    let format = dump_format(&mut args);
+    let backend = backend(&mut args);
+    let dump = take_option(&mut args, "--dump-ir").map(|path| vm::Dump {
+        target: if path == "-" {
+            vm::DumpTarget::Stdout
+        } else {
+            vm::DumpTarget::File(path)
+        },
+        format,
+    });
+    let linked = load_linked(&args, all_errors);
+    match sabervm::run(&linked, &sabervm::Config { dump, backend }) {
+        Ok(status) => exit(status.into()),
+        Err(_) => exit(EXIT_RUNTIME_ERROR),
+    }
+}
+
+fn check(mut args: Vec<String>) {
+    let all_errors = take_flag(&mut args, "--all-errors");
+    let ir_programs = load(&args, all_errors);
+    if let Err(e) = sabervm::link::check_compatible(&ir_programs) {
+        invalid(&args.join(", "), e, None);
+    }
+}
+
+fn dump_ir(mut args: Vec<String>) {
+    let all_errors = take_flag(&mut args, "--all-errors");
+    let format = dump_format(&mut args);
+    let linked = load_linked(&args, all_errors);
+    print!("{}", vm::listing(linked.ir_programs(), format));
+}
+
+fn link(args: &[String]) {
+    let (paths, out_path) = inputs_and_output(args);
+    let modules: Vec<header::ByteStream> = paths.iter().map(|path| read(path)).collect();
+    match sabervm::link::go(&modules) {
+        Ok(bytes) => write(out_path, bytes),
+        Err(e) => invalid(&paths.join(", "), e, None),
+    }
+}
+
+fn asm(args: &[String]) {
+    let (src_path, out_path) = match inputs_and_output(args) {
+        ([src_path], out_path) => (src_path, out_path),
+        _ => usage_error("Expected exactly one input file."),
+    };
+    let src = match String::from_utf8(read(src_path)) {
+        Ok(src) => src,
+        Err(_) => {
+            eprintln!("{}: not valid UTF-8", src_path);
+            exit(EXIT_INVALID);
+        }
+    };
+    match assemble::go(&src) {
+        Ok(bytes) => write(out_path, bytes),
+        Err(e) => invalid(src_path, e, None),
+    }
+}
+
+fn disasm(args: &[String]) {
+    reject_options(args);
+    let [path] = args else {
+        usage_error("Expected exactly one input file.");
+    };
+    match disassemble::go(&read(path)) {
+        Ok(text) => print!("{}", text),
+        Err(e) => invalid(path, e, None),
+    }
+}
+
+fn explain(args: &[String]) {
+    reject_options(args);
+    let [code] = args else {
+        usage_error("Expected exactly one error code.");
+    };
+    match explain::explain(code) {
+        Some(explanation) => print!("{}", explanation),
+        None => usage_error(&format!("Unknown error code `{}`.", code)),
     }
-    Ok(())
 }
 
 fn main() {
     let args = env::args().collect::<Vec<_>>();
-    let bytes: Vec<header::ByteStream> = args.iter().skip(1).map(|filename| fs::read(filename).unwrap()).collect();
-    let res = go(bytes);
-    if let Err(e) = res {
-        println!("{}", error_msgs::msg(e));
+    let Some(cmd) = args.get(1) else {
+        usage_error("Expected a command.");
+    };
+    let mut rest = args[2..].to_vec();
+    let error_format = match take_option(&mut rest, "--error-format").as_deref() {
+        None | Some("text") => ErrorFormat::Text,
+        Some("json") => ErrorFormat::Json,
+        Some(format) => usage_error(&format!("Unknown error format `{}`.", format)),
+    };
+    ERROR_FORMAT.set(error_format).ok();
+    if cmd != "help" && rest.iter().any(|arg| arg == "--help" || arg == "-h") {
+        match command_usage(cmd) {
+            Some(usage) => print!("{}", usage),
+            None => usage_error(&format!("Unknown command `{}`.", cmd)),
+        }
+        return;
+    }
+    let rest = &rest;
+    match cmd.as_str() {
+        "run" => run(rest.to_vec()),
+        "check" => check(rest.to_vec()),
+        "dump-ir" => dump_ir(rest.to_vec()),
+        "link" => link(rest),
+        "asm" => asm(rest),
+        "disasm" => disasm(rest),
+        "explain" => explain(rest),
+        "help" | "--help" | "-h" => match rest.as_slice() {
+            [] => print!("{}", USAGE),
+            [cmd] => match command_usage(cmd) {
+                Some(usage) => print!("{}", usage),
+                None => usage_error(&format!("Unknown command `{}`.", cmd)),
+            },
+            _ => usage_error("Expected at most one command."),
+        },
+        _ => usage_error(&format!("Unknown command `{}`.", cmd)),
     }
 }
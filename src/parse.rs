@@ -5,49 +5,142 @@
  */
 
 use crate::header::*;
+use std::collections::BTreeMap;
 
 /// Output of the lexer, input of the parser.
 /// A sequence of (possibly parameterized) opcodes.
 type LexedOpcodes = Vec<Op1>;
 
-/// Lex bytes into (possibly parameterized) intructions.
-fn lex(bytes: &ByteStream) -> Result<(Vec<u8>, LexedOpcodes, u32), Error> {
-    let mut bytes_iter = bytes.iter();
-    let mut lexed_opcodes = vec![];
-    let mut data_section_len_vec: [u8; 4] = [0, 0, 0, 0];
-    for i in 0..4 {
-        let Some(a) = bytes_iter.next() else {
-            dbg!("a");
-            return Err(Error::UnexpectedEOF);
-        };
-        data_section_len_vec[i] = *a;
+/// Split a `u32` off the front of the bytes.
+fn read_u32(bytes: &[u8]) -> Result<(u32, &[u8]), Error> {
+    let (n, rest) = bytes.split_first_chunk::<4>().ok_or(Error::UnexpectedEOF)?;
+    Ok((u32::from_le_bytes(*n), rest))
+}
+
+/// Split a section, which starts with its length, off the front of the bytes.
+fn read_section(bytes: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    let (len, rest) = read_u32(bytes)?;
+    if rest.len() < len as usize {
+        return Err(Error::UnexpectedEOF);
     }
-    let data_section_len_u32 = u32::from_le_bytes(data_section_len_vec);
-    let data_section_len = data_section_len_u32 as usize;
-    // skip past whatever bytes are in the data section
-    let mut data_section = Vec::with_capacity(data_section_len);
-    for _ in 0..data_section_len {
-        data_section.push(
-            *(bytes_iter.next().ok_or_else(|| {
-                dbg!(data_section_len);
-                Error::UnexpectedEOF
-            })?),
-        );
+    Ok(rest.split_at(len as usize))
+}
+
+/// Parse a debug section: the file names, each as a section,
+/// then the number of locations and each location as its op position, file index, line, and column.
+fn parse_debug_section(bytes: &[u8]) -> Result<DebugInfo, Error> {
+    let bad = |_| Error::SyntaxErrorBadDebugSection;
+    let mut debug_info = DebugInfo::default();
+    let (num_files, mut rest) = read_u32(bytes).map_err(bad)?;
+    for _ in 0..num_files {
+        let (file, rest2) = read_section(rest).map_err(bad)?;
+        let file = String::from_utf8(file.to_vec()).map_err(|_| Error::SyntaxErrorBadDebugSection)?;
+        debug_info.files.push(file);
+        rest = rest2;
     }
-    let mut a = [0, 0, 0, 0];
-    for i in 0..4 {
-        match bytes_iter.next() {
-            None => {
-                dbg!("c");
-                return Err(Error::UnexpectedEOF);
-            }
-            Some(b) => {
-                a[i] = *b;
+    let (num_locations, mut rest) = read_u32(rest).map_err(bad)?;
+    for _ in 0..num_locations {
+        let (pos, rest2) = read_u32(rest).map_err(bad)?;
+        let (file, rest2) = read_u32(rest2).map_err(bad)?;
+        let (line, rest2) = read_u32(rest2).map_err(bad)?;
+        let (col, rest2) = read_u32(rest2).map_err(bad)?;
+        rest = rest2;
+        // the positions must be in order, so every module has one encoding
+        let in_order = debug_info.locations.last_key_value().is_none_or(|(last, _)| *last < pos);
+        if !in_order || file as usize >= debug_info.files.len() {
+            return Err(Error::SyntaxErrorBadDebugSection);
+        }
+        debug_info.locations.insert(pos, SourceLoc { file, line, col });
+    }
+    if !rest.is_empty() {
+        return Err(Error::SyntaxErrorBadDebugSection);
+    }
+    Ok(debug_info)
+}
+
+/// Parse a names section: the number of function names and each as its label and the name as a section,
+/// then the number of variable names and each as the position of the op that introduces the variable and the name.
+/// Only the module's `n` functions can have names.
+fn parse_names_section(bytes: &[u8], n: u32) -> Result<Names, Error> {
+    let bad = |_| Error::SyntaxErrorBadNamesSection;
+    // the keys must be in order, so every module has one encoding
+    fn entries(mut rest: &[u8], max: Option<u32>) -> Result<(BTreeMap<u32, String>, &[u8]), Error> {
+        let mut entries: BTreeMap<u32, String> = BTreeMap::new();
+        let (num_entries, rest2) = read_u32(rest)?;
+        rest = rest2;
+        for _ in 0..num_entries {
+            let (key, rest2) = read_u32(rest)?;
+            let (name, rest2) = read_section(rest2)?;
+            rest = rest2;
+            let in_order = entries.last_key_value().is_none_or(|(last, _)| *last < key);
+            let name = String::from_utf8(name.to_vec()).map_err(|_| Error::SyntaxErrorBadNamesSection)?;
+            if !in_order || max.is_some_and(|max| key >= max) {
+                return Err(Error::SyntaxErrorBadNamesSection);
             }
+            entries.insert(key, name);
         }
+        Ok((entries, rest))
+    }
+    let (funcs, rest) = entries(bytes, Some(n)).map_err(bad)?;
+    let (vars, rest) = entries(rest, None).map_err(bad)?;
+    if !rest.is_empty() {
+        return Err(Error::SyntaxErrorBadNamesSection);
     }
-    let mut pos = 8 + data_section_len_u32;
-    let n = u32::from_le_bytes(a);
+    Ok(Names { funcs, vars })
+}
+
+/// The data section of a module, its debug and names sections if there are any,
+/// its function count, and the bytes of its ops.
+type Sections<'a> = (Vec<u8>, Option<DebugInfo>, Option<Names>, u32, &'a [u8]);
+
+/// The sections of a module with its ops lexed, and its function count last.
+pub type Lexed = (Vec<u8>, Option<DebugInfo>, Option<Names>, LexedOpcodes, u32);
+
+/// The sections of a module with its forward declarations and definitions parsed.
+pub type Parsed = (Vec<u8>, Option<DebugInfo>, Option<Names>, Vec<ForwardDec>, Vec<Stmt1>);
+
+/// Read the header and the sections of a module.
+fn read_sections(bytes: &[u8]) -> Result<Sections<'_>, Error> {
+    if bytes.len() < 8 || bytes[0..4] != MAGIC {
+        return Err(Error::SyntaxErrorNotAModule);
+    }
+    let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    if version != FORMAT_VERSION {
+        return Err(Error::SyntaxErrorUnsupportedVersion(version));
+    }
+    let (data_section, rest) = read_section(&bytes[8..])?;
+    let (debug_section, rest) = read_section(rest)?;
+    let debug_info = match debug_section {
+        [] => None,
+        bytes => Some(parse_debug_section(bytes)?),
+    };
+    let (names_section, rest) = read_section(rest)?;
+    let (n, rest) = read_u32(rest)?;
+    let names = match names_section {
+        [] => None,
+        bytes => Some(parse_names_section(bytes, n)?),
+    };
+    Ok((data_section.to_vec(), debug_info, names, n, rest))
+}
+
+/// Read just the debug section of a module, if it has one.
+pub fn debug_section(bytes: &[u8]) -> Result<Option<DebugInfo>, Error> {
+    let (_, debug_info, _, _, _) = read_sections(bytes)?;
+    Ok(debug_info)
+}
+
+/// Read just the names section of a module, if it has one.
+pub fn names_section(bytes: &[u8]) -> Result<Option<Names>, Error> {
+    let (_, _, names, _, _) = read_sections(bytes)?;
+    Ok(names)
+}
+
+/// Lex bytes into (possibly parameterized) intructions.
+pub fn lex(bytes: &[u8]) -> Result<Lexed, Error> {
+    let (data_section, debug_info, names, n, rest) = read_sections(bytes)?;
+    let mut bytes_iter = rest.iter();
+    let mut lexed_opcodes = vec![];
+    let mut pos = 0;
     loop {
         match bytes_iter.next() {
             None => break,
@@ -182,15 +275,80 @@ fn lex(bytes: &ByteStream) -> Result<(Vec<u8>, LexedOpcodes, u32), Error> {
                     None => return Err(Error::SyntaxErrorParamNeeded(pos, *byte)),
                     Some(n) => Op1::Write(*n),
                 },
+                0x2F => Op1::Catch,
+                0x30 => Op1::CheckedAdd,
+                0x31 => Op1::CheckedMul,
+                0x32 => Op1::CheckedDiv,
+                0x33 => Op1::SaturatingAdd,
+                0x34 => Op1::SaturatingMul,
+                0x35 => Op1::SaturatingDiv,
+                0x36 => Op1::Sub,
+                0x37 => Op1::Neg,
+                0x38 => Op1::Eq,
+                0x39 => Op1::Lt,
+                0x3A => Op1::And,
+                0x3B => Op1::Or,
+                0x3C => Op1::Xor,
+                0x3D => Op1::Shl,
+                0x3E => Op1::Shr,
+                0x3F => Op1::I64,
+                0x40 => Op1::U64,
+                0x41 => {
+                    let mut n = [0u8; 8];
+                    for b in n.iter_mut() {
+                        *b = *bytes_iter.next().ok_or(Error::SyntaxErrorParamNeeded(pos, *byte))?;
+                    }
+                    Op1::I64Lit(i64::from_le_bytes(n))
+                }
+                0x42 => {
+                    let mut n = [0u8; 8];
+                    for b in n.iter_mut() {
+                        *b = *bytes_iter.next().ok_or(Error::SyntaxErrorParamNeeded(pos, *byte))?;
+                    }
+                    Op1::U64Lit(u64::from_le_bytes(n))
+                }
+                0x43 => Op1::Cast,
+                0x44 => Op1::F32,
+                0x45 => Op1::F64,
+                0x46 => {
+                    let mut n = [0u8; 4];
+                    for b in n.iter_mut() {
+                        *b = *bytes_iter.next().ok_or(Error::SyntaxErrorParamNeeded(pos, *byte))?;
+                    }
+                    Op1::F32Lit(u32::from_le_bytes(n))
+                }
+                0x47 => {
+                    let mut n = [0u8; 8];
+                    for b in n.iter_mut() {
+                        *b = *bytes_iter.next().ok_or(Error::SyntaxErrorParamNeeded(pos, *byte))?;
+                    }
+                    Op1::F64Lit(u64::from_le_bytes(n))
+                }
+                0x48 => match bytes_iter.next() {
+                    None => return Err(Error::SyntaxErrorParamNeeded(pos, *byte)),
+                    Some(n) => Op1::Variant(*n),
+                },
+                0x49 => match bytes_iter.next() {
+                    None => return Err(Error::SyntaxErrorParamNeeded(pos, *byte)),
+                    Some(i) => Op1::Inj(*i),
+                },
+                0x4A => match bytes_iter.next() {
+                    None => return Err(Error::SyntaxErrorParamNeeded(pos, *byte)),
+                    Some(n) => Op1::Case(*n),
+                },
+                0x4B => match bytes_iter.next() {
+                    None => return Err(Error::SyntaxErrorParamNeeded(pos, *byte)),
+                    Some(n) => Op1::Switch(*n),
+                },
                 op => return Err(Error::SyntaxErrorUnknownOp(pos, *op)),
             }),
         }
         pos += 1;
     }
-    Ok((data_section, lexed_opcodes, n))
+    Ok((data_section, debug_info, names, lexed_opcodes, n))
 }
 
-fn parse_forward_decs(
+pub fn parse_forward_decs(
     tokens: &LexedOpcodes,
     n: u32,
 ) -> Result<(Vec<ForwardDec>, std::slice::Iter<'_, Op1>, u32), Error> {
@@ -200,6 +358,7 @@ fn parse_forward_decs(
     let mut pos = 0;
     for i in 0..n {
         loop {
+            pos += 1;
             match tokens_iter.next() {
                 None => {
                     return Err(Error::UnexpectedEOF)
@@ -227,7 +386,6 @@ fn parse_forward_decs(
                 }
                 Some(op) => current_stmt_opcodes.push(*op),
             }
-            pos += 1;
         }
         current_stmt_opcodes = vec![];
     }
@@ -240,9 +398,11 @@ fn parse(mut tokens_iter: std::slice::Iter<'_, Op1>, forward_decs: &Vec<ForwardD
     for decl in forward_decs {
         match decl {
             ForwardDec::Func(i, Visibility::Local | Visibility::Export(_, _), _) => {
+                let start = pos;
                 loop {
+                    pos += 1;
                     match tokens_iter.next() {
-                        None => break,
+                        None => return Err(Error::UnexpectedEOF),
                         Some(Op1::Call) => {
                             current_stmt_opcodes.push(Op1::Call);
                             break;
@@ -251,32 +411,35 @@ fn parse(mut tokens_iter: std::slice::Iter<'_, Op1>, forward_decs: &Vec<ForwardD
                             current_stmt_opcodes.push(Op1::CallNZ);
                             break;
                         }
+                        Some(Op1::Case(n)) => {
+                            current_stmt_opcodes.push(Op1::Case(*n));
+                            break;
+                        }
+                        Some(Op1::Switch(n)) => {
+                            current_stmt_opcodes.push(Op1::Switch(*n));
+                            break;
+                        }
                         Some(Op1::Halt) => {
                             current_stmt_opcodes.push(Op1::Halt);
                             break;
                         }
                         Some(op) => current_stmt_opcodes.push(*op),
                     }
-                    pos += 1;
                 }
-                parsed_stmts.push(Stmt1::Func(*i, pos, current_stmt_opcodes));
+                parsed_stmts.push(Stmt1::Func(*i, start, current_stmt_opcodes));
                 current_stmt_opcodes = vec![];
             }
             ForwardDec::Func(_, Visibility::Import(_, _), _) => {}
         }
     }
-    if current_stmt_opcodes.len() > 0 {
-        dbg!(current_stmt_opcodes);
-        return Err(Error::UnexpectedEOF);
-    }
     Ok(parsed_stmts)
 }
 
 /// Lex a stream of bytes, maybe return an error, otherwise parse.
-pub fn go(istream: &ByteStream) -> Result<(Vec<u8>, Vec<ForwardDec>, Vec<Stmt1>), Error> {
+pub fn go(istream: &[u8]) -> Result<Parsed, Error> {
     // this is two-pass currently (lex and parse); it would be straightforward to fuse these passes.
-    let (data_section, tokens, n) = lex(istream)?;
+    let (data_section, debug_info, names, tokens, n) = lex(istream)?;
     let (forward_decs, rest, pos) = parse_forward_decs(&tokens, n)?;
     let stmts = parse(rest, &forward_decs, pos)?;
-    Ok((data_section, forward_decs, stmts))
+    Ok((data_section, debug_info, names, forward_decs, stmts))
 }
@@ -8,6 +8,27 @@ use crate::header::*;
 
 pub trait Pretty {
     fn pretty(&self) -> String;
+
+    /// Like `pretty`, but printing functions, type variables, and regions by the names a names section gives them.
+    fn pretty_named(&self, _names: &Names) -> String {
+        self.pretty()
+    }
+}
+
+/// Print a label, followed by the function's name if it has one.
+pub fn label_name(label: Label, names: &Names) -> String {
+    match names.func(label) {
+        Some(name) => format!("{} ({})", label, name),
+        None => label.to_string(),
+    }
+}
+
+/// Print a type variable by its name, or as `a` followed by its number if it doesn't have one.
+pub fn type_var_name(id: Id, names: &Names) -> String {
+    match names.var(id) {
+        Some(name) => name.to_string(),
+        None => "a".to_string() + &id.1.to_string(),
+    }
 }
 
 impl Pretty for Op1 {
@@ -60,12 +81,178 @@ impl Pretty for Op1 {
             Op1::I32ToU8 => "i32_to_u8".to_string(),
             Op1::Read(c) => "read ".to_string() + &c.to_string(),
             Op1::Write(c) => "write ".to_string() + &c.to_string(),
+            Op1::Catch => "catch".to_string(),
+            Op1::CheckedAdd => "checked_add".to_string(),
+            Op1::CheckedMul => "checked_mul".to_string(),
+            Op1::CheckedDiv => "checked_div".to_string(),
+            Op1::SaturatingAdd => "saturating_add".to_string(),
+            Op1::SaturatingMul => "saturating_mul".to_string(),
+            Op1::SaturatingDiv => "saturating_div".to_string(),
+            Op1::Sub => "sub".to_string(),
+            Op1::Neg => "neg".to_string(),
+            Op1::Eq => "eq".to_string(),
+            Op1::Lt => "lt".to_string(),
+            Op1::And => "and".to_string(),
+            Op1::Or => "or".to_string(),
+            Op1::Xor => "xor".to_string(),
+            Op1::Shl => "shl".to_string(),
+            Op1::Shr => "shr".to_string(),
+            Op1::I64 => "i64".to_string(),
+            Op1::U64 => "u64".to_string(),
+            Op1::I64Lit(n) => "i64_lit ".to_string() + &n.to_string(),
+            Op1::U64Lit(n) => "u64_lit ".to_string() + &n.to_string(),
+            Op1::Cast => "cast".to_string(),
+            Op1::F32 => "f32".to_string(),
+            Op1::F64 => "f64".to_string(),
+            Op1::F32Lit(bits) => "f32_lit ".to_string() + &f32_to_str(*bits),
+            Op1::F64Lit(bits) => "f64_lit ".to_string() + &f64_to_str(*bits),
+            Op1::Variant(n) => "variant ".to_string() + &n.to_string(),
+            Op1::Inj(i) => "inj ".to_string() + &i.to_string(),
+            Op1::Case(n) => "case ".to_string() + &n.to_string(),
+            Op1::Switch(n) => "switch ".to_string() + &n.to_string(),
         }
     }
 }
 
-fn int_pair_to_str(a: &u64, b: &u64) -> String {
-    return std::str::from_utf8(&a.to_le_bytes()).unwrap().to_owned() + &std::str::from_utf8(&b.to_le_bytes()).unwrap();
+/// Print a 128-bit import/export ID as a string literal, leaving off the zero bytes of padding on the left.
+pub fn int_pair_to_str(a: &u64, b: &u64) -> String {
+    let id = [a.to_le_bytes(), b.to_le_bytes()].concat();
+    let start = id.iter().position(|b| *b != 0).unwrap_or(id.len());
+    "\"".to_string() + &escape_bytes(&id[start..]) + "\""
+}
+
+/// Print the bits of a float literal as the float, like `-0.0` or `NaN`,
+/// or as the bits in hexadecimal if it's a NaN other than the canonical one, whose sign and payload the float wouldn't show.
+pub fn f32_to_str(bits: u32) -> String {
+    let x = f32::from_bits(bits);
+    if x.is_nan() && bits != f32::NAN.to_bits() {
+        format!("0x{:08x}", bits)
+    } else {
+        format!("{:?}", x)
+    }
+}
+
+pub fn f64_to_str(bits: u64) -> String {
+    let x = f64::from_bits(bits);
+    if x.is_nan() && bits != f64::NAN.to_bits() {
+        format!("0x{:016x}", bits)
+    } else {
+        format!("{:?}", x)
+    }
+}
+
+/// Escape bytes for a string literal in the assembly language.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for b in bytes {
+        match b {
+            b'\n' => out += "\\n",
+            b'\t' => out += "\\t",
+            b'\r' => out += "\\r",
+            b'\\' => out += "\\\\",
+            b'"' => out += "\\\"",
+            0x20..=0x7E => out.push(*b as char),
+            b => out += &format!("\\x{:02x}", b),
+        }
+    }
+    out
+}
+
+/// Quote a string for JSON output.
+pub fn json_string(s: &str) -> String {
+    let mut out = "\"".to_string();
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+/// A type as a JSON value, for tools that want its structure rather than its text.
+pub fn type_json(t: &Type, names: &Names) -> String {
+    match t {
+        Type::I32 => "{\"type\":\"i32\"}".to_string(),
+        Type::U8 => "{\"type\":\"u8\"}".to_string(),
+        Type::I64 => "{\"type\":\"i64\"}".to_string(),
+        Type::U64 => "{\"type\":\"u64\"}".to_string(),
+        Type::F32 => "{\"type\":\"f32\"}".to_string(),
+        Type::F64 => "{\"type\":\"f64\"}".to_string(),
+        Type::Handle(r) => format!("{{\"type\":\"handle\",\"region\":{}}}", region_json(r, names)),
+        Type::Tuple(ts) => format!(
+            "{{\"type\":\"tuple\",\"components\":[{}]}}",
+            ts.iter()
+                .map(|(init, t)| format!("{{\"initialized\":{},\"type\":{}}}", init, type_json(t, names)))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Type::Ptr(t, r) => format!(
+            "{{\"type\":\"ptr\",\"pointee\":{},\"region\":{}}}",
+            type_json(t, names),
+            region_json(r, names)
+        ),
+        Type::Var(id, size) => format!("{{\"type\":\"var\",\"var\":{},\"size\":{}}}", var_json(*id, names), size),
+        Type::Func(ts) => format!(
+            "{{\"type\":\"func\",\"params\":[{}]}}",
+            ts.iter().map(|t| type_json(t, names)).collect::<Vec<_>>().join(",")
+        ),
+        Type::Forall(id, size, t) => format!(
+            "{{\"type\":\"forall\",\"var\":{},\"size\":{},\"body\":{}}}",
+            var_json(*id, names),
+            size,
+            type_json(t, names)
+        ),
+        Type::ForallRegion(r, t, captured_rgns) => format!(
+            "{{\"type\":\"forall_region\",\"region\":{},\"body\":{},\"captured_regions\":[{}]}}",
+            region_json(r, names),
+            type_json(t, names),
+            captured_rgns.iter().map(|r| region_json(r, names)).collect::<Vec<_>>().join(",")
+        ),
+        Type::Exists(id, size, t) => format!(
+            "{{\"type\":\"exists\",\"var\":{},\"size\":{},\"body\":{}}}",
+            var_json(*id, names),
+            size,
+            type_json(t, names)
+        ),
+        Type::Array(t, r) => format!(
+            "{{\"type\":\"array\",\"element\":{},\"region\":{}}}",
+            type_json(t, names),
+            region_json(r, names)
+        ),
+        Type::Variant(ts) => format!(
+            "{{\"type\":\"variant\",\"components\":[{}]}}",
+            ts.iter().map(|t| type_json(t, names)).collect::<Vec<_>>().join(",")
+        ),
+    }
+}
+
+/// A region as a JSON value.
+pub fn region_json(r: &Region, names: &Names) -> String {
+    match r.id {
+        RgnId::Var(id) => format!("{{\"region\":\"var\",\"var\":{},\"unique\":{}}}", var_json(id, names), r.unique),
+        RgnId::DataSection => format!("{{\"region\":\"data_section\",\"unique\":{}}}", r.unique),
+    }
+}
+
+/// A type variable or region variable as a JSON value, with the name the names section gives it if there is one.
+pub fn var_json(id: Id, names: &Names) -> String {
+    match names.var(id) {
+        Some(name) => format!("{{\"id\":{},\"name\":{}}}", id.1, json_string(name)),
+        None => format!("{{\"id\":{}}}", id.1),
+    }
+}
+
+/// A value on the compile-time stack as a JSON value.
+pub fn ctval_json(ctval: &CTStackVal, names: &Names) -> String {
+    match ctval {
+        CTStackVal::Region(r) => format!("{{\"region\":{}}}", region_json(r, names)),
+        CTStackVal::Type(t) => format!("{{\"type\":{}}}", type_json(t, names)),
+        CTStackVal::Size(s) => format!("{{\"size\":{}}}", s),
+    }
 }
 
 impl Pretty for Op2 {
@@ -106,6 +293,95 @@ impl Pretty for Op2 {
             Op2::I32ToU8 => "i32_to_u8".to_string(),
             Op2::Read(c) => "read ".to_string() + &c.to_string(),
             Op2::Write(c) => "write ".to_string() + &c.to_string(),
+            Op2::Catch(s) => "catch ".to_string() + &s.to_string(),
+            Op2::CheckedAddI32 => "checked_add_i32".to_string(),
+            Op2::CheckedAddU8 => "checked_add_u8".to_string(),
+            Op2::CheckedMulI32 => "checked_mul_i32".to_string(),
+            Op2::CheckedMulU8 => "checked_mul_u8".to_string(),
+            Op2::CheckedDivI32 => "checked_div_i32".to_string(),
+            Op2::SaturatingAddI32 => "saturating_add_i32".to_string(),
+            Op2::SaturatingAddU8 => "saturating_add_u8".to_string(),
+            Op2::SaturatingMulI32 => "saturating_mul_i32".to_string(),
+            Op2::SaturatingMulU8 => "saturating_mul_u8".to_string(),
+            Op2::SaturatingDivI32 => "saturating_div_i32".to_string(),
+            Op2::SubI32 => "sub_i32".to_string(),
+            Op2::SubU8 => "sub_u8".to_string(),
+            Op2::NegI32 => "neg_i32".to_string(),
+            Op2::NegU8 => "neg_u8".to_string(),
+            Op2::EqI32 => "eq_i32".to_string(),
+            Op2::EqU8 => "eq_u8".to_string(),
+            Op2::LtI32 => "lt_i32".to_string(),
+            Op2::LtU8 => "lt_u8".to_string(),
+            Op2::AndI32 => "and_i32".to_string(),
+            Op2::AndU8 => "and_u8".to_string(),
+            Op2::OrI32 => "or_i32".to_string(),
+            Op2::OrU8 => "or_u8".to_string(),
+            Op2::XorI32 => "xor_i32".to_string(),
+            Op2::XorU8 => "xor_u8".to_string(),
+            Op2::ShlI32 => "shl_i32".to_string(),
+            Op2::ShlU8 => "shl_u8".to_string(),
+            Op2::ShrI32 => "shr_i32".to_string(),
+            Op2::ShrU8 => "shr_u8".to_string(),
+            Op2::I64Lit(n) => "i64_lit ".to_string() + &n.to_string(),
+            Op2::U64Lit(n) => "u64_lit ".to_string() + &n.to_string(),
+            Op2::Cast(from, to) => "cast ".to_string() + &from.pretty() + " " + &to.pretty(),
+            Op2::F32Lit(bits) => "f32_lit ".to_string() + &f32_to_str(*bits),
+            Op2::F64Lit(bits) => "f64_lit ".to_string() + &f64_to_str(*bits),
+            Op2::AddF32 => "add_f32".to_string(),
+            Op2::AddF64 => "add_f64".to_string(),
+            Op2::SubF32 => "sub_f32".to_string(),
+            Op2::SubF64 => "sub_f64".to_string(),
+            Op2::MulF32 => "mul_f32".to_string(),
+            Op2::MulF64 => "mul_f64".to_string(),
+            Op2::DivF32 => "div_f32".to_string(),
+            Op2::DivF64 => "div_f64".to_string(),
+            Op2::NegF32 => "neg_f32".to_string(),
+            Op2::NegF64 => "neg_f64".to_string(),
+            Op2::EqF32 => "eq_f32".to_string(),
+            Op2::EqF64 => "eq_f64".to_string(),
+            Op2::LtF32 => "lt_f32".to_string(),
+            Op2::LtF64 => "lt_f64".to_string(),
+            Op2::AddI64 => "add_i64".to_string(),
+            Op2::MulI64 => "mul_i64".to_string(),
+            Op2::DivI64 => "div_i64".to_string(),
+            Op2::ModuloI64 => "modulo_i64".to_string(),
+            Op2::CheckedAddI64 => "checked_add_i64".to_string(),
+            Op2::CheckedMulI64 => "checked_mul_i64".to_string(),
+            Op2::CheckedDivI64 => "checked_div_i64".to_string(),
+            Op2::SaturatingAddI64 => "saturating_add_i64".to_string(),
+            Op2::SaturatingMulI64 => "saturating_mul_i64".to_string(),
+            Op2::SaturatingDivI64 => "saturating_div_i64".to_string(),
+            Op2::SubI64 => "sub_i64".to_string(),
+            Op2::NegI64 => "neg_i64".to_string(),
+            Op2::EqI64 => "eq_i64".to_string(),
+            Op2::LtI64 => "lt_i64".to_string(),
+            Op2::AndI64 => "and_i64".to_string(),
+            Op2::OrI64 => "or_i64".to_string(),
+            Op2::XorI64 => "xor_i64".to_string(),
+            Op2::ShlI64 => "shl_i64".to_string(),
+            Op2::ShrI64 => "shr_i64".to_string(),
+            Op2::AddU64 => "add_u64".to_string(),
+            Op2::MulU64 => "mul_u64".to_string(),
+            Op2::DivU64 => "div_u64".to_string(),
+            Op2::ModuloU64 => "modulo_u64".to_string(),
+            Op2::CheckedAddU64 => "checked_add_u64".to_string(),
+            Op2::CheckedMulU64 => "checked_mul_u64".to_string(),
+            Op2::SaturatingAddU64 => "saturating_add_u64".to_string(),
+            Op2::SaturatingMulU64 => "saturating_mul_u64".to_string(),
+            Op2::SubU64 => "sub_u64".to_string(),
+            Op2::NegU64 => "neg_u64".to_string(),
+            Op2::EqU64 => "eq_u64".to_string(),
+            Op2::LtU64 => "lt_u64".to_string(),
+            Op2::AndU64 => "and_u64".to_string(),
+            Op2::OrU64 => "or_u64".to_string(),
+            Op2::XorU64 => "xor_u64".to_string(),
+            Op2::ShlU64 => "shl_u64".to_string(),
+            Op2::ShrU64 => "shr_u64".to_string(),
+            Op2::Inj(i, s, max) => "inj ".to_string() + &i.to_string() + " " + &s.to_string() + " " + &max.to_string(),
+            Op2::SwitchI32(n) => "switch_i32 ".to_string() + &n.to_string(),
+            Op2::SwitchU8(n) => "switch_u8 ".to_string() + &n.to_string(),
+            Op2::NewArrFill(s) => "new_arr_fill ".to_string() + &s.to_string(),
+            Op2::Case(max, sizes) => "case ".to_string() + &max.to_string() + " " + &sizes.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(" "),
         }
     }
 }
@@ -120,51 +396,90 @@ impl Pretty for Stmt1 {
 
 impl Pretty for Stmt2 {
     fn pretty(&self) -> String {
+        self.pretty_named(&Names::default())
+    }
+
+    fn pretty_named(&self, names: &Names) -> String {
         match self {
-            Stmt2::Func(pos, t, ops) => "fn foo".to_string() + &pos.to_string() + ": " + &t.pretty() + " = " + &ops.iter().map(|op|op.pretty()).collect::<Vec<String>>().join("; "),
+            Stmt2::Func(pos, t, ops, _) => {
+                let name = match names.func(*pos) {
+                    Some(name) => name.to_string(),
+                    None => "foo".to_string() + &pos.to_string(),
+                };
+                "fn ".to_string() + &name + ": " + &t.pretty_named(names) + " = " + &ops.iter().map(|op|op.pretty()).collect::<Vec<String>>().join("; ")
+            }
         }
     }
 }
 
 impl Pretty for RgnId {
     fn pretty(&self) -> String {
+        self.pretty_named(&Names::default())
+    }
+
+    fn pretty_named(&self, names: &Names) -> String {
         match self {
-            RgnId::Var(id) => "r".to_string() + &id.1.to_string(),
+            RgnId::Var(id) => match names.var(*id) {
+                Some(name) => name.to_string(),
+                None => "r".to_string() + &id.1.to_string(),
+            },
             RgnId::DataSection => "data_section".to_string(),
         }
     }
 }
 
 impl Pretty for Region {
+    fn pretty(&self) -> String {
+        self.pretty_named(&Names::default())
+    }
+
+    fn pretty_named(&self, names: &Names) -> String {
+        self.id.pretty_named(names)
+    }
+}
+
+impl Pretty for NumType {
     fn pretty(&self) -> String {
         match self {
-            Region { id, .. } => id.pretty()
+            NumType::U8 => "u8".to_string(),
+            NumType::I32 => "i32".to_string(),
+            NumType::I64 => "i64".to_string(),
+            NumType::U64 => "u64".to_string(),
+            NumType::F32 => "f32".to_string(),
+            NumType::F64 => "f64".to_string(),
         }
     }
 }
 
 impl Pretty for Type {
     fn pretty(&self) -> String {
+        self.pretty_named(&Names::default())
+    }
+
+    fn pretty_named(&self, names: &Names) -> String {
         match self {
             Type::I32 => "i32".to_string(),
             Type::U8 => "u8".to_string(),
-            Type::Handle(r) => "handle(".to_string() + &r.pretty() + ")",
-            Type::Tuple(ts) => "(".to_string() + &ts.iter().map(|(_, t)| t.pretty()).collect::<Vec<String>>().join(", ") + ")",
-            Type::Ptr(t, r) => t.pretty() + "@" + &r.pretty(),
-            Type::Var(id, _) => "a".to_string() + &id.1.to_string(),
-            Type::Func(ts) => "(".to_string() + &ts.iter().map(|t| t.pretty()).collect::<Vec<String>>().join(", ") + ")->0",
-            Type::Forall(id, size, t) => "forall a".to_string() + &id.1.to_string() + ": " + &size.to_string() + "byte. " + &t.pretty(),
-            Type::ForallRegion(r, t, _) => "forall ".to_string() + &r.pretty() + ": Rgn" + own_suffix(r) + ". " + &t.pretty(),
-            Type::Exists(id, size, t) => "exists a".to_string() + &id.1.to_string() + ": " + &size.to_string() + "byte. " + &t.pretty(),
-            Type::Array(t, r) => t.pretty() + "[]@" + &r.pretty(),
+            Type::I64 => "i64".to_string(),
+            Type::U64 => "u64".to_string(),
+            Type::F32 => "f32".to_string(),
+            Type::F64 => "f64".to_string(),
+            Type::Handle(r) => "handle(".to_string() + &r.pretty_named(names) + ")",
+            Type::Tuple(ts) => "(".to_string() + &ts.iter().map(|(_, t)| t.pretty_named(names)).collect::<Vec<String>>().join(", ") + ")",
+            Type::Ptr(t, r) => t.pretty_named(names) + "@" + &r.pretty_named(names),
+            Type::Var(id, _) => type_var_name(*id, names),
+            Type::Func(ts) => "(".to_string() + &ts.iter().map(|t| t.pretty_named(names)).collect::<Vec<String>>().join(", ") + ")->0",
+            Type::Forall(id, size, t) => "forall ".to_string() + &type_var_name(*id, names) + ": " + &size.to_string() + "byte. " + &t.pretty_named(names),
+            Type::ForallRegion(r, t, _) => "forall ".to_string() + &r.pretty_named(names) + ": Rgn" + own_suffix(r) + ". " + &t.pretty_named(names),
+            Type::Exists(id, size, t) => "exists ".to_string() + &type_var_name(*id, names) + ": " + &size.to_string() + "byte. " + &t.pretty_named(names),
+            Type::Array(t, r) => t.pretty_named(names) + "[]@" + &r.pretty_named(names),
+            Type::Variant(ts) => "<".to_string() + &ts.iter().map(|t| t.pretty_named(names)).collect::<Vec<String>>().join(" | ") + ">",
         }
     }
 }
 
 fn own_suffix(r: &Region) -> &str {
-    match r {
-        Region { unique, .. } => if *unique { "!" } else { "" },
-    }
+    if r.unique { "!" } else { "" }
 }
 
 impl Pretty for Kind {
@@ -179,9 +494,13 @@ impl Pretty for Kind {
 
 impl Pretty for CTStackVal {
     fn pretty(&self) -> String {
+        self.pretty_named(&Names::default())
+    }
+
+    fn pretty_named(&self, names: &Names) -> String {
         match self {
-            CTStackVal::Region(r) => r.pretty(),
-            CTStackVal::Type(t) => t.pretty(),
+            CTStackVal::Region(r) => r.pretty_named(names),
+            CTStackVal::Type(t) => t.pretty_named(names),
             CTStackVal::Size(s) => s.to_string(),
         }
     }
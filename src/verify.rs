@@ -6,20 +6,53 @@
 
 use crate::header::RgnId::DataSection;
 use crate::header::*;
-use crate::pretty::Pretty;
 use std::collections::HashMap;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
+/// Verify a module, stopping at the first error.
 pub fn go(
     data_section: Vec<u8>,
+    debug_info: Option<DebugInfo>,
+    names: Option<Names>,
     types_instrs: Vec<ForwardDec>,
     unverified_stmts: Vec<Stmt1>,
 ) -> Result<IRProgram, Error> {
+    verify(data_section, debug_info, names, types_instrs, unverified_stmts, true)
+        .map_err(|mut diagnostics| diagnostics.remove(0).error)
+}
+
+/// Verify a module, checking every function independently and reporting all the errors,
+/// in the order of the functions' labels for forward declarations and then in the order of the definitions.
+/// The definitions are only checked if every forward declaration is fine,
+/// since any of them might use any of the declared types.
+pub fn go_all(
+    data_section: Vec<u8>,
+    debug_info: Option<DebugInfo>,
+    names: Option<Names>,
+    types_instrs: Vec<ForwardDec>,
+    unverified_stmts: Vec<Stmt1>,
+) -> Result<IRProgram, Vec<Diagnostic>> {
+    verify(data_section, debug_info, names, types_instrs, unverified_stmts, false)
+}
+
+fn verify(
+    data_section: Vec<u8>,
+    debug_info: Option<DebugInfo>,
+    names: Option<Names>,
+    types_instrs: Vec<ForwardDec>,
+    unverified_stmts: Vec<Stmt1>,
+    fail_fast: bool,
+) -> Result<IRProgram, Vec<Diagnostic>> {
+    let mut diagnostics = vec![];
     let mut types = HashMap::new();
     let mut fresh_id = 0;
     let mut imports = HashMap::new();
     let mut exports = HashMap::new();
+    let mut pos = 0;
     for stmt in types_instrs {
-        match type_pass(&stmt, fresh_id) {
+        match type_pass(&stmt, pos, fresh_id) {
             Ok((l, vis, t, new_fresh_id)) => {
                 types.insert(l, t);
                 match vis {
@@ -33,38 +66,65 @@ pub fn go(
                 }
                 fresh_id = new_fresh_id;
             }
-            Err(e) => return Err(e),
+            Err(error) => {
+                let ForwardDec::Func(label, _, _) = stmt;
+                diagnostics.push(Diagnostic { label: Some(label), error });
+                if fail_fast {
+                    return Err(diagnostics);
+                }
+            }
         }
+        let ForwardDec::Func(_, _, ops) = stmt;
+        pos += ops.len() as Pos + 1;
     }
-    let verified_stmts: Vec<Stmt2> = unverified_stmts
-        .iter()
-        .map(|stmt| definition_pass(data_section.len(), stmt, &types, fresh_id))
-        .collect::<Result<Vec<_>, Error>>()?;
-    match verified_stmts.get(0) {
-        Some(Stmt2::Func(_, Type::Func(param_ts), _)) => {
-            if param_ts.len() != 0 {
-                return Err(Error::TypeErrorMainHasArgs);
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    let mut verified_stmts: Vec<Stmt2> = vec![];
+    let results = definition_passes(data_section.len(), &unverified_stmts, &types, fresh_id);
+    for (stmt, result) in unverified_stmts.iter().zip(results) {
+        match result {
+            Ok(verified_stmt) => verified_stmts.push(verified_stmt),
+            Err(error) => {
+                let Stmt1::Func(label, _, _) = stmt;
+                diagnostics.push(Diagnostic { label: Some(*label), error });
+                if fail_fast {
+                    return Err(diagnostics);
+                }
             }
         }
-        _ => (),
+    }
+    if let Some(Stmt1::Func(label, _, _)) = unverified_stmts.first() {
+        if let Some(Type::Func(param_ts)) = types.get(label) {
+            if !param_ts.is_empty() {
+                diagnostics.push(Diagnostic { label: Some(*label), error: Error::TypeErrorMainHasArgs });
+            }
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
     }
     Ok(IRProgram {
         data_section,
+        debug_info,
+        names,
         imports,
         exports,
+        types,
         funcs: verified_stmts,
     })
 }
 
+/// Find the type a forward declaration declares, given the position of its first op.
 pub fn type_pass(
     stmt: &ForwardDec,
+    mut pos: Pos,
     mut fresh_id: u32,
 ) -> Result<(Label, Visibility, Type, u32), Error> {
     let ForwardDec::Func(label, visibility, ops) = stmt;
     let mut next_region_is_unique = false;
     let mut compile_time_stack: Vec<CTStackVal> = vec![];
     let mut quantification_stack: Vec<Quantification> = vec![];
-    let mut pos = *label;
     for op in ops {
         match op {
             Op1::Unique => next_region_is_unique = true,
@@ -76,7 +136,6 @@ pub fn type_pass(
                 op,
                 &mut compile_time_stack,
                 &mut fresh_id,
-                label,
                 &mut quantification_stack,
             )?,
             Op1::All => handle_all(
@@ -84,12 +143,11 @@ pub fn type_pass(
                 op,
                 &mut compile_time_stack,
                 &mut fresh_id,
-                label,
                 &mut quantification_stack,
             )?,
             Op1::Rgn => handle_rgn(
+                pos,
                 &mut next_region_is_unique,
-                label,
                 &mut fresh_id,
                 &mut compile_time_stack,
                 &mut quantification_stack,
@@ -105,822 +163,1259 @@ pub fn type_pass(
                 id: DataSection,
             })),
             Op1::U8 => compile_time_stack.push(CTStackVal::Type(Type::U8)),
+            Op1::I64 => compile_time_stack.push(CTStackVal::Type(Type::I64)),
+            Op1::U64 => compile_time_stack.push(CTStackVal::Type(Type::U64)),
+            Op1::F32 => compile_time_stack.push(CTStackVal::Type(Type::F32)),
+            Op1::F64 => compile_time_stack.push(CTStackVal::Type(Type::F64)),
+            Op1::Variant(n) => handle_variant(n, pos, op, &mut compile_time_stack)?,
             op => return Err(Error::ForwardDeclRuntimeOp(*op)),
         }
         pos += 1;
     }
     match &compile_time_stack[..] {
-        [CTStackVal::Type(t)] => Ok((*label, *visibility, t.clone(), pos)),
+        [CTStackVal::Type(t)] => Ok((*label, *visibility, t.clone(), fresh_id)),
         _ => return Err(Error::ForwardDeclBadStack(compile_time_stack)),
     }
 }
 
+/// The fewest definitions worth giving a thread of its own.
+const MIN_DEFINITIONS_PER_THREAD: usize = 32;
+
+/// Check the definitions, on as many threads as there are cores once there are enough definitions.
+/// Every definition only needs the forward-declared types, so they can be checked in any order,
+/// but the results are in the order of the definitions, so errors come out the same as checking them one by one.
+pub fn definition_passes(
+    data_section_len: usize,
+    stmts: &[Stmt1],
+    types: &HashMap<Label, Type>,
+    fresh_id: u32,
+) -> Vec<Result<Stmt2, Error>> {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let num_threads = cores.min(stmts.len() / MIN_DEFINITIONS_PER_THREAD);
+    definition_passes_on(num_threads, data_section_len, stmts, types, fresh_id)
+}
+
+/// Check the definitions on the given number of threads, or on this one if that's 1 or less.
+fn definition_passes_on(
+    num_threads: usize,
+    data_section_len: usize,
+    stmts: &[Stmt1],
+    types: &HashMap<Label, Type>,
+    fresh_id: u32,
+) -> Vec<Result<Stmt2, Error>> {
+    if num_threads <= 1 {
+        return stmts
+            .iter()
+            .map(|stmt| definition_pass(data_section_len, stmt, types, fresh_id))
+            .collect();
+    }
+    // the threads take the next unchecked definition until there are none left,
+    // so one big function doesn't hold up the definitions after it
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<Stmt2, Error>)> = thread::scope(|scope| {
+        let threads: Vec<_> = (0..num_threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(stmt) = stmts.get(i) else {
+                            return results;
+                        };
+                        results.push((i, definition_pass(data_section_len, stmt, types, fresh_id)));
+                    }
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
 pub fn definition_pass(
     data_section_len: usize,
     stmt: &Stmt1,
     types: &HashMap<Label, Type>,
-    mut fresh_id: u32,
+    fresh_id: u32,
 ) -> Result<Stmt2, Error> {
     let Stmt1::Func(label, pos, ops) = stmt;
-    let mut pos = *pos;
-    let mut ops_iter = ops.iter();
+    let mut verifier = FunctionVerifier::new(data_section_len, types, *label, *pos, fresh_id)?;
+    for op in ops {
+        verifier.step(op)?;
+    }
+    verifier.finish()
+}
 
-    let Some(my_type) = types.get(label).cloned() else {
-        panic!("Type not found for label {}", label);
-    };
+/// The definition pass partway through a function.
+/// Ops are verified one at a time, so the stacks can be inspected between them.
+pub struct FunctionVerifier<'a> {
+    data_section_len: usize,
+    types: &'a HashMap<Label, Type>,
+    label: Label,
+    my_type: Type,
+    pos: Pos,
+    fresh_id: u32,
+    next_region_is_unique: bool,
     // The stacks used for this pass algorithm.
-    let (mut compile_time_stack, mut stack_type) = setup_verifier(&my_type)?;
-    compile_time_stack.reverse();
-    // println!("Stack type:");
-    // for t in &stack_type {
-    //     println!("- {}", t.pretty());
-    // }
-    let mut quantification_stack: Vec<Quantification> = vec![];
-
-    // The verified bytecode produced by this first pass.
-    let mut verified_ops: Vec<Op2> = vec![];
-
+    compile_time_stack: Vec<CTStackVal>,
+    stack_type: Vec<Type>,
+    quantification_stack: Vec<Quantification>,
+    // The verified bytecode produced so far.
+    verified_ops: Vec<Op2>,
+    // The position of the op each verified op came from.
+    op_positions: Vec<Pos>,
     // The list of region variables the function is quantified (polymorphic) over.
-    let mut rgn_vars: Vec<Region> = vec![Region {
-        unique: false,
-        id: DataSection,
-    }];
-    for ctval in &compile_time_stack {
-        if let CTStackVal::Region(r) = ctval {
-            rgn_vars.push(r.clone());
+    rgn_vars: Vec<Region>,
+    // The regions in the types of the exception handler's arguments, which can't be freed while it's installed.
+    handler_rgns: Vec<Region>,
+    // Whether the function has installed an exception handler, which it has to before any op that can fault.
+    handler_installed: bool,
+}
+
+impl<'a> FunctionVerifier<'a> {
+    /// Start verifying the definition of the function with the given label,
+    /// whose first op is at position `pos`.
+    pub fn new(
+        data_section_len: usize,
+        types: &'a HashMap<Label, Type>,
+        label: Label,
+        pos: Pos,
+        fresh_id: u32,
+    ) -> Result<Self, Error> {
+        let Some(my_type) = types.get(&label).cloned() else {
+            panic!("Type not found for label {}", label);
+        };
+        let (mut compile_time_stack, stack_type) = setup_verifier(&my_type)?;
+        compile_time_stack.reverse();
+        let mut rgn_vars: Vec<Region> = vec![Region {
+            unique: false,
+            id: DataSection,
+        }];
+        for ctval in &compile_time_stack {
+            if let CTStackVal::Region(r) = ctval {
+                rgn_vars.push(*r);
+            }
         }
+        Ok(FunctionVerifier {
+            data_section_len,
+            types,
+            label,
+            my_type,
+            pos,
+            fresh_id,
+            next_region_is_unique: false,
+            compile_time_stack,
+            stack_type,
+            quantification_stack: vec![],
+            verified_ops: vec![],
+            op_positions: vec![],
+            rgn_vars,
+            handler_rgns: vec![],
+            handler_installed: false,
+        })
     }
 
-    let mut next_region_is_unique = false;
+    /// The compile-time stack, with the top at the end.
+    pub fn compile_time_stack(&self) -> &[CTStackVal] {
+        &self.compile_time_stack
+    }
 
-    loop {
-        // dbg!(&compile_time_stack.iter().map(|v| v.pretty()).collect::<Vec<_>>());
-        // dbg!(&stack_type.iter().map(|v| v.pretty()).collect::<Vec<_>>());
-        match ops_iter.next() {
-            None => break,
-            Some(op) => match op {
-                Op1::Unique => next_region_is_unique = true,
-                Op1::Handle => handle_handle(pos, op, &mut compile_time_stack)?,
-                Op1::I32 => compile_time_stack.push(CTStackVal::Type(Type::I32)),
-                Op1::Tuple(n) => handle_tuple(n, pos, op, &mut compile_time_stack)?,
-                Op1::Some => handle_some(
-                    pos,
-                    op,
-                    &mut compile_time_stack,
-                    &mut fresh_id,
-                    label,
-                    &mut quantification_stack,
-                )?,
-                Op1::All => handle_all(
-                    pos,
-                    op,
-                    &mut compile_time_stack,
-                    &mut fresh_id,
-                    label,
-                    &mut quantification_stack,
-                )?,
-                Op1::Rgn => handle_rgn(
-                    &mut next_region_is_unique,
-                    label,
-                    &mut fresh_id,
-                    &mut compile_time_stack,
-                    &mut quantification_stack,
-                )?,
-                Op1::End => {
-                    handle_end(pos, op, &mut compile_time_stack, &mut quantification_stack)?
-                }
-                Op1::App => match compile_time_stack.pop() {
-                    Some(CTStackVal::Type(t_arg)) => {
-                        let (id, s, t) = match stack_type.pop() {
-                            Some(Type::Forall(id, s, t)) => (id, s, t),
-                            Some(t) => return Err(Error::TypeErrorForallExpected(pos, *op, t)),
-                            None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
-                        };
-                        if s != t_arg.size() {
-                            return Err(Error::SizeError(pos, *op, s, t_arg.size()));
-                        }
-                        let new_t =
-                            substitute_t(&*t, &HashMap::from([(id, t_arg)]), &HashMap::new());
-                        stack_type.push(new_t);
+    /// The types of the values on the runtime stack, with the top at the end.
+    pub fn stack_type(&self) -> &[Type] {
+        &self.stack_type
+    }
+
+    /// The position the next op will have in error messages.
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    /// Verify the next op of the function.
+    pub fn step(&mut self, op: &Op1) -> Result<(), Error> {
+        let FunctionVerifier {
+            data_section_len,
+            types,
+            pos,
+            fresh_id,
+            next_region_is_unique,
+            compile_time_stack,
+            stack_type,
+            quantification_stack,
+            verified_ops,
+            rgn_vars,
+            handler_rgns,
+            handler_installed,
+            ..
+        } = self;
+        let (data_section_len, types, pos) = (*data_section_len, *types, *pos);
+        match op {
+            Op1::Unique => *next_region_is_unique = true,
+            Op1::Handle => handle_handle(pos, op, compile_time_stack)?,
+            Op1::I32 => compile_time_stack.push(CTStackVal::Type(Type::I32)),
+            Op1::Tuple(n) => handle_tuple(n, pos, op, compile_time_stack)?,
+            Op1::Some => handle_some(
+                pos,
+                op,
+                compile_time_stack,
+                fresh_id,
+                quantification_stack,
+            )?,
+            Op1::All => handle_all(
+                pos,
+                op,
+                compile_time_stack,
+                fresh_id,
+                quantification_stack,
+            )?,
+            Op1::Rgn => handle_rgn(
+                pos,
+                next_region_is_unique,
+                fresh_id,
+                compile_time_stack,
+                quantification_stack,
+            )?,
+            Op1::End => {
+                handle_end(pos, op, compile_time_stack, quantification_stack)?
+            }
+            Op1::App => match compile_time_stack.pop() {
+                Some(CTStackVal::Type(t_arg)) => {
+                    let (id, s, t) = match stack_type.pop() {
+                        Some(Type::Forall(id, s, t)) => (id, s, t),
+                        Some(t) => return Err(Error::TypeErrorForallExpected(pos, *op, t)),
+                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                    };
+                    if s != t_arg.size() {
+                        return Err(Error::SizeError(pos, *op, s, t_arg.size()));
                     }
-                    Some(CTStackVal::Region(r_arg)) => {
-                        let (r, t, captured_rgns) = match stack_type.pop() {
-                            Some(Type::ForallRegion(r, t, captured_rgns)) => (r, t, captured_rgns),
-                            Some(t) => {
-                                return Err(Error::TypeErrorForallRegionExpected(pos, *op, t))
-                            }
-                            None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
-                        };
-                        if r.unique && captured_rgns.iter().any(|r2| r_arg.id == r2.id) {
-                            return Err(Error::RegionAccessError(pos, *op, r_arg));
+                    let new_t =
+                        substitute_t(&*t, &HashMap::from([(id, t_arg)]), &HashMap::new());
+                    stack_type.push(new_t);
+                }
+                Some(CTStackVal::Region(r_arg)) => {
+                    let (r, t, captured_rgns) = match stack_type.pop() {
+                        Some(Type::ForallRegion(r, t, captured_rgns)) => (r, t, captured_rgns),
+                        Some(t) => {
+                            return Err(Error::TypeErrorForallRegionExpected(pos, *op, t))
                         }
-                        let new_t =
-                            substitute_t(&*t, &HashMap::new(), &HashMap::from([(r.id, r_arg)]));
-                        stack_type.push(new_t);
-                    }
-                    Some(ctval) => return Err(Error::KindErrorBadApp(pos, *op, ctval)),
-                    None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
-                },
-                Op1::Func(n) => handle_func(n, pos, op, &mut compile_time_stack)?,
-                Op1::CTGet(i) => handle_ctget(pos, i, &mut compile_time_stack)?,
-                Op1::Lced => panic!("Lced should not appear in this context"),
-                Op1::Import(_, _) => panic!("Import should not appear in this context"),
-                Op1::Export(_, _) => panic!("Export should not appear in this context"),
-                Op1::Unpack => {
-                    let t = match stack_type.pop() {
-                        Some(Type::Exists(_id, _s, t)) => t,
-                        Some(t) => return Err(Error::TypeErrorExistentialExpected(pos, *op, t)),
                         None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
                     };
-                    stack_type.push(*t);
+                    if r.unique && captured_rgns.iter().any(|r2| r_arg.id == r2.id) {
+                        return Err(Error::RegionAccessError(pos, *op, r_arg));
+                    }
+                    let new_t =
+                        substitute_t(&*t, &HashMap::new(), &HashMap::from([(r.id, r_arg)]));
+                    stack_type.push(new_t);
                 }
-                Op1::Get(i) => {
-                    let stack_len = stack_type.len();
-                    if stack_len == 0 {
+                Some(ctval) => return Err(Error::KindErrorBadApp(pos, *op, ctval)),
+                None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+            },
+            Op1::Func(n) => handle_func(n, pos, op, compile_time_stack)?,
+            Op1::CTGet(i) => handle_ctget(pos, i, compile_time_stack)?,
+            Op1::Lced => panic!("Lced should not appear in this context"),
+            Op1::Import(_, _) => panic!("Import should not appear in this context"),
+            Op1::Export(_, _) => panic!("Export should not appear in this context"),
+            Op1::Unpack => {
+                let t = match stack_type.pop() {
+                    Some(Type::Exists(_id, _s, t)) => t,
+                    Some(t) => return Err(Error::TypeErrorExistentialExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                stack_type.push(*t);
+            }
+            Op1::Get(i) => {
+                let stack_len = stack_type.len();
+                if stack_len == 0 {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                }
+                let i2 = usize::from(*i);
+                if stack_len - 1 < i2 {
+                    return Err(Error::TypeErrorGetOutOfRange(pos, *i, stack_len));
+                }
+                let mut offset = 0;
+                for j in 0..*i {
+                    offset += stack_type[stack_len - 1 - (j as usize)].size();
+                }
+                let t = stack_type.get(stack_len - 1 - i2).unwrap().clone();
+                let size = t.size();
+                stack_type.push(t);
+                verified_ops.push(Op2::Get(offset, size));
+            }
+            Op1::Init(i) => {
+                let mb_val = stack_type.pop();
+                let mb_tpl = stack_type.pop();
+                let f = |component_types: Vec<(bool, Type)>,
+                         g: &dyn Fn(
+                    &Type,
+                    Vec<(bool, Type)>,
+                    &mut Vec<Type>,
+                    &mut Vec<Op2>,
+                ) -> ()| {
+                    let formal = match component_types.get(usize::from(*i)) {
+                        Some((false, formal)) => formal,
+                        Some((true, _t)) => {
+                            return Err(Error::TypeErrorDoubleInit(pos, *op, *i))
+                        }
+                        None => {
+                            return Err(Error::TypeErrorInitOutOfRange(
+                                pos,
+                                *i,
+                                component_types.len(),
+                            ))
+                        }
+                    };
+                    let Some(actual) = mb_val else {
                         return Err(Error::TypeErrorEmptyStack(pos, *op));
+                    };
+                    if type_eq(formal, &actual) {
+                        g(&actual, component_types, stack_type, verified_ops);
+                    } else {
+                        return Err(Error::TypeErrorInitTypeMismatch(
+                            pos,
+                            formal.clone(),
+                            actual,
+                        ));
                     }
-                    let i2 = usize::from(*i);
-                    if stack_len - 1 < i2 {
-                        return Err(Error::TypeErrorGetOutOfRange(pos, *i, stack_len));
-                    }
-                    let mut offset = 0;
-                    for j in 0..*i {
-                        offset += stack_type[stack_len - 1 - (j as usize)].size();
-                    }
-                    let t = stack_type.get(stack_len - 1 - i2).unwrap().clone();
-                    let size = t.size();
-                    stack_type.push(t);
-                    verified_ops.push(Op2::Get(offset, size));
-                }
-                Op1::Init(i) => {
-                    let mb_val = stack_type.pop();
-                    let mb_tpl = stack_type.pop();
-                    let f = |component_types: Vec<(bool, Type)>,
-                             g: &dyn Fn(
-                        &Type,
-                        Vec<(bool, Type)>,
-                        &mut Vec<Type>,
-                        &mut Vec<Op2>,
-                    ) -> ()| {
-                        let formal = match component_types.get(usize::from(*i)) {
-                            Some((false, formal)) => formal,
-                            Some((true, _t)) => {
-                                return Err(Error::TypeErrorDoubleInit(pos, *op, *i))
-                            }
-                            None => {
-                                return Err(Error::TypeErrorInitOutOfRange(
-                                    pos,
-                                    *i,
-                                    component_types.len(),
-                                ))
+                    Ok(())
+                };
+                match mb_tpl {
+                    Some(Type::Tuple(component_types)) => f(
+                        component_types,
+                        &|actual: &Type,
+                          mut component_types: Vec<(bool, Type)>,
+                          stack_type: &mut Vec<Type>,
+                          verified_ops: &mut Vec<Op2>| {
+                            let mut offset = 0;
+                            let tpl_size = component_types.iter().map(|(_, t)| t.size()).sum();
+                            for i2 in 0..*i {
+                                let (_, t) = &component_types[i2 as usize];
+                                offset += t.size();
                             }
+                            component_types[*i as usize] = (true, actual.clone());
+                            stack_type.push(Type::Tuple(component_types));
+                            verified_ops.push(Op2::Init(offset, actual.size(), tpl_size));
+                        },
+                    )?,
+                    Some(Type::Ptr(boxed_t, r)) => {
+                        let Type::Tuple(component_types) = *boxed_t else {
+                            return Err(Error::TypeErrorTupleExpected(pos, *op, *boxed_t));
                         };
-                        let Some(actual) = mb_val else {
-                            return Err(Error::TypeErrorEmptyStack(pos, *op));
-                        };
-                        if type_eq(formal, &actual) {
-                            g(&actual, component_types, &mut stack_type, &mut verified_ops);
-                        } else {
-                            return Err(Error::TypeErrorInitTypeMismatch(
-                                pos,
-                                formal.clone(),
-                                actual,
-                            ));
+                        if rgn_vars.iter().all(|r2| r.id != r2.id) {
+                            return Err(Error::RegionAccessError(pos, *op, r));
                         }
-                        Ok(())
-                    };
-                    match mb_tpl {
-                        Some(Type::Tuple(component_types)) => f(
+                        f(
                             component_types,
                             &|actual: &Type,
                               mut component_types: Vec<(bool, Type)>,
                               stack_type: &mut Vec<Type>,
                               verified_ops: &mut Vec<Op2>| {
                                 let mut offset = 0;
-                                let tpl_size = component_types.iter().map(|(_, t)| t.size()).sum();
                                 for i2 in 0..*i {
                                     let (_, t) = &component_types[i2 as usize];
                                     offset += t.size();
                                 }
                                 component_types[*i as usize] = (true, actual.clone());
-                                stack_type.push(Type::Tuple(component_types));
-                                verified_ops.push(Op2::Init(offset, actual.size(), tpl_size));
+                                stack_type
+                                    .push(Type::Ptr(Box::new(Type::Tuple(component_types)), r));
+                                verified_ops.push(Op2::InitIP(offset, actual.size()));
                             },
-                        )?,
-                        Some(Type::Ptr(boxed_t, r)) => {
-                            let Type::Tuple(component_types) = *boxed_t else {
-                                return Err(Error::TypeErrorTupleExpected(pos, *op, *boxed_t));
-                            };
-                            if rgn_vars.iter().all(|r2| r.id != r2.id) {
-                                return Err(Error::RegionAccessError(pos, *op, r));
-                            }
-                            f(
-                                component_types,
-                                &|actual: &Type,
-                                  mut component_types: Vec<(bool, Type)>,
-                                  stack_type: &mut Vec<Type>,
-                                  verified_ops: &mut Vec<Op2>| {
-                                    let mut offset = 0;
-                                    for i2 in 0..*i {
-                                        let (_, t) = &component_types[i2 as usize];
-                                        offset += t.size();
-                                    }
-                                    component_types[*i as usize] = (true, actual.clone());
-                                    stack_type
-                                        .push(Type::Ptr(Box::new(Type::Tuple(component_types)), r));
-                                    verified_ops.push(Op2::InitIP(offset, actual.size()));
-                                },
-                            )?
-                        }
-                        Some(t) => return Err(Error::TypeErrorTupleExpected(pos, *op, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                        )?
                     }
+                    Some(t) => return Err(Error::TypeErrorTupleExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
                 }
-                Op1::Malloc => {
-                    let mb_type = compile_time_stack.pop();
-                    match mb_type {
-                        Some(CTStackVal::Type(Type::Ptr(t, r))) => {
-                            let r2 = match stack_type.pop() {
-                                Some(Type::Handle(r2)) => r2,
-                                Some(t) => {
-                                    return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t));
-                                }
-                                None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                            };
-                            // check that t is in r and that r is in the list of declared regions
-                            if r.id != r2.id {
-                                return Err(Error::RegionError(pos, *op, r, r2));
-                            }
-                            if rgn_vars.iter().all(|r2: &Region| r.id != r2.id) {
-                                return Err(Error::RegionAccessError(pos, *op, r));
-                            }
-                            let t = *t;
-                            let size = t.size();
-                            if let Type::Tuple(component_types) = t {
-                                let mut ts = vec![];
-                                for (_, t) in component_types {
-                                    ts.push((false, t));
-                                }
-                                stack_type.push(Type::Ptr(Box::new(Type::Tuple(ts)), r));
-                                verified_ops.push(Op2::Malloc(size));
-                            } else {
-                                return Err(Error::TypeErrorMallocNonTuple(pos, *op, t));
+            }
+            Op1::Malloc => {
+                let mb_type = compile_time_stack.pop();
+                match mb_type {
+                    Some(CTStackVal::Type(Type::Ptr(t, r))) => {
+                        let r2 = match stack_type.pop() {
+                            Some(Type::Handle(r2)) => r2,
+                            Some(t) => {
+                                return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t));
                             }
+                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                        };
+                        // check that t is in r and that r is in the list of declared regions
+                        if r.id != r2.id {
+                            return Err(Error::RegionError(pos, *op, r, r2));
+                        }
+                        if rgn_vars.iter().all(|r2: &Region| r.id != r2.id) {
+                            return Err(Error::RegionAccessError(pos, *op, r));
                         }
-                        Some(CTStackVal::Type(Type::Tuple(component_types))) => {
+                        let t = *t;
+                        let size = t.size();
+                        if let Type::Tuple(component_types) = t {
                             let mut ts = vec![];
                             for (_, t) in component_types {
-                                ts.push((false, t))
+                                ts.push((false, t));
                             }
-                            let t = Type::Tuple(ts);
-                            let size = t.size();
-                            if size > 4096 {
-                                return Err(Error::TooBigForStack(pos, *op, t));
-                            }
-                            stack_type.push(t);
-                            verified_ops.push(Op2::Alloca(size));
+                            stack_type.push(Type::Ptr(Box::new(Type::Tuple(ts)), r));
+                            verified_ops.push(Op2::Malloc(size));
+                        } else {
+                            return Err(Error::TypeErrorMallocNonTuple(pos, *op, t));
                         }
-                        Some(CTStackVal::Type(Type::Array(t, r))) => {
-                            match stack_type.pop() {
-                                Some(Type::I32) => {} // success
-                                Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                                None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
-                            }
+                    }
+                    Some(CTStackVal::Type(Type::Tuple(component_types))) => {
+                        let mut ts = vec![];
+                        for (_, t) in component_types {
+                            ts.push((false, t))
+                        }
+                        let t = Type::Tuple(ts);
+                        let size = t.size();
+                        if size > 4096 {
+                            return Err(Error::TooBigForStack(pos, *op, t));
+                        }
+                        stack_type.push(t);
+                        verified_ops.push(Op2::Alloca(size));
+                    }
+                    Some(CTStackVal::Type(Type::Array(t, r))) => {
+                        match stack_type.pop() {
+                            Some(Type::I32) => {} // success
+                            Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                        }
+                        // the array starts out filled with the given element if it can't start out zeroed
+                        let fill = !zero_is_valid(&t);
+                        if fill {
                             match stack_type.pop() {
-                                Some(Type::Handle(r2)) if r2.id != r.id => {
-                                    return Err(Error::RegionError(pos, *op, r, r2))
-                                }
-                                Some(Type::Handle(_r)) => {} // success
-                                Some(t) => {
-                                    return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t))
-                                }
+                                Some(t2) if type_eq(&t, &t2) => {} // success
+                                Some(t2) => return Err(Error::TypeError(pos, *op, *t, t2)),
                                 None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
                             }
-                            if rgn_vars.iter().all(|r2: &Region| r.id != r2.id) {
-                                return Err(Error::RegionAccessError(pos, *op, r));
+                        }
+                        match stack_type.pop() {
+                            Some(Type::Handle(r2)) if r2.id != r.id => {
+                                return Err(Error::RegionError(pos, *op, r, r2))
+                            }
+                            Some(Type::Handle(_r)) => {} // success
+                            Some(t) => {
+                                return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t))
                             }
-                            let size = (*t).size();
-                            stack_type.push(Type::Array(t, r));
+                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                        }
+                        if rgn_vars.iter().all(|r2: &Region| r.id != r2.id) {
+                            return Err(Error::RegionAccessError(pos, *op, r));
+                        }
+                        let size = (*t).size();
+                        if size > 4096 {
+                            return Err(Error::TooBigForStack(pos, *op, *t));
+                        }
+                        stack_type.push(Type::Array(t, r));
+                        if fill {
+                            verified_ops.push(Op2::NewArrFill(size));
+                        } else {
                             verified_ops.push(Op2::NewArr(size));
                         }
-                        Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
-                        None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+                    }
+                    Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
+                    None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+                };
+            }
+            Op1::Proj(i) => {
+                let mut f = |component_types: Vec<(bool, Type)>,
+                             stack_type: &mut Vec<Type>,
+                             g: &dyn Fn(
+                    &Type,
+                    usize,
+                    &mut Vec<Type>,
+                    &mut Vec<Op2>,
+                    Vec<(bool, Type)>,
+                ) -> ()| {
+                    let s: usize = component_types.iter().map(|(_, t)| t.size()).sum();
+                    let mb_t = component_types.get(usize::from(*i)).cloned();
+                    let t = match mb_t {
+                        Some((true, t)) => t,
+                        Some((false, _)) => {
+                            return Err(Error::TypeErrorUninitializedRead(pos, *op, *i))
+                        }
+                        None => {
+                            return Err(Error::TypeErrorProjOutOfRange(
+                                pos,
+                                *i,
+                                component_types.len(),
+                            ))
+                        }
                     };
-                }
-                Op1::Proj(i) => {
-                    let mut f = |component_types: Vec<(bool, Type)>,
-                                 mut stack_type: &mut Vec<Type>,
-                                 g: &dyn Fn(
-                        &Type,
-                        usize,
-                        &mut Vec<Type>,
-                        &mut Vec<Op2>,
-                        Vec<(bool, Type)>,
-                    ) -> ()| {
-                        let s: usize = component_types.iter().map(|(_, t)| t.size()).sum();
-                        let mb_t = component_types.get(usize::from(*i)).cloned();
-                        let t = match mb_t {
-                            Some((true, t)) => t,
-                            Some((false, _)) => {
-                                return Err(Error::TypeErrorUninitializedRead(pos, *op, *i))
-                            }
-                            None => {
-                                return Err(Error::TypeErrorProjOutOfRange(
-                                    pos,
-                                    *i,
-                                    component_types.len(),
-                                ))
+                    g(&t, s, stack_type, verified_ops, component_types);
+                    Ok(())
+                };
+                let Some(tpl) = stack_type.pop() else {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                };
+                match tpl {
+                    Type::Tuple(component_types) => {
+                        f(component_types, stack_type, &|t: &Type, s: usize, stack_type: &mut Vec<Type>, verified_ops: &mut Vec<Op2>, component_types: Vec<(bool, Type)>| {
+                            let mut offset = 0;
+                            for i2 in 0..*i {
+                                let (_, t) = &component_types[i2 as usize];
+                                offset += t.size();
                             }
-                        };
-                        g(&t, s, &mut stack_type, &mut verified_ops, component_types);
-                        Ok(())
-                    };
-                    let Some(tpl) = stack_type.pop() else {
-                        return Err(Error::TypeErrorEmptyStack(pos, *op));
-                    };
-                    match tpl {
-                        Type::Tuple(component_types) => {
-                            f(component_types, &mut stack_type, &|t: &Type, s: usize, stack_type: &mut Vec<Type>, verified_ops: &mut Vec<Op2>, component_types: Vec<(bool, Type)>| {
-                                let mut offset = 0;
-                                for i2 in 0..*i {
-                                    let (_, t) = &component_types[i2 as usize];
-                                    offset += t.size();
-                                }
-                                stack_type.push(t.clone());
-                                verified_ops.push(Op2::Proj(offset, t.size(), s));
-                            })?;
+                            stack_type.push(t.clone());
+                            verified_ops.push(Op2::Proj(offset, t.size(), s));
+                        })?;
+                    }
+                    Type::Ptr(boxed_t, r) => {
+                        if r.id == RgnId::DataSection {
+                            return Err(Error::ReadOnlyRegionError(pos, *op, r.id));
+                        } else if rgn_vars.iter().all(|r2| r.id != r2.id) {
+                            return Err(Error::RegionAccessError(pos, *op, r));
                         }
-                        Type::Ptr(boxed_t, r) => {
-                            if r.id == RgnId::DataSection {
-                                return Err(Error::ReadOnlyRegionError(pos, *op, r.id));
-                            } else if rgn_vars.iter().all(|r2| r.id != r2.id) {
-                                return Err(Error::RegionAccessError(pos, *op, r));
+                        let Type::Tuple(component_types) = *boxed_t else {
+                            return Err(Error::TypeErrorTupleExpected(pos, *op, *boxed_t));
+                        };
+                        f(component_types, stack_type, &|t: &Type, _s: usize, stack_type: &mut Vec<Type>, verified_ops: &mut Vec<Op2>, component_types: Vec<(bool, Type)>| {
+                            let mut offset = 0;
+                            for i2 in 0..*i {
+                                let (_, t) = &component_types[i2 as usize];
+                                offset += t.size();
                             }
-                            let Type::Tuple(component_types) = *boxed_t else {
-                                return Err(Error::TypeErrorTupleExpected(pos, *op, *boxed_t));
-                            };
-                            f(component_types, &mut stack_type, &|t: &Type, _s: usize, stack_type: &mut Vec<Type>, verified_ops: &mut Vec<Op2>, component_types: Vec<(bool, Type)>| {
-                                let mut offset = 0;
-                                for i2 in 0..*i {
-                                    let (_, t) = &component_types[i2 as usize];
-                                    offset += t.size();
-                                }
-                                stack_type.push(t.clone());
-                                verified_ops.push(Op2::ProjIP(offset, t.size()));
-                            })?;
-                        }
-                        t => return Err(Error::TypeErrorTupleExpected(pos, *op, t)),
+                            stack_type.push(t.clone());
+                            verified_ops.push(Op2::ProjIP(offset, t.size()));
+                        })?;
                     }
+                    t => return Err(Error::TypeErrorTupleExpected(pos, *op, t)),
                 }
-                Op1::Call => {
-                    let Some(t) = stack_type.pop() else {
-                        return Err(Error::TypeErrorEmptyStack(pos, *op));
-                    };
-                    handle_call(pos, &t, &mut stack_type, &mut compile_time_stack, Op1::Call)?;
-                    verified_ops.push(Op2::Call)
-                }
-                // Op1::Print => {
-                //     panic!("op `print` no longer supported. Use `write` instead, targetting the console.");
-                    // Some(Type::Array(t, r)) => {
-                    //     if *t != Type::U8 {
-                    //         return Err(Error::TypeError(pos, *op, Type::U8, *t));
-                    //     }
-                    //     if rgn_vars.iter().all(|r2| r.id != r2.id) {
-                    //         return Err(Error::RegionAccessError(pos, *op, r));
-                    //     }
-                    //     verified_ops.push(Op2::Print);
-                    // }
-                    // Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
-                    // None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+            }
+            Op1::Call => {
+                let Some(t) = stack_type.pop() else {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                };
+                handle_call(pos, &t, stack_type, compile_time_stack, Op1::Call)?;
+                verified_ops.push(Op2::Call)
+            }
+            // Op1::Print => {
+            //     panic!("op `print` no longer supported. Use `write` instead, targetting the console.");
+                // Some(Type::Array(t, r)) => {
+                //     if *t != Type::U8 {
+                //         return Err(Error::TypeError(pos, *op, Type::U8, *t));
+                //     }
+                //     if rgn_vars.iter().all(|r2| r.id != r2.id) {
+                //         return Err(Error::RegionAccessError(pos, *op, r));
+                //     }
+                //     verified_ops.push(Op2::Print);
                 // }
-                Op1::Lit(lit) => {
-                    stack_type.push(Type::I32);
-                    verified_ops.push(Op2::Lit(*lit))
+                // Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
+                // None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+            // }
+            Op1::Lit(lit) => {
+                stack_type.push(Type::I32);
+                verified_ops.push(Op2::Lit(*lit))
+            }
+            Op1::GlobalFunc(label) => {
+                let t = types
+                    .get(label)
+                    .ok_or(Error::UnknownGlobalFunc(pos, *op, *label))?;
+                stack_type.push(t.clone());
+                verified_ops.push(Op2::GlobalFunc(*label))
+            }
+            Op1::Halt => match stack_type.pop() {
+                Some(Type::U8) => verified_ops.push(Op2::Halt),
+                Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
+                None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+            },
+            Op1::Pack => {
+                let Some(type_of_hidden) = stack_type.pop() else {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                };
+                let hidden_type = match compile_time_stack.pop() {
+                    Some(CTStackVal::Type(t)) => t,
+                    Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
+                    None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+                };
+                let (id, size_of_hidden, existential_type) = match compile_time_stack.pop() {
+                    Some(CTStackVal::Type(Type::Exists(
+                        id,
+                        size_of_hidden,
+                        existential_type,
+                    ))) => (id, size_of_hidden, existential_type),
+                    Some(CTStackVal::Type(t)) => {
+                        return Err(Error::TypeErrorExistentialExpected(pos, *op, t))
+                    }
+                    Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
+                    None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+                };
+                // dbg!(&type_of_hidden.pretty());
+                if size_of_hidden != hidden_type.size() {
+                    return Err(Error::SizeError(
+                        pos,
+                        *op,
+                        size_of_hidden,
+                        type_of_hidden.size(),
+                    ));
                 }
-                Op1::GlobalFunc(label) => {
-                    let t = types
-                        .get(label)
-                        .ok_or(Error::UnknownGlobalFunc(pos, *op, *label))?;
-                    stack_type.push(t.clone());
-                    verified_ops.push(Op2::GlobalFunc(*label))
+                let unpacked_type = substitute_t(
+                    &existential_type,
+                    &HashMap::from([(id, hidden_type)]),
+                    &HashMap::new(),
+                );
+                if !type_eq(&type_of_hidden, &unpacked_type) {
+                    return Err(Error::TypeError(pos, *op, unpacked_type, type_of_hidden));
                 }
-                Op1::Halt => match stack_type.pop() {
-                    Some(Type::U8) => verified_ops.push(Op2::Halt),
-                    Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
+                stack_type.push(Type::Exists(id, size_of_hidden, existential_type));
+            }
+            Op1::Size(s) => compile_time_stack.push(CTStackVal::Size((*s).try_into().unwrap())),
+            Op1::NewRgn(size) => {
+                let id = Id(pos, *fresh_id);
+                *fresh_id += 1;
+                let r = Region {
+                    unique: true,
+                    id: RgnId::Var(id),
+                };
+                rgn_vars.push(r.clone());
+                stack_type.push(Type::Handle(r.clone()));
+                compile_time_stack.push(CTStackVal::Region(r));
+                verified_ops.push(Op2::NewRgn((*size).try_into().unwrap()));
+            }
+            Op1::FreeRgn => {
+                let r = match stack_type.pop() {
+                    Some(Type::Handle(r)) => r,
+                    Some(t) => return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t)),
                     None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                },
-                Op1::Pack => {
-                    let Some(type_of_hidden) = stack_type.pop() else {
-                        return Err(Error::TypeErrorEmptyStack(pos, *op));
-                    };
-                    let hidden_type = match compile_time_stack.pop() {
-                        Some(CTStackVal::Type(t)) => t,
-                        Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
-                        None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
-                    };
-                    let (id, size_of_hidden, existential_type) = match compile_time_stack.pop() {
-                        Some(CTStackVal::Type(Type::Exists(
-                            id,
-                            size_of_hidden,
-                            existential_type,
-                        ))) => (id, size_of_hidden, existential_type),
-                        Some(CTStackVal::Type(t)) => {
-                            return Err(Error::TypeErrorExistentialExpected(pos, *op, t))
-                        }
-                        Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
-                        None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
-                    };
-                    // dbg!(&type_of_hidden.pretty());
-                    if size_of_hidden != hidden_type.size() {
-                        return Err(Error::SizeError(
-                            pos,
-                            *op,
-                            size_of_hidden,
-                            type_of_hidden.size(),
-                        ));
-                    }
-                    let unpacked_type = substitute_t(
-                        &existential_type,
-                        &HashMap::from([(id, hidden_type)]),
-                        &HashMap::new(),
-                    );
-                    if !type_eq(&type_of_hidden, &unpacked_type) {
-                        return Err(Error::TypeError(pos, *op, unpacked_type, type_of_hidden));
-                    }
-                    stack_type.push(Type::Exists(id, size_of_hidden, existential_type));
+                };
+                match rgn_vars.iter().find(|r2| r.id == r2.id) {
+                    Some(r2) if r2.unique => {} // success
+                    Some(_r2) => return Err(Error::UniquenessError(pos, *op, r)),
+                    None => return Err(Error::RegionAccessError(pos, *op, r)),
+                };
+                if handler_rgns.iter().any(|r2| r.id == r2.id) {
+                    return Err(Error::RegionUsedByHandler(pos, *op, r));
                 }
-                Op1::Size(s) => compile_time_stack.push(CTStackVal::Size((*s).try_into().unwrap())),
-                Op1::NewRgn(size) => {
-                    let id = Id(*label, fresh_id);
-                    fresh_id += 1;
-                    let r = Region {
-                        unique: true,
-                        id: RgnId::Var(id),
-                    };
-                    rgn_vars.push(r.clone());
-                    stack_type.push(Type::Handle(r.clone()));
-                    compile_time_stack.push(CTStackVal::Region(r));
-                    verified_ops.push(Op2::NewRgn((*size).try_into().unwrap()));
+                rgn_vars.retain(|r2| r2.id != r.id);
+                verified_ops.push(Op2::FreeRgn);
+            }
+            Op1::Ptr => handle_ptr(pos, op, compile_time_stack)?,
+            Op1::Deref => {
+                let (t, r) = match stack_type.pop() {
+                    Some(Type::Ptr(t, r)) => (t, r),
+                    Some(t) => return Err(Error::TypeErrorPtrExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                if rgn_vars.iter().all(|r2| r.id != r2.id) {
+                    return Err(Error::RegionAccessError(pos, *op, r));
                 }
-                Op1::FreeRgn => {
-                    let r = match stack_type.pop() {
-                        Some(Type::Handle(r)) => r,
-                        Some(t) => return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    match rgn_vars.iter().find(|r2| r.id == r2.id) {
-                        Some(r2) if r2.unique => {} // success
-                        Some(_r2) => return Err(Error::UniquenessError(pos, *op, r)),
-                        None => return Err(Error::RegionAccessError(pos, *op, r)),
-                    };
-                    rgn_vars.retain(|r2| r2.id != r.id);
-                    verified_ops.push(Op2::FreeRgn);
+                let size = t.size();
+                if size > 4096 {
+                    return Err(Error::TooBigForStack(pos, *op, *t));
                 }
-                Op1::Ptr => handle_ptr(pos, op, &mut compile_time_stack)?,
-                Op1::Deref => {
-                    let (t, r) = match stack_type.pop() {
-                        Some(Type::Ptr(t, r)) => (t, r),
-                        Some(t) => return Err(Error::TypeErrorPtrExpected(pos, *op, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    if rgn_vars.iter().all(|r2| r.id != r2.id) {
-                        return Err(Error::RegionAccessError(pos, *op, r));
-                    }
-                    let size = t.size();
-                    if size > 4096 {
-                        return Err(Error::TooBigForStack(pos, *op, *t));
+                stack_type.push(*t);
+                verified_ops.push(Op2::Deref(size));
+            }
+            Op1::Arr => handle_arr(pos, op, compile_time_stack)?,
+            Op1::ArrMut => {
+                match stack_type.pop() {
+                    Some(Type::I32) => {} // success
+                    Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                let Some(t) = stack_type.pop() else {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                };
+                let r = match stack_type.pop() {
+                    Some(Type::Array(_, r)) if r.id == DataSection => {
+                        return Err(Error::CannotMutateDataSection(pos, *op));
                     }
-                    stack_type.push(*t);
-                    verified_ops.push(Op2::Deref(size));
+                    Some(Type::Array(t2, r)) if type_eq(&t, &t2) => r,
+                    Some(Type::Array(t2, _)) => return Err(Error::TypeError(pos, *op, t, *t2)),
+                    Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                if rgn_vars.iter().all(|r2| r2.id != r.id) {
+                    return Err(Error::RegionAccessError(pos, *op, r));
                 }
-                Op1::Arr => handle_arr(pos, op, &mut compile_time_stack)?,
-                Op1::ArrMut => {
-                    match stack_type.pop() {
-                        Some(Type::I32) => {} // success
-                        Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    let Some(t) = stack_type.pop() else {
-                        return Err(Error::TypeErrorEmptyStack(pos, *op));
-                    };
-                    let r = match stack_type.pop() {
-                        Some(Type::Array(_, r)) if r.id == DataSection => {
-                            return Err(Error::CannotMutateDataSection(pos, *op));
-                        }
-                        Some(Type::Array(t2, r)) if type_eq(&t, &t2) => r,
-                        Some(Type::Array(t2, _)) => return Err(Error::TypeError(pos, *op, t, *t2)),
-                        Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    if rgn_vars.iter().all(|r2| r2.id != r.id) {
-                        return Err(Error::RegionAccessError(pos, *op, r));
-                    }
-                    let size = t.size();
-                    stack_type.push(Type::Array(Box::new(t), r));
-                    verified_ops.push(Op2::ArrMut(size))
+                let size = t.size();
+                stack_type.push(Type::Array(Box::new(t), r));
+                verified_ops.push(Op2::ArrMut(size))
+            }
+            Op1::ArrProj => {
+                match stack_type.pop() {
+                    Some(Type::I32) => {} // success
+                    Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                let (t, r) = match stack_type.pop() {
+                    Some(Type::Array(t, r)) => (t, r),
+                    Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                if rgn_vars.iter().all(|r2| r2.id != r.id) {
+                    return Err(Error::RegionAccessError(pos, *op, r));
                 }
-                Op1::ArrProj => {
-                    match stack_type.pop() {
-                        Some(Type::I32) => {} // success
-                        Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    let (t, r) = match stack_type.pop() {
-                        Some(Type::Array(t, r)) => (t, r),
-                        Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    if rgn_vars.iter().all(|r2| r2.id != r.id) {
-                        return Err(Error::RegionAccessError(pos, *op, r));
-                    }
-                    let t = *t;
-                    stack_type.push(t.clone());
-                    if r.id == DataSection {
-                        verified_ops.push(Op2::DataIndex(t.size()))
-                    } else {
-                        verified_ops.push(Op2::ArrProj(t.size()))
-                    }
+                let t = *t;
+                stack_type.push(t.clone());
+                if r.id == DataSection {
+                    verified_ops.push(Op2::DataIndex(t.size()))
+                } else {
+                    verified_ops.push(Op2::ArrProj(t.size()))
                 }
-                Op1::Add => match stack_type.pop() {
-                    Some(Type::I32) => {
-                        match stack_type.pop() {
-                            Some(Type::I32) => {} // success
-                            Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                        };
-                        stack_type.push(Type::I32);
-                        verified_ops.push(Op2::AddI32);
-                    }
-                    Some(Type::U8) => {
-                        match stack_type.pop() {
-                            Some(Type::U8) => {} // success
-                            Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                        };
-                        stack_type.push(Type::U8);
-                        verified_ops.push(Op2::AddU8);
-                    }
+            }
+            Op1::Add => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::AddI32, Op2::AddU8, Op2::AddI64, Op2::AddU64],
+                    Some([Op2::AddF32, Op2::AddF64]),
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Mul => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::MulI32, Op2::MulU8, Op2::MulI64, Op2::MulU64],
+                    Some([Op2::MulF32, Op2::MulF64]),
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Div => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::DivI32, Op2::DivU8, Op2::DivI64, Op2::DivU64],
+                    Some([Op2::DivF32, Op2::DivF64]),
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::CallNZ => {
+                let Some(t1) = stack_type.pop() else {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                };
+                match stack_type.pop() {
+                    Some(t2) if type_eq(&t1, &t2) => {} // success
+                    Some(t2) => return Err(Error::TypeError(pos, *op, t1, t2)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                match stack_type.pop() {
+                    Some(Type::I32) => {} // success
                     Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
                     None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                },
-                Op1::Mul => match stack_type.pop() {
-                    Some(Type::I32) => {
-                        match stack_type.pop() {
-                            Some(Type::I32) => {} // success
-                            Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                        }
-                        stack_type.push(Type::I32);
-                        verified_ops.push(Op2::MulI32);
+                };
+                handle_call(
+                    pos,
+                    &t1,
+                    stack_type,
+                    compile_time_stack,
+                    Op1::CallNZ,
+                )?;
+                verified_ops.push(Op2::CallNZ);
+            }
+            Op1::Data(loc) => match compile_time_stack.pop() {
+                Some(CTStackVal::Type(Type::Array(t, r))) if r.id == DataSection => {
+                    // the elements are read straight from the bytes, so they can't be pointers or functions
+                    if !valid_data_section_type(&t) {
+                        return Err(Error::InvalidDataSectionType(pos, *op, *t));
                     }
-                    Some(Type::U8) => {
-                        match stack_type.pop() {
-                            Some(Type::U8) => {} // success
-                            Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                    let loc = *loc as usize;
+                    if loc > data_section_len {
+                        return Err(Error::DataSectionLoadOutOfBounds(
+                            pos,
+                            *op,
+                            loc,
+                            data_section_len,
+                        ));
+                    }
+                    stack_type.push(Type::Array(t, r.clone()));
+                    verified_ops.push(Op2::Data(loc));
+                }
+                Some(CTStackVal::Type(t)) => {
+                    if valid_data_section_type(&t) {
+                        let size = t.size();
+                        let loc = *loc as usize;
+                        if loc + size > data_section_len {
+                            return Err(Error::DataSectionLoadOutOfBounds(
+                                pos,
+                                *op,
+                                loc,
+                                data_section_len,
+                            ));
                         }
-                        stack_type.push(Type::U8);
-                        verified_ops.push(Op2::MulU8);
+                        stack_type.push(Type::Ptr(
+                            Box::new(t),
+                            Region {
+                                unique: false,
+                                id: DataSection,
+                            },
+                        ));
+                        verified_ops.push(Op2::Data(loc));
+                    } else {
+                        return Err(Error::InvalidDataSectionType(pos, *op, t.clone()));
                     }
+                }
+                Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
+                None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+            },
+            Op1::DataSec => {
+                compile_time_stack.push(CTStackVal::Region(Region {
+                    unique: false,
+                    id: DataSection,
+                }));
+            }
+            Op1::U8 => {
+                compile_time_stack.push(CTStackVal::Type(Type::U8));
+            }
+            Op1::CopyN => {
+                match stack_type.pop() {
+                    Some(Type::I32) => {} // success
                     Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
                     None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                },
-                Op1::Div => match stack_type.pop() {
-                    Some(Type::I32) => {
-                        match stack_type.pop() {
-                            Some(Type::I32) => {} // success
-                            Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                        }
-                        stack_type.push(Type::I32);
-                        verified_ops.push(Op2::DivI32);
-                    }
-                    Some(Type::U8) => {
-                        match stack_type.pop() {
-                            Some(Type::U8) => {} // success
-                            Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                        }
-                        stack_type.push(Type::U8);
-                        verified_ops.push(Op2::DivU8);
+                };
+                let (t, r) = match stack_type.pop() {
+                    Some(Type::Array(t, r)) => (t, r),
+                    Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                let r2 = match stack_type.pop() {
+                    Some(Type::Array(t2, r2)) if type_eq(&t, &t2) => r2,
+                    Some(Type::Array(t2, _)) => {
+                        return Err(Error::TypeError(pos, *op, *t, *t2))
                     }
-                    Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                    Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
                     None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                },
-                Op1::CallNZ => {
-                    let Some(t1) = stack_type.pop() else {
-                        return Err(Error::TypeErrorEmptyStack(pos, *op));
-                    };
-                    match stack_type.pop() {
-                        Some(t2) if type_eq(&t1, &t2) => {} // success
-                        Some(t2) => return Err(Error::TypeError(pos, *op, t1, t2)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    match stack_type.pop() {
-                        Some(Type::I32) => {} // success
-                        Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    handle_call(
-                        pos,
-                        &t1,
-                        &mut stack_type,
-                        &mut compile_time_stack,
-                        Op1::CallNZ,
-                    )?;
-                    verified_ops.push(Op2::CallNZ);
+                };
+                if r2.id == DataSection {
+                    return Err(Error::CannotMutateDataSection(pos, *op));
                 }
-                Op1::Data(loc) => match compile_time_stack.pop() {
-                    Some(CTStackVal::Type(Type::Array(t, r))) if r.id == DataSection => {
-                        let loc = *loc as usize;
-                        stack_type.push(Type::Array(t, r.clone()));
-                        verified_ops.push(Op2::Data(loc));
-                    }
+                if rgn_vars.iter().all(|r2| r.id != r2.id) {
+                    return Err(Error::RegionAccessError(pos, *op, r));
+                }
+                if rgn_vars.iter().all(|r| r.id != r2.id) {
+                    return Err(Error::RegionAccessError(pos, *op, r2));
+                }
+                verified_ops.push(Op2::CopyN(t.size()));
+                stack_type.push(Type::Array(t, r2));
+            }
+            Op1::U8Lit(n) => {
+                stack_type.push(Type::U8);
+                verified_ops.push(Op2::U8Lit(*n));
+            }
+            Op1::I64 => compile_time_stack.push(CTStackVal::Type(Type::I64)),
+            Op1::U64 => compile_time_stack.push(CTStackVal::Type(Type::U64)),
+            Op1::I64Lit(n) => {
+                stack_type.push(Type::I64);
+                verified_ops.push(Op2::I64Lit(*n));
+            }
+            Op1::U64Lit(n) => {
+                stack_type.push(Type::U64);
+                verified_ops.push(Op2::U64Lit(*n));
+            }
+            Op1::Cast => {
+                let to = match compile_time_stack.pop() {
+                    Some(CTStackVal::Type(t)) => t,
+                    Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
+                    None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+                };
+                let Some(to_num) = num_type(&to) else {
+                    return Err(Error::TypeError(pos, *op, Type::I32, to));
+                };
+                let from_num = match stack_type.pop() {
+                    Some(from) => match num_type(&from) {
+                        Some(from_num) => from_num,
+                        None => return Err(Error::TypeError(pos, *op, Type::I32, from)),
+                    },
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                stack_type.push(to);
+                verified_ops.push(Op2::Cast(from_num, to_num));
+            }
+            Op1::F32 => compile_time_stack.push(CTStackVal::Type(Type::F32)),
+            Op1::F64 => compile_time_stack.push(CTStackVal::Type(Type::F64)),
+            Op1::F32Lit(bits) => {
+                stack_type.push(Type::F32);
+                verified_ops.push(Op2::F32Lit(*bits));
+            }
+            Op1::F64Lit(bits) => {
+                stack_type.push(Type::F64);
+                verified_ops.push(Op2::F64Lit(*bits));
+            }
+            Op1::Variant(n) => handle_variant(n, pos, op, compile_time_stack)?,
+            Op1::Inj(i) => {
+                let ts = match compile_time_stack.pop() {
+                    Some(CTStackVal::Type(Type::Variant(ts))) => ts,
                     Some(CTStackVal::Type(t)) => {
-                        if valid_data_section_type(&t) {
-                            let size = t.size();
-                            let loc = *loc as usize;
-                            if loc + size > data_section_len {
-                                return Err(Error::DataSectionLoadOutOfBounds(
-                                    pos,
-                                    *op,
-                                    loc,
-                                    data_section_len,
-                                ));
-                            }
-                            stack_type.push(Type::Ptr(
-                                Box::new(t),
-                                Region {
-                                    unique: false,
-                                    id: DataSection,
-                                },
-                            ));
-                            verified_ops.push(Op2::Data(loc));
-                        } else {
-                            return Err(Error::InvalidDataSectionType(pos, *op, t.clone()));
-                        }
+                        return Err(Error::TypeErrorVariantExpected(pos, *op, t))
                     }
                     Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
                     None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
-                },
-                Op1::DataSec => {
-                    compile_time_stack.push(CTStackVal::Region(Region {
-                        unique: false,
-                        id: DataSection,
-                    }));
-                }
-                Op1::U8 => {
-                    compile_time_stack.push(CTStackVal::Type(Type::U8));
+                };
+                let Some(formal) = ts.get(usize::from(*i)).cloned() else {
+                    return Err(Error::TypeErrorInjOutOfRange(pos, *i, ts.len()));
+                };
+                match stack_type.pop() {
+                    Some(t) if type_eq(&formal, &t) => {} // success
+                    Some(t) => return Err(Error::TypeError(pos, *op, formal, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                let t = Type::Variant(ts);
+                if t.size() > 4096 {
+                    return Err(Error::TooBigForStack(pos, *op, t));
                 }
-                Op1::CopyN => {
+                verified_ops.push(Op2::Inj(*i, formal.size(), t.size() - 4));
+                stack_type.push(t);
+            }
+            Op1::Case(n) => {
+                let mut fs = vec![];
+                for _ in 0..*n {
                     match stack_type.pop() {
-                        Some(Type::I32) => {} // success
-                        Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    let (t, r) = match stack_type.pop() {
-                        Some(Type::Array(t, r)) => (t, r),
-                        Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
+                        Some(f) => fs.push(f),
                         None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    let r2 = match stack_type.pop() {
-                        Some(Type::Array(t2, r2)) if type_eq(&t, &t2) => r2,
-                        Some(Type::Array(t2, _)) => {
-                            return Err(Error::TypeError(pos, *op, *t, *t2))
-                        }
-                        Some(t) => return Err(Error::TypeErrorArrayExpected(pos, *op, t)),
+                    }
+                }
+                let ts = match stack_type.pop() {
+                    Some(Type::Variant(ts)) => ts,
+                    Some(t) => return Err(Error::TypeErrorVariantExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                if ts.len() != fs.len() {
+                    return Err(Error::TypeErrorCaseArityMismatch(pos, *n, ts.len()));
+                }
+                // each function is called with the rest of the stack and the component it's for on top
+                let rest = stack_type.clone();
+                let ct_rest = compile_time_stack.clone();
+                for (f, t) in fs.iter().zip(&ts) {
+                    *stack_type = rest.clone();
+                    *compile_time_stack = ct_rest.clone();
+                    stack_type.push(t.clone());
+                    handle_call(pos, f, stack_type, compile_time_stack, *op)?;
+                }
+                let sizes: Vec<usize> = ts.iter().map(Type::size).collect();
+                let max = sizes.iter().copied().max().unwrap_or(0);
+                verified_ops.push(Op2::Case(max, sizes));
+            }
+            Op1::Switch(n) => {
+                // one function for each index, index 0 on top, and the default function
+                // beneath them all, deepest; they all have the type of the first one popped
+                let Some(t1) = stack_type.pop() else {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                };
+                for _ in 0..*n {
+                    match stack_type.pop() {
+                        Some(t2) if type_eq(&t1, &t2) => {} // success
+                        Some(t2) => return Err(Error::TypeError(pos, *op, t1, t2)),
                         None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
                     };
-                    if r2.id == DataSection {
-                        return Err(Error::CannotMutateDataSection(pos, *op));
-                    }
-                    if rgn_vars.iter().all(|r2| r.id != r2.id) {
-                        return Err(Error::RegionAccessError(pos, *op, r));
-                    }
-                    if rgn_vars.iter().all(|r| r.id != r2.id) {
-                        return Err(Error::RegionAccessError(pos, *op, r2));
-                    }
-                    verified_ops.push(Op2::CopyN(t.size()));
-                    stack_type.push(Type::Array(t, r));
                 }
-                Op1::U8Lit(n) => {
+                let op2 = match stack_type.pop() {
+                    Some(Type::I32) => Op2::SwitchI32(*n),
+                    Some(Type::U8) => Op2::SwitchU8(*n),
+                    Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                handle_call(pos, &t1, stack_type, compile_time_stack, *op)?;
+                verified_ops.push(op2);
+            }
+            Op1::U8ToI32 => match stack_type.pop() {
+                Some(Type::U8) => {
+                    stack_type.push(Type::I32);
+                    verified_ops.push(Op2::U8ToI32);
+                }
+                Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
+                None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+            },
+            Op1::Modulo => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::ModuloI32, Op2::ModuloU8, Op2::ModuloI64, Op2::ModuloU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::I32ToU8 => match stack_type.pop() {
+                Some(Type::I32) => {
                     stack_type.push(Type::U8);
-                    verified_ops.push(Op2::U8Lit(*n));
+                    verified_ops.push(Op2::I32ToU8);
                 }
-                Op1::U8ToI32 => match stack_type.pop() {
-                    Some(Type::U8) => {
-                        stack_type.push(Type::I32);
-                        verified_ops.push(Op2::U8ToI32);
-                    }
-                    Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
-                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                },
-                Op1::Modulo => match stack_type.pop() {
-                    Some(Type::I32) => match stack_type.pop() {
-                        Some(Type::I32) => {
-                            stack_type.push(Type::I32);
-                            verified_ops.push(Op2::ModuloI32);
+                Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+            },
+            Op1::Read(c) => {
+                let t = match c {
+                    0 => match stack_type.pop() {
+                        Some(Type::Handle(r)) => Type::Array(Box::new(Type::U8), r),
+                        Some(t) => {
+                            return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t))
                         }
-                        Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
                         None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
                     },
-                    Some(Type::U8) => match stack_type.pop() {
-                        Some(Type::U8) => {
-                            stack_type.push(Type::U8);
-                            verified_ops.push(Op2::ModuloU8);
+                    _ => return Err(Error::UnknownChannel(pos, *op, *c)),
+                };
+                let (a, body) = match stack_type.pop() {
+                    Some(Type::Exists(a, 16, body)) => (a, body),
+                    Some(t) => return Err(Error::TypeErrorExistentialExpected(pos, *op, t)),
+                    None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                };
+                let body2 = Type::Tuple(vec![
+                    (true, Type::Func(vec![t, Type::Var(a, 16)])),
+                    (true, Type::Var(a, 16)),
+                ]);
+                if type_eq(&*body, &body2) {
+                    verified_ops.push(Op2::Read(*c));
+                } else {
+                    return Err(Error::TypeError(pos, *op, body2, *body));
+                }
+            }
+            Op1::Write(c) => {
+                let t = match c {
+                    0 => match stack_type.pop() {
+                        Some(Type::Handle(r)) => Type::Array(Box::new(Type::U8), r),
+                        Some(t) => {
+                            return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t))
                         }
-                        Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
                         None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
                     },
-                    Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                    _ => return Err(Error::UnknownChannel(pos, *op, *c)),
+                };
+                match stack_type.pop() {
+                    Some(Type::U8) => {} // success
+                    Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
                     None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                },
-                Op1::I32ToU8 => match stack_type.pop() {
-                    Some(Type::I32) => {
-                        stack_type.push(Type::U8);
-                        verified_ops.push(Op2::I32ToU8);
-                    }
-                    Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+                };
+                let (a, body) = match stack_type.pop() {
+                    Some(Type::Exists(a, 16, body)) => (a, body),
+                    Some(t) => return Err(Error::TypeErrorExistentialExpected(pos, *op, t)),
                     None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                },
-                Op1::Read(c) => {
-                    let t = match c {
-                        0 => match stack_type.pop() {
-                            Some(Type::Handle(r)) => Type::Array(Box::new(Type::U8), r),
-                            Some(t) => {
-                                return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t))
-                            }
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                        },
-                        _ => return Err(Error::UnknownChannel(pos, *op, *c)),
-                    };
-                    let (a, body) = match stack_type.pop() {
-                        Some(Type::Exists(a, 16, body)) => (a, body),
-                        Some(t) => return Err(Error::TypeErrorExistentialExpected(pos, *op, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    let body2 = Type::Tuple(vec![
-                        (true, Type::Func(vec![t, Type::Var(a, 16)])),
-                        (true, Type::Var(a, 16)),
-                    ]);
-                    if type_eq(&*body, &body2) {
-                        verified_ops.push(Op2::Read(*c));
-                    } else {
-                        return Err(Error::TypeError(pos, *op, body2, *body));
-                    }
-                }
-                Op1::Write(c) => {
-                    let t = match c {
-                        0 => match stack_type.pop() {
-                            Some(Type::Handle(r)) => Type::Array(Box::new(Type::U8), r),
-                            Some(t) => {
-                                return Err(Error::TypeErrorRegionHandleExpected(pos, *op, t))
-                            }
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                        },
-                        _ => return Err(Error::UnknownChannel(pos, *op, *c)),
-                    };
+                };
+                let body2 = Type::Tuple(vec![
+                    (true, Type::Func(vec![Type::Var(a, 16)])),
+                    (true, Type::Var(a, 16)),
+                ]);
+                if type_eq(&*body, &body2) {
                     match stack_type.pop() {
-                        Some(Type::U8) => {} // success
-                        Some(t) => return Err(Error::TypeError(pos, *op, Type::U8, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    let (a, body) = match stack_type.pop() {
-                        Some(Type::Exists(a, 16, body)) => (a, body),
-                        Some(t) => return Err(Error::TypeErrorExistentialExpected(pos, *op, t)),
-                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
-                    };
-                    let body2 = Type::Tuple(vec![
-                        (true, Type::Func(vec![Type::Var(a, 16)])),
-                        (true, Type::Var(a, 16)),
-                    ]);
-                    if type_eq(&*body, &body2) {
-                        match stack_type.pop() {
-                            Some(t2) if type_eq(&t, &t2) => {
-                                verified_ops.push(Op2::Write(*c));
-                            }
-                            Some(t2) => return Err(Error::TypeError(pos, *op, t, t2)),
-                            None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+                        Some(t2) if type_eq(&t, &t2) => {
+                            verified_ops.push(Op2::Write(*c));
                         }
-                    } else {
-                        return Err(Error::TypeError(pos, *op, body2, *body));
+                        Some(t2) => return Err(Error::TypeError(pos, *op, t, t2)),
+                        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
                     }
+                } else {
+                    return Err(Error::TypeError(pos, *op, body2, *body));
                 }
+            }
+            Op1::Catch => {
+                let Some(t) = stack_type.pop() else {
+                    return Err(Error::TypeErrorEmptyStack(pos, *op));
+                };
+                // the handler takes its arguments now, like a call, but only runs if an op faults
+                let stack_before = stack_type.clone();
+                handle_call(pos, &t, stack_type, compile_time_stack, Op1::Catch)?;
+                let args = &stack_before[stack_type.len()..];
+                let size = args.iter().map(Type::size).sum();
+                if size > 16 {
+                    return Err(Error::TooBigForHandler(pos, *op, size));
+                }
+                handler_rgns.clear();
+                for t in args {
+                    regions_in(t, handler_rgns);
+                }
+                *handler_installed = true;
+                verified_ops.push(Op2::Catch(size));
+            }
+            Op1::CheckedAdd => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [
+                        Op2::CheckedAddI32,
+                        Op2::CheckedAddU8,
+                        Op2::CheckedAddI64,
+                        Op2::CheckedAddU64,
+                    ],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::CheckedMul => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [
+                        Op2::CheckedMulI32,
+                        Op2::CheckedMulU8,
+                        Op2::CheckedMulI64,
+                        Op2::CheckedMulU64,
+                    ],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::CheckedDiv => {
+                // dividing unsigned integers never overflows
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::CheckedDivI32, Op2::DivU8, Op2::CheckedDivI64, Op2::DivU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::SaturatingAdd => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [
+                        Op2::SaturatingAddI32,
+                        Op2::SaturatingAddU8,
+                        Op2::SaturatingAddI64,
+                        Op2::SaturatingAddU64,
+                    ],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::SaturatingMul => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [
+                        Op2::SaturatingMulI32,
+                        Op2::SaturatingMulU8,
+                        Op2::SaturatingMulI64,
+                        Op2::SaturatingMulU64,
+                    ],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::SaturatingDiv => {
+                // dividing unsigned integers never overflows
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::SaturatingDivI32, Op2::DivU8, Op2::SaturatingDivI64, Op2::DivU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Sub => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::SubI32, Op2::SubU8, Op2::SubI64, Op2::SubU64],
+                    Some([Op2::SubF32, Op2::SubF64]),
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::And => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::AndI32, Op2::AndU8, Op2::AndI64, Op2::AndU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Or => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::OrI32, Op2::OrU8, Op2::OrI64, Op2::OrU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Xor => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::XorI32, Op2::XorU8, Op2::XorI64, Op2::XorU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Shl => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::ShlI32, Op2::ShlU8, Op2::ShlI64, Op2::ShlU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Shr => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::ShrI32, Op2::ShrU8, Op2::ShrI64, Op2::ShrU64],
+                    None,
+                )?;
+                verified_ops.push(op2);
+            }
+            Op1::Neg => match stack_type.last() {
+                Some(Type::I32) => verified_ops.push(Op2::NegI32),
+                Some(Type::U8) => verified_ops.push(Op2::NegU8),
+                Some(Type::I64) => verified_ops.push(Op2::NegI64),
+                Some(Type::U64) => verified_ops.push(Op2::NegU64),
+                Some(Type::F32) => verified_ops.push(Op2::NegF32),
+                Some(Type::F64) => verified_ops.push(Op2::NegF64),
+                Some(t) => return Err(Error::TypeError(pos, *op, Type::I32, t.clone())),
+                None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
             },
+            Op1::Eq => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::EqI32, Op2::EqU8, Op2::EqI64, Op2::EqU64],
+                    Some([Op2::EqF32, Op2::EqF64]),
+                )?;
+                // comparisons give an `i32`, so that `call_nz` can branch on them
+                stack_type.pop();
+                stack_type.push(Type::I32);
+                verified_ops.push(op2);
+            }
+            Op1::Lt => {
+                let op2 = handle_arith(
+                    pos,
+                    op,
+                    stack_type,
+                    [Op2::LtI32, Op2::LtU8, Op2::LtI64, Op2::LtU64],
+                    Some([Op2::LtF32, Op2::LtF64]),
+                )?;
+                stack_type.pop();
+                stack_type.push(Type::I32);
+                verified_ops.push(op2);
+            }
         }
-        pos += 1;
+        let new_ops = &self.verified_ops[self.op_positions.len()..];
+        if !self.handler_installed && new_ops.iter().any(Op2::can_fault) {
+            return Err(Error::NoHandler(self.pos, *op));
+        }
+        self.op_positions.resize(self.verified_ops.len(), self.pos);
+        self.pos += 1;
+        Ok(())
     }
-    if quantification_stack.len() > 0 {
-        return Err(Error::TypeErrorNonEmptyQuantificationStack(*label));
+
+    /// Finish verifying the function, after its last op.
+    pub fn finish(self) -> Result<Stmt2, Error> {
+        if !self.quantification_stack.is_empty() {
+            return Err(Error::TypeErrorNonEmptyQuantificationStack(self.label));
+        }
+        Ok(Stmt2::Func(self.label, self.my_type, self.verified_ops, self.op_positions))
     }
-    // wrap t in the quantifiers from kind_context
-    Ok(Stmt2::Func(*label, my_type, verified_ops))
 }
 
 fn valid_data_section_type(t: &Type) -> bool {
     match t {
-        Type::I32 => true,
-        Type::Array(_, r) if r.id == DataSection => true,
+        Type::I32 | Type::U8 | Type::I64 | Type::U64 | Type::F32 | Type::F64 => true,
         Type::Tuple(components) if components.iter().all(|(_, t)| valid_data_section_type(t)) => {
             true
         }
@@ -928,6 +1423,17 @@ fn valid_data_section_type(t: &Type) -> bool {
     }
 }
 
+/// Whether all zero bytes are a value of the type, so an array of it can start out zeroed.
+fn zero_is_valid(t: &Type) -> bool {
+    match t {
+        Type::I32 | Type::U8 | Type::I64 | Type::U64 | Type::F32 | Type::F64 => true,
+        Type::Tuple(ts) => ts.iter().all(|(_, t)| zero_is_valid(t)),
+        // a zeroed variant is its first component, zeroed
+        Type::Variant(ts) => ts.first().is_some_and(zero_is_valid),
+        _ => false,
+    }
+}
+
 fn handle_call(
     pos: u32,
     t: &Type,
@@ -956,9 +1462,6 @@ fn handle_call(
                 .zip(arg_ts_needed.iter())
                 .all(|(t1, t2)| type_eq(t1, t2));
             if !types_match {
-                for t in stack_type {
-                    println!("{}", t.pretty());
-                }
                 return Err(Error::TypeErrorCallArgTypesMismatch(
                     pos,
                     arg_ts_needed.to_vec(),
@@ -1036,17 +1539,34 @@ fn handle_tuple(
     Ok(())
 }
 
+fn handle_variant(
+    n: &u8,
+    pos: u32,
+    op: &Op1,
+    compile_time_stack: &mut Vec<CTStackVal>,
+) -> Result<(), Error> {
+    let mut ts = vec![];
+    for _ in 0..*n {
+        match compile_time_stack.pop() {
+            Some(CTStackVal::Type(t)) => ts.push(t),
+            Some(ctval) => return Err(Error::KindError(pos, *op, Kind::Type, ctval)),
+            None => return Err(Error::TypeErrorEmptyCTStack(pos, *op)),
+        }
+    }
+    compile_time_stack.push(CTStackVal::Type(Type::Variant(ts)));
+    Ok(())
+}
+
 fn handle_some(
     pos: u32,
     op: &Op1,
     compile_time_stack: &mut Vec<CTStackVal>,
     fresh_id: &mut u32,
-    label: &u32,
     quantification_stack: &mut Vec<Quantification>,
 ) -> Result<(), Error> {
     match compile_time_stack.pop() {
         Some(CTStackVal::Size(s)) => {
-            let id = Id(*label, *fresh_id);
+            let id = Id(pos, *fresh_id);
             *fresh_id += 1;
             compile_time_stack.push(CTStackVal::Type(Type::Var(id.clone(), s)));
             quantification_stack.push(Quantification::Exist(id, s));
@@ -1062,12 +1582,11 @@ fn handle_all(
     op: &Op1,
     compile_time_stack: &mut Vec<CTStackVal>,
     fresh_id: &mut u32,
-    label: &u32,
     quantification_stack: &mut Vec<Quantification>,
 ) -> Result<(), Error> {
     match compile_time_stack.pop() {
         Some(CTStackVal::Size(s)) => {
-            let id = Id(*label, *fresh_id);
+            let id = Id(pos, *fresh_id);
             *fresh_id += 1;
             compile_time_stack.push(CTStackVal::Type(Type::Var(id.clone(), s)));
             quantification_stack.push(Quantification::Forall(id, s));
@@ -1079,13 +1598,13 @@ fn handle_all(
 }
 
 fn handle_rgn(
+    pos: u32,
     next_region_is_unique: &mut bool,
-    label: &u32,
     fresh_id: &mut u32,
     compile_time_stack: &mut Vec<CTStackVal>,
     quantification_stack: &mut Vec<Quantification>,
 ) -> Result<(), Error> {
-    let id = Id(*label, *fresh_id);
+    let id = Id(pos, *fresh_id);
     let r = Region {
         unique: *next_region_is_unique,
         id: RgnId::Var(id),
@@ -1179,22 +1698,16 @@ fn handle_func(
 }
 
 fn handle_ctget(pos: u32, i: &u8, compile_time_stack: &mut Vec<CTStackVal>) -> Result<(), Error> {
-    if compile_time_stack.len() - 1 < *i as usize {
+    let len = compile_time_stack.len();
+    if len == 0 {
         return Err(Error::TypeErrorEmptyCTStack(pos, Op1::CTGet(*i)));
     }
-    match compile_time_stack.get(compile_time_stack.len() - 1 - (*i) as usize) {
-        Some(ctval) => {
-            compile_time_stack.push(ctval.clone());
-            Ok(())
-        }
-        None => {
-            return Err(Error::TypeErrorCTGetOutOfRange(
-                pos,
-                *i,
-                compile_time_stack.len(),
-            ))
-        }
+    if len - 1 < *i as usize {
+        return Err(Error::TypeErrorCTGetOutOfRange(pos, *i, len));
     }
+    let ctval = compile_time_stack[len - 1 - *i as usize].clone();
+    compile_time_stack.push(ctval);
+    Ok(())
 }
 
 fn handle_ptr(pos: u32, op: &Op1, compile_time_stack: &mut Vec<CTStackVal>) -> Result<(), Error> {
@@ -1231,12 +1744,59 @@ pub fn handle_arr(
     }
 }
 
+/// The number type of a type that `cast` can convert to or from.
+fn num_type(t: &Type) -> Option<NumType> {
+    match t {
+        Type::U8 => Some(NumType::U8),
+        Type::I32 => Some(NumType::I32),
+        Type::I64 => Some(NumType::I64),
+        Type::U64 => Some(NumType::U64),
+        Type::F32 => Some(NumType::F32),
+        Type::F64 => Some(NumType::F64),
+        _ => None,
+    }
+}
+
+/// Check an arithmetic op, which takes two numbers of the same type and gives one of that type,
+/// and return the verified op for that type out of the ones for `i32`, `u8`, `i64`, and `u64`,
+/// and the ones for `f32` and `f64` if the op works on floats too.
+fn handle_arith(
+    pos: u32,
+    op: &Op1,
+    stack_type: &mut Vec<Type>,
+    ints: [Op2; 4],
+    floats: Option<[Op2; 2]>,
+) -> Result<Op2, Error> {
+    let [for_i32, for_u8, for_i64, for_u64] = ints;
+    let (t, op2) = match (stack_type.pop(), floats) {
+        (Some(Type::I32), _) => (Type::I32, for_i32),
+        (Some(Type::U8), _) => (Type::U8, for_u8),
+        (Some(Type::I64), _) => (Type::I64, for_i64),
+        (Some(Type::U64), _) => (Type::U64, for_u64),
+        (Some(Type::F32), Some([for_f32, _])) => (Type::F32, for_f32),
+        (Some(Type::F64), Some([_, for_f64])) => (Type::F64, for_f64),
+        (Some(t), _) => return Err(Error::TypeError(pos, *op, Type::I32, t)),
+        (None, _) => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+    };
+    match stack_type.pop() {
+        Some(t2) if type_eq(&t, &t2) => {} // success
+        Some(t2) => return Err(Error::TypeError(pos, *op, t, t2)),
+        None => return Err(Error::TypeErrorEmptyStack(pos, *op)),
+    }
+    stack_type.push(t);
+    Ok(op2)
+}
+
 /// Perform some variable substitutions within a type.
 /// This does not modify the original.
 pub fn substitute_t(typ: &Type, tsubs: &HashMap<Id, Type>, rsubs: &HashMap<RgnId, Region>) -> Type {
     match typ {
         Type::I32 => Type::I32,
         Type::U8 => Type::U8,
+        Type::I64 => Type::I64,
+        Type::U64 => Type::U64,
+        Type::F32 => Type::F32,
+        Type::F64 => Type::F64,
         Type::Handle(r) => Type::Handle(substitute_r(r, rsubs)),
         Type::Tuple(ts) => Type::Tuple(
             ts.iter()
@@ -1263,12 +1823,65 @@ pub fn substitute_t(typ: &Type, tsubs: &HashMap<Id, Type>, rsubs: &HashMap<RgnId
                     captured_rgns.push(*r);
                 }
             }
+            // a type argument substituted into the body can bring unique regions in too,
+            // when the outer quantifiers are applied first
+            let mut rgns_in_tsubs = vec![];
+            for (var, t2) in tsubs {
+                if type_var_in(*var, t) {
+                    regions_in(t2, &mut rgns_in_tsubs);
+                }
+            }
+            captured_rgns.extend(rgns_in_tsubs.into_iter().filter(|r| r.unique));
             Type::ForallRegion(*id, Box::new(substitute_t(t, tsubs, rsubs)), captured_rgns)
         }
         Type::Array(t, r) => Type::Array(
             Box::new(substitute_t(t, tsubs, rsubs)),
             substitute_r(r, rsubs),
         ),
+        Type::Variant(ts) => {
+            Type::Variant(ts.iter().map(|t| substitute_t(t, tsubs, rsubs)).collect())
+        }
+    }
+}
+
+/// Add the regions a type mentions to the list.
+fn regions_in(t: &Type, rgns: &mut Vec<Region>) {
+    match t {
+        Type::I32
+        | Type::U8
+        | Type::I64
+        | Type::U64
+        | Type::F32
+        | Type::F64
+        | Type::Var(..) => {}
+        Type::Handle(r) => rgns.push(*r),
+        Type::Tuple(ts) => ts.iter().for_each(|(_, t)| regions_in(t, rgns)),
+        Type::Ptr(t, r) | Type::Array(t, r) => {
+            rgns.push(*r);
+            regions_in(t, rgns);
+        }
+        Type::Func(ts) | Type::Variant(ts) => ts.iter().for_each(|t| regions_in(t, rgns)),
+        Type::Forall(_, _, t) | Type::Exists(_, _, t) => regions_in(t, rgns),
+        Type::ForallRegion(_, t, captured_rgns) => {
+            rgns.extend(captured_rgns);
+            regions_in(t, rgns);
+        }
+    }
+}
+
+/// Whether a type mentions the type variable.
+fn type_var_in(var: Id, t: &Type) -> bool {
+    match t {
+        Type::I32 | Type::U8 | Type::I64 | Type::U64 | Type::F32 | Type::F64 | Type::Handle(_) => {
+            false
+        }
+        Type::Var(id, _) => *id == var,
+        Type::Tuple(ts) => ts.iter().any(|(_, t)| type_var_in(var, t)),
+        Type::Ptr(t, _) | Type::Array(t, _) => type_var_in(var, t),
+        Type::Func(ts) | Type::Variant(ts) => ts.iter().any(|t| type_var_in(var, t)),
+        Type::Forall(_, _, t) | Type::Exists(_, _, t) | Type::ForallRegion(_, t, _) => {
+            type_var_in(var, t)
+        }
     }
 }
 
@@ -1286,6 +1899,10 @@ pub fn type_eq(type1: &Type, type2: &Type) -> bool {
     match (type1, type2) {
         (Type::I32, Type::I32) => true,
         (Type::U8, Type::U8) => true,
+        (Type::I64, Type::I64) => true,
+        (Type::U64, Type::U64) => true,
+        (Type::F32, Type::F32) => true,
+        (Type::F64, Type::F64) => true,
         (Type::Handle(r1), Type::Handle(r2)) => r1 == r2,
         (Type::Tuple(ts1), Type::Tuple(ts2)) => {
             ts1.len() == ts2.len() && {
@@ -1302,18 +1919,18 @@ pub fn type_eq(type1: &Type, type2: &Type) -> bool {
         (Type::Ptr(t1, r1), Type::Ptr(t2, r2)) => r1 == r2 && type_eq(t1, t2),
         (Type::Var(id1, repr1), Type::Var(id2, repr2)) => id1 == id2 && repr1 == repr2,
         (Type::Func(ts1), Type::Func(ts2)) => {
-            ts1.iter().zip(ts2.iter()).all(|(t1, t2)| type_eq(&t1, &t2))
+            ts1.len() == ts2.len() && ts1.iter().zip(ts2.iter()).all(|(t1, t2)| type_eq(t1, t2))
         }
         (Type::Exists(id1, repr1, t1), Type::Exists(id2, repr2, t2)) => {
             let mut sub = HashMap::new();
-            sub.insert(*id2, Type::Var(*id1, repr1.clone()));
+            sub.insert(*id2, Type::Var(*id1, *repr1));
             let t2_subbed = substitute_t(t2, &sub, &HashMap::new());
             repr1 == repr2 && type_eq(t1, &t2_subbed)
         }
         (Type::Forall(id1, size1, body1), Type::Forall(id2, size2, body2)) => {
             let mut sub = HashMap::new();
-            sub.insert(*id2, Type::Var(*id1, size1.clone()));
-            let body2_subbed = substitute_t(&body2, &sub, &HashMap::new());
+            sub.insert(*id2, Type::Var(*id1, *size1));
+            let body2_subbed = substitute_t(body2, &sub, &HashMap::new());
             size1 == size2 && type_eq(body1, &body2_subbed)
         }
         (
@@ -1322,10 +1939,13 @@ pub fn type_eq(type1: &Type, type2: &Type) -> bool {
         ) => {
             let mut sub = HashMap::new();
             sub.insert(r2.id, *r1);
-            let body2_subbed = substitute_t(&body2, &HashMap::new(), &sub);
+            let body2_subbed = substitute_t(body2, &HashMap::new(), &sub);
             type_eq(body1, &body2_subbed)
         }
         (Type::Array(t1, r1), Type::Array(t2, r2)) => r1 == r2 && type_eq(t1, t2),
+        (Type::Variant(ts1), Type::Variant(ts2)) => {
+            ts1.len() == ts2.len() && ts1.iter().zip(ts2.iter()).all(|(t1, t2)| type_eq(t1, t2))
+        }
         (_, _) => false,
     }
 }
@@ -1345,8 +1965,148 @@ fn setup_verifier(t: &Type) -> Result<(Vec<CTStackVal>, Vec<Type>), Error> {
         Type::Func(param_ts) => {
             let mut param_ts = param_ts.to_vec();
             param_ts.reverse();
-            Ok((vec![], param_ts.into()))
+            Ok((vec![], param_ts))
+        }
+        t => Err(Error::ForwardDeclNotType(t.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(src: &str) -> Result<IRProgram, Error> {
+        crate::load(&crate::assemble::go(src).unwrap())
+    }
+
+    /// A function of a type `t` and then a unique region `r`, taking a `t` and a handle to `r`.
+    const FORALL_THEN_REGION: &str = "func 0; lced
+size 8; all; unique; rgn; ctget 1; ctget 1; handle; func 2; end; end; lced
+";
+
+    /// The type on the top of the stack after the code, found by adding to it.
+    fn type_after(code: &str) -> Type {
+        let src = format!(
+            "{}\n{}\nlit 0; add\nu8_lit 0; halt\n\nu8_lit 3; halt\n",
+            FORALL_THEN_REGION, code
+        );
+        match load(&src) {
+            Err(Error::TypeError(_, Op1::Add, Type::I32, found)) => found,
+            result => panic!("expected a type error at `add`, got {:?}", result.err()),
+        }
+    }
+
+    #[test]
+    fn partial_type_application() {
+        let Type::ForallRegion(r, body, captured_rgns) =
+            type_after("new_rgn 64; ctget 0; handle; global_func 1; app")
+        else {
+            panic!("expected a region quantifier to be left");
+        };
+        let [r_arg] = captured_rgns[..] else {
+            panic!(
+                "expected just the type argument's region to be captured, got {:?}",
+                captured_rgns
+            );
+        };
+        assert!(r.unique && r_arg.unique && r != r_arg);
+        assert_eq!(
+            *body,
+            Type::Func(vec![Type::Handle(r), Type::Handle(r_arg)])
+        );
+    }
+
+    #[test]
+    fn partial_type_then_region_application() {
+        let t =
+            type_after("new_rgn 64; new_rgn 64; ctget 0; ctget 2; handle; global_func 1; app; app");
+        let Type::Func(ts) = t else {
+            panic!("expected a function, got {:?}", t);
+        };
+        let [Type::Handle(r1), Type::Handle(r2)] = ts[..] else {
+            panic!("expected two handles, got {:?}", ts);
+        };
+        assert_ne!(r1, r2);
+    }
+
+    #[test]
+    fn unique_region_in_a_type_argument_is_captured() {
+        let src = format!(
+            "{}\nnew_rgn 64; ctget 0; ctget 0; handle; global_func 1; app; app\nu8_lit 0; halt\n\nu8_lit 3; halt\n",
+            FORALL_THEN_REGION
+        );
+        assert!(matches!(load(&src), Err(Error::RegionAccessError(..))));
+    }
+
+    #[test]
+    fn unused_type_argument_captures_nothing() {
+        let src = "func 0; lced
+size 8; all; unique; rgn; ctget 0; handle; func 1; end; end; lced
+
+new_rgn 64; ctget 0; ctget 0; handle; global_func 1; app; app
+u8_lit 0; halt
+
+u8_lit 3; halt
+";
+        assert!(load(src).is_ok());
+    }
+
+    #[test]
+    fn ops_that_can_fault_need_a_handler() {
+        let src = |body: &str| format!("func 0; lced\nfunc 0; lced\n\n{}\n\nu8_lit 1; halt\n", body);
+        assert!(matches!(
+            load(&src("lit 7; lit 2; div; i32_to_u8; halt")),
+            Err(Error::NoHandler(_, Op1::Div))
+        ));
+        assert!(matches!(
+            load(&src("lit 7; lit 2; div; global_func 1; catch; i32_to_u8; halt")),
+            Err(Error::NoHandler(_, Op1::Div))
+        ));
+        assert!(load(&src("global_func 1; catch; lit 7; lit 2; div; i32_to_u8; halt")).is_ok());
+        // ops that can't fault don't need one
+        assert!(load(&src("lit 7; lit 2; add; i32_to_u8; halt")).is_ok());
+    }
+
+    /// A module with a main function and `n` more, where the ones with the given labels are ill-typed,
+    /// in a couple of different ways.
+    fn many_definitions(n: u32, bad: &[u32]) -> String {
+        let mut src = "func 0; lced\n".repeat(n as usize + 1) + "\nu8_lit 0; halt\n";
+        for label in 1..=n {
+            src += match (bad.contains(&label), label % 2) {
+                (false, _) => "\nu8_lit 0; halt\n",
+                (true, 0) => "\nlit 1; halt\n",
+                (true, _) => "\nu8_lit 1; add; halt\n",
+            };
+        }
+        src
+    }
+
+    #[test]
+    fn definitions_on_threads_come_out_in_order() {
+        let bad = [3, 40, 41, 77, 98];
+        let bytes = crate::assemble::go(&many_definitions(99, &bad)).unwrap();
+        let (data_section, _, _, decs, stmts) = crate::parse::go(&bytes).unwrap();
+        let mut types = HashMap::new();
+        let mut fresh_id = 0;
+        for dec in &decs {
+            let (label, _, t, new_fresh_id) = type_pass(dec, 0, fresh_id).unwrap();
+            types.insert(label, t);
+            fresh_id = new_fresh_id;
+        }
+        let sequential = definition_passes_on(1, data_section.len(), &stmts, &types, fresh_id);
+        let errors: Vec<_> = sequential.iter().enumerate().filter(|(_, result)| result.is_err()).collect();
+        assert_eq!(errors.iter().map(|(i, _)| *i as u32).collect::<Vec<_>>(), bad);
+        for num_threads in [2, 3, 8] {
+            let parallel = definition_passes_on(num_threads, data_section.len(), &stmts, &types, fresh_id);
+            assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential), "on {} threads", num_threads);
         }
-        t => return Err(Error::ForwardDeclNotType(t.clone())),
+        // and through `load_all`, on as many threads as there are cores
+        let Err(diagnostics) = crate::load_all(&bytes) else {
+            panic!("expected the ill-typed definitions to be rejected");
+        };
+        let labels: Vec<_> = diagnostics.iter().map(|d| d.label.unwrap()).collect();
+        assert_eq!(labels, bad);
+        let first_error = errors[0].1.as_ref().unwrap_err();
+        assert_eq!(crate::load(&bytes).err().as_ref(), Some(first_error));
     }
 }
@@ -17,6 +17,16 @@
 
 #define METADATA_OFFSET (sizeof(u64) + sizeof(u64))
 
+// the position of the op being evaluated, for locating runtime errors
+u32 current_op = 0;
+
+void print_source_location() {
+    const char *location = source_location(current_op);
+    if (location != NULL) {
+        printf("At %s\n", location);
+    }
+}
+
 Region *new_region(size_t size) {
     dbg("region size with metadata: %lu\n", sizeof(size_t) + sizeof(size_t) + sizeof(size_t) + size);
     Region *r = malloc(sizeof(size_t) + sizeof(size_t) + sizeof(size_t) + size); // an extra sizeof(size_t) to be safe re: padding
@@ -44,8 +54,7 @@ Pointer alloc_object(Region *r, u64 size) {
             }
         }
         dbg("r->offet: %lu, size: %lu, r->capacity: %lu\n", r->offset, size, r->capacity);
-        printf("Runtime Error! Allocation too big for region!\n");
-        exit(1); // this will jump to an exception handler eventually
+        return (Pointer){0, NULL};
     } else {
         i64 first_generation = 1;
         memcpy(r->data + r->offset, &first_generation, sizeof(first_generation));
@@ -61,7 +70,7 @@ Pointer alloc_object(Region *r, u64 size) {
     }
 }
 
-void check_ptr(Pointer ptr) {
+int check_ptr(Pointer ptr) {
     dbg("check ptr:\n");
     for (int i = 0; i < 20; i++) {
         dbg(" %d",  *(u8*)(ptr.reference - METADATA_OFFSET - 16 + i));
@@ -70,24 +79,25 @@ void check_ptr(Pointer ptr) {
     if (ptr.generation < 0) {
         // negative generation in a pointer means the referent is unfreeable
         // and therefore doesn't have a generation tag in the preceding memory
-        return;
+        return 1;
     }
     i64 g;
     memcpy(&g, ptr.reference - METADATA_OFFSET, sizeof(g));
     dbg("check generation %ld\n", g);
     if (ptr.generation != g) {
         dbg("%ld != %ld\n", ptr.generation, g);
-        printf("Runtime Error! The program is trying to access memory that's already been freed!\n");
-        exit(1); // this will be a jump to exception handler soon
+        return 0;
     }
+    return 1;
 }
 
-void free_object(Pointer ptr) {
-    check_ptr(ptr);
+int free_object(Pointer ptr) {
+    if (!check_ptr(ptr)) return 0;
     i64 g;
     memcpy(&g, ptr.reference - METADATA_OFFSET, sizeof(g));
     g = -g;
     memcpy(ptr.reference - METADATA_OFFSET, &g, sizeof(g));
+    return 1;
 }
 
 #define INSTR_PARAM(t, name) \
@@ -95,12 +105,30 @@ void free_object(Pointer ptr) {
     memcpy(&name, instrs + pc, sizeof(name)); \
     pc += sizeof(name); \
 
+// go back to the last stack chunk if the current one is empty, freeing the empty one.
+// `saved_sp` is kept in the newer chunk, so read it before leaving.
+#define LEAVE_EMPTY_CHUNK \
+    if (sp == 0 && stack->last != NULL) { \
+        struct Stack *empty = stack; \
+        sp = stack->saved_sp; \
+        stack = stack->last; \
+        free(empty); \
+    }
+
 #define POP(t, name) \
     t name; \
-    if (sp == 0 && stack->last != NULL) { stack = stack->last; sp = stack->saved_sp; } \
+    LEAVE_EMPTY_CHUNK \
     sp -= sizeof(name); \
     memcpy(&name, stack->data + sp, sizeof(name));
 
+// pop a value of a size only known at runtime.
+// It's copied out, because popping what's below it can free the chunk it was in.
+#define POP_BYTES(name, size) \
+    u8 name[STACK_CHUNK_SIZE]; \
+    LEAVE_EMPTY_CHUNK \
+    sp -= size; \
+    memcpy(name, stack->data + sp, size);
+
 // push a value onto the stack.
 // no `ensure_size` here because the caller will often know that it's not necessary.
 #define PUSH(t, e) \
@@ -125,6 +153,58 @@ void ensure_size(struct Stack **stack, u32 *sp, size_t size) {
 Handler scheduler[255];
 u8 scheduler_len = 0;
 
+f32 canonical_f32(f32 x) {
+    if (x == x) {
+        return x;
+    }
+    u32 nan = 0x7FC00000;
+    memcpy(&x, &nan, sizeof(x));
+    return x;
+}
+
+f64 canonical_f64(f64 x) {
+    if (x == x) {
+        return x;
+    }
+    u64 nan = 0x7FF8000000000000;
+    memcpy(&x, &nan, sizeof(x));
+    return x;
+}
+
+u64 float_to_int(f64 x, u8 to) {
+    if (x != x) {
+        return 0;
+    }
+    switch (to) {
+    case NUM_U8:
+        return x <= 0 ? 0 : x >= UINT8_MAX ? UINT8_MAX : (u8)x;
+    case NUM_I32:
+        return (u64)(i64)(x <= INT32_MIN ? INT32_MIN : x >= INT32_MAX ? INT32_MAX : (i32)x);
+    case NUM_I64:
+        return (u64)(x <= -9223372036854775808.0 ? INT64_MIN : x >= 9223372036854775808.0 ? INT64_MAX : (i64)x);
+    default:
+        return x <= 0 ? 0 : x >= 18446744073709551616.0 ? UINT64_MAX : (u64)x;
+    }
+}
+
+int add_overflows_i64(i64 b, i64 a) {
+    return a > 0 ? b > INT64_MAX - a : b < INT64_MIN - a;
+}
+
+int mul_overflows_i64(i64 b, i64 a) {
+    if (a == 0 || b == 0) {
+        return 0;
+    }
+    if (a == -1) {
+        return b == INT64_MIN;
+    }
+    if (b == -1) {
+        return a == INT64_MIN;
+    }
+    // the wrapped-around product only divides back if it didn't wrap around
+    return (i64)((u64)b * (u64)a) / a != b;
+}
+
 int post_task(Handler h) {
     if (scheduler_len == 255) return 0;
     scheduler[scheduler_len++] = h;
@@ -138,28 +218,69 @@ Pointer stdin_str_ptr = {0, NULL};
 Handler stdout_handler = {0};
 Handler stderr_handler = {0};
 
-void handle_stdin() {
-    ssize_t bytes;
+// the exception handler of the function being evaluated, with the arguments `catch` gave it.
+// `f` is 0 if the function hasn't installed one, since no function starts at position 0.
+Handler exception_handler = {0};
+
+// Jump to the exception handler of the function being evaluated on a runtime error,
+// with a fresh stack holding just the handler's arguments.
+// If the function hasn't installed a handler, report the error and stop the program instead.
+#define FAULT(...) \
+    { \
+        if (exception_handler.f == 0) { \
+            printf("Runtime Error! " __VA_ARGS__); \
+            printf("\n"); \
+            print_source_location(); \
+            return FAULTED; \
+        } \
+        while (stack->last != NULL) { \
+            struct Stack *chunk = stack; \
+            stack = stack->last; \
+            free(chunk); \
+        } \
+        memcpy(stack->data, exception_handler.param, exception_handler.param_size); \
+        sp = exception_handler.param_size; \
+        pc = exception_handler.f; \
+        exception_handler.f = 0; \
+        break; \
+    }
+
+#define CHECK_PTR(ptr) \
+    if (!check_ptr(ptr)) FAULT("The program is trying to access memory that's already been freed!")
+
+// read the next chunk of the input into the region `read` gave, and post its handler with it,
+// returning how to stop the program, or HALTED to keep going
+u8 handle_stdin() {
+    // flush what the program has written first, in case it's a prompt for this input
+    fflush(stdout);
     char buffer[1024];
-    // Read all available input
-    while ((bytes = read(STDIN_FILENO, buffer, sizeof(buffer))) > 0) {
-        Pointer ptr = alloc_object(stdin_rgn, bytes + sizeof(bytes));
-        memcpy(ptr.reference, &bytes, sizeof(bytes));
-        memcpy(ptr.reference + sizeof(bytes), buffer, bytes);
-        Handler h;
-        memcpy(&h, &stdin_handler, sizeof(h));
-        memcpy(h.param, &ptr, sizeof(ptr));
-        h.param_size = sizeof(ptr);
-        if (post_task(h)) {
-            printf("failed to post stdin handler to scheduler\n");
-            exit(1);
-        }
-        waiting &= 0b11111110;
+    ssize_t bytes = read(STDIN_FILENO, buffer, sizeof(buffer));
+    waiting &= 0b11111110;
+    if (bytes <= 0) {
+        // the end of the input, so the handler never runs
+        return HALTED;
     }
-    
+    size_t len = bytes;
+    Pointer ptr = alloc_object(stdin_rgn, len + sizeof(len));
+    if (ptr.reference == NULL) {
+        // no function is being evaluated, so there's no exception handler to jump to
+        printf("Runtime Error! Allocation too big for region!\n");
+        return FAULTED;
+    }
+    memcpy(ptr.reference, &len, sizeof(len));
+    memcpy(ptr.reference + sizeof(len), buffer, len);
+    Handler h;
+    memcpy(&h, &stdin_handler, sizeof(h));
+    memcpy(h.param, &ptr, sizeof(ptr));
+    h.param_size = sizeof(ptr);
+    if (!post_task(h)) {
+        printf("failed to post stdin handler to scheduler\n");
+        return INTERNAL_ERROR;
+    }
+    return HALTED;
 }
 
-u8 vm_function(u8 instrs[]) {
+u8 vm_function(u8 instrs[], u8 *status) {
     // for (u32 i = 0; i < instrs_len; i++) {
     //     dbg(" %d", instrs[i]);
     // }
@@ -170,40 +291,52 @@ u8 vm_function(u8 instrs[]) {
     u32 pc = sizeof(data_section_size) + data_section_size;
     dbg("pc: %lu\n", pc);
     u32 sp = 0;
+    // a program stopped by an error can leave tasks and handlers behind, so clear out the last run's
+    scheduler_len = 0;
+    waiting = 0;
+    exception_handler.f = 0;
+    *status = 0;
     struct Stack *stack = malloc(sizeof(struct Stack));
-
-    int flags = fcntl(STDIN_FILENO, F_GETFL, 0);
-    fcntl(STDIN_FILENO, F_SETFL, flags | O_NONBLOCK | O_ASYNC);
-    fcntl(STDIN_FILENO, __F_SETOWN, getpid());
-    signal(SIGIO, handle_stdin);
+    if (stack == NULL) {
+        printf("Internal SaberVM Error! Couldn't allocate the stack.\n");
+        return INTERNAL_ERROR;
+    }
+    stack->last = NULL;
 
     Handler on_start = (Handler){.f=pc};
     post_task(on_start); // guaranteed to succeed; no failure check here
     while (1) {
         while (scheduler_len > 0) {
             Handler h = scheduler[--scheduler_len];
-            memcpy(stack->data + sp, &h.param, h.param_size);
-            sp += h.param_size;
+            // every task starts with a fresh stack, with its environment below its argument
+            sp = 0;
             memcpy(stack->data + sp, &h.env, sizeof(h.env));
             sp += sizeof(h.env);
-            u8 err = eval(instrs, h.f, sp + h.param_size + sizeof(h.env), data_section_size, stack);
-            if (err) return err;
+            memcpy(stack->data + sp, &h.param, h.param_size);
+            sp += h.param_size;
+            exception_handler.f = 0;
+            u8 stop = eval(instrs, h.f, sp, data_section_size, stack, status);
+            if (stop != HALTED || *status != 0) return stop;
+        }
+        dbg("waiting: %d\nscheduler_len: %d\n", waiting, scheduler_len);
+        if (waiting & 0b1) {
+            u8 stop = handle_stdin();
+            if (stop != HALTED) return stop;
         }
-        dbg("waiting: %d\nscheduler_len: %d\n", waiting);
-        while (scheduler_len == 0 && waiting) usleep(10000);
         if (!waiting && scheduler_len == 0) {
-            return 0;
+            return HALTED;
         }
     }
 }
 
-u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack) {
+u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack, u8 *status) {
     while (1) {
         // dbg("pc: %d, sp: %d\n", pc, sp);
         // for (u32 i = 0; i < sp; i++) {
         //     dbg(" %d", stack->data[i]);
         // }
         // dbg("\n");
+        current_op = pc;
         switch (instrs[pc]) {
         case 0: {
             dbg("get!\n");
@@ -216,13 +349,13 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             int i = 10;
             while (sp2 < offset + size && i > 0) {
                 dbg(" sp2: %u\n offset: %lu\n size: %lu\n saved sp: %u\n\n", sp2, offset, size, stack2->saved_sp);
-                offset -= sp2 + (STACK_CHUNK_SIZE - stack2->saved_sp);
+                offset -= sp2;
                 sp2 = stack2->saved_sp;
                 stack2 = stack2->last;
                 i--;
             }
             if (i == 0) {
-                return 1;
+                return INTERNAL_ERROR;
             }
             memcpy(stack->data + sp, stack2->data + sp2 - offset - size, size);
             sp += size;
@@ -234,8 +367,10 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             INSTR_PARAM(size_t, offset);
             INSTR_PARAM(size_t, size);
             INSTR_PARAM(size_t, tpl_size);
-            sp -= size;
-            memcpy(stack->data + sp - tpl_size + offset, stack->data + sp, size);
+            POP_BYTES(val, size);
+            // the value can start a new chunk, leaving the tuple at the end of the last one
+            LEAVE_EMPTY_CHUNK
+            memcpy(stack->data + sp - tpl_size + offset, val, size);
             break;
         }
         case 2: {
@@ -243,11 +378,10 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             INSTR_PARAM(size_t, offset);
             INSTR_PARAM(size_t, size);
-            Pointer ptr; 
-            sp -= size + sizeof(ptr);
-            memcpy(&ptr, stack->data + sp, sizeof(ptr));
-            check_ptr(ptr);
-            memcpy(ptr.reference + offset, stack->data + sp + sizeof(ptr), size);
+            POP_BYTES(val, size);
+            POP(Pointer, ptr);
+            CHECK_PTR(ptr);
+            memcpy(ptr.reference + offset, val, size);
             PUSH(Pointer, ptr);
             break;
         }
@@ -256,8 +390,10 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             INSTR_PARAM(size_t, size);
             POP(Region*, handle);
-            ensure_size(&stack, &sp, sizeof(handle));
-            PUSH(Pointer, alloc_object(handle, size));
+            Pointer ptr = alloc_object(handle, size);
+            if (ptr.reference == NULL) FAULT("Allocation too big for region!");
+            ensure_size(&stack, &sp, sizeof(ptr));
+            PUSH(Pointer, ptr);
             break;
         }
         case 4: {
@@ -274,6 +410,7 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             INSTR_PARAM(size_t, offset);
             INSTR_PARAM(size_t, size);
             INSTR_PARAM(size_t, tpl_size);
+            LEAVE_EMPTY_CHUNK
             sp -= tpl_size;
             memcpy(stack->data + sp, stack->data + sp + offset, size);
             sp += size;
@@ -285,7 +422,7 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             INSTR_PARAM(size_t, offset);
             INSTR_PARAM(size_t, size);
             POP(Pointer, ptr);
-            check_ptr(ptr);
+            CHECK_PTR(ptr);
             ensure_size(&stack, &sp, size);
             memcpy(stack->data + sp, ptr.reference + offset, size);
             sp += size;
@@ -295,6 +432,8 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             dbg("call!\n");
             POP(u32, new_pc);
             pc = new_pc;
+            // the handler was the last function's
+            exception_handler.f = 0;
             break;
         }
         case 8: {
@@ -306,7 +445,7 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
                 size_t size = (size_t)instrs + 4 + (size_t)data_section_size - (size_t)ptr.reference;
                 printf("%.*s", (int)size, ptr.reference);
             } else {
-                check_ptr(ptr);
+                CHECK_PTR(ptr);
                 size_t array_len;
                 memcpy(&array_len, ptr.reference, sizeof(array_len));
                 printf("%.*s", (int)array_len, ptr.reference + sizeof(array_len));
@@ -332,7 +471,8 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
         case 11: {
             dbg("halt!\n");
             POP(u8, status_code);
-            return status_code;
+            *status = status_code;
+            return HALTED;
             break;
         }
         case 12: {
@@ -356,7 +496,7 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             INSTR_PARAM(size_t, size);
             POP(Pointer, ptr);
-            check_ptr(ptr);
+            CHECK_PTR(ptr);
             ensure_size(&stack, &sp, size);
             memcpy(stack->data + sp, ptr.reference, size);
             sp += size;
@@ -367,10 +507,12 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             INSTR_PARAM(size_t, elem_size);
             POP(i32, len);
+            if (len < 0) FAULT("Negative array length (%d).", len);
             POP(Region*, r);
             size_t size = elem_size * len;
             dbg("size: %ld\n", sizeof(size) + size);
             Pointer ptr = alloc_object(r, sizeof(size) + size);
+            if (ptr.reference == NULL) FAULT("Allocation too big for region!");
             memcpy(ptr.reference, &size, sizeof(size));
             memset(ptr.reference + sizeof(size), 0, size);
             ensure_size(&stack, &sp, sizeof(ptr));
@@ -382,17 +524,16 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             INSTR_PARAM(size_t, elem_size);
             POP(i32, i);
-            Pointer ptr;
-            memcpy(&ptr, stack->data + sp - elem_size - sizeof(ptr), sizeof(ptr));
+            POP_BYTES(val, elem_size);
+            POP(Pointer, ptr);
+            CHECK_PTR(ptr);
             size_t n = elem_size * i;
             size_t array_len;
             memcpy(&array_len, ptr.reference, sizeof(array_len));
-            if (n + elem_size > array_len) {
-                printf("Runtime Error! Array index out of bounds during an initialization.\n");
-                return 1;
+            if (i < 0 || n + elem_size > array_len) {
+                FAULT("Array index out of bounds during an initialization.");
             }
-            memcpy(ptr.reference + sizeof(array_len) + n, stack->data + sp - elem_size, elem_size);
-            sp -= elem_size + sizeof(ptr);
+            memcpy(ptr.reference + sizeof(array_len) + n, val, elem_size);
             PUSH(Pointer, ptr);
             break;
         }
@@ -403,12 +544,11 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             POP(i32, i);
             size_t n = elem_size * i;
             POP(Pointer, ptr);
-            check_ptr(ptr);
+            CHECK_PTR(ptr);
             size_t array_len;
             memcpy(&array_len, ptr.reference, sizeof(array_len));
-            if (n + elem_size > array_len) {
-                printf("Runtime Error! Array index out of bounds during a projection.\n");
-                return 1;
+            if (i < 0 || n + elem_size > array_len) {
+                FAULT("Array index out of bounds during a projection.");
             }
             ensure_size(&stack, &sp, elem_size);
             memcpy(stack->data + sp, ptr.reference + sizeof(array_len) + n, elem_size);
@@ -420,7 +560,8 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             POP(i32, a);
             POP(i32, b);
-            PUSH(i32, a + b);
+            // wraps around on overflow, which signed C arithmetic doesn't do by itself
+            PUSH(i32, (i32)((u32)b + (u32)a));
             break;
         }
         case 19: {
@@ -428,7 +569,7 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             POP(i32, a);
             POP(i32, b);
-            PUSH(i32, a * b);
+            PUSH(i32, (i32)((u32)b * (u32)a));
             break;
         }
         case 20: {
@@ -436,6 +577,14 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             POP(i32, a);
             POP(i32, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            // the one overflowing division wraps around to itself
+            if (b == INT32_MIN && a == -1) {
+                PUSH(i32, INT32_MIN);
+                break;
+            }
             PUSH(i32, b / a);
             break;
         }
@@ -450,6 +599,7 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             } else {
                 pc = f;
             }
+            exception_handler.f = 0;
             break;
         }
         case 22: {
@@ -471,9 +621,13 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             POP(i32, i);
             size_t n = elem_size * i;
             POP(Pointer, ptr); // frontend ensures this is a data-section pointer, so we don't need to check it.
-            if (n + elem_size > data_section_size) {
-                printf("Runtime Error! Array index out of bounds during a projection from the data section.\n");
-                return 1;
+            u8 *data_section_end = instrs + 4 + data_section_size;
+            if (ptr.reference > data_section_end) {
+                FAULT("Array index out of bounds during a projection from the data section.");
+            }
+            size_t rest_of_data_section = data_section_end - ptr.reference;
+            if (i < 0 || n + elem_size > rest_of_data_section) {
+                FAULT("Array index out of bounds during a projection from the data section.");
             }
             ensure_size(&stack, &sp, elem_size);
             memcpy(stack->data + sp, ptr.reference + n, elem_size);
@@ -487,34 +641,37 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             POP(Pointer, src_array);
             POP(Pointer, dest_array);
             INSTR_PARAM(size_t, elem_size);
-            size_t size;
+            if (n < 0) {
+                FAULT("Negative size (%d) during a copy.", n);
+            }
+            // array lengths are in bytes, and the copy stops at the end of the source
+            size_t size = (size_t)n * elem_size;
             u8 *src_ref;
             if (src_array.generation == -1) {
                 // -1 generation means data section string
-                size_t rest_of_data_section = instrs + 4 + data_section_size - src_array.reference;
-                size = (size_t)n * elem_size;
+                u8 *data_section_end = instrs + 4 + data_section_size;
+                size_t rest_of_data_section = 0;
+                if (src_array.reference <= data_section_end) {
+                    rest_of_data_section = data_section_end - src_array.reference;
+                }
                 if (size > rest_of_data_section) {
-                    size = rest_of_data_section;
+                    size = rest_of_data_section - rest_of_data_section % elem_size;
                 }
                 src_ref = src_array.reference;
             } else {
-                check_ptr(src_array);
+                CHECK_PTR(src_array);
                 size_t array_len;
                 memcpy(&array_len, src_array.reference, sizeof(array_len));
-                size = (size_t)n * elem_size;
-                if ((size_t)n > array_len) {
-                    size = array_len * elem_size;
+                if (size > array_len) {
+                    size = array_len;
                 }
                 src_ref = src_array.reference + sizeof(array_len);
             }
+            CHECK_PTR(dest_array);
             size_t dest_array_len;
             memcpy(&dest_array_len, dest_array.reference, sizeof(dest_array_len));
-            if (n < 0) {
-                printf("Runtime Error! Negative size (%d) during a copy.\n", n);
-                return 1;
-            } else if (dest_array_len < (u32)n) {
-                printf("Runtime Error! Copy (%d) out of bounds for array of size %lu.\n", n, dest_array_len);
-                return 1;
+            if (dest_array_len < (size_t)n * elem_size) {
+                FAULT("Copy (%d) out of bounds for array of size %lu.", n, dest_array_len / elem_size);
             }
             memcpy(dest_array.reference + sizeof(size), src_ref, size);
             PUSH(Pointer, dest_array);
@@ -550,6 +707,9 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             POP(u8, a);
             POP(u8, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
             PUSH(u8, b / a);
             break;
         }
@@ -565,6 +725,14 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             POP(i32, a);
             POP(i32, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            if (a == -1) {
+                // b % -1 is always 0, but INT32_MIN % -1 overflows in C
+                PUSH(i32, 0);
+                break;
+            }
             PUSH(i32, b % a);
             break;
         }
@@ -573,6 +741,9 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             pc++;
             POP(u8, a);
             POP(u8, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
             PUSH(u8, b % a);
             break;
         }
@@ -628,7 +799,7 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
                         post_task(stderr_handler);
                     } else {
                         printf("Internal SaberVM Error! Unknown write mode %d.\n", write_mode);
-                        exit(1);
+                        return INTERNAL_ERROR;
                     }
                     // waiting |= 0b10;
                     break;
@@ -636,9 +807,921 @@ u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack)
             }
             break;
         }
+        case 35: {
+            dbg("catch!\n");
+            pc++;
+            INSTR_PARAM(size_t, size);
+            POP(u32, f);
+            LEAVE_EMPTY_CHUNK
+            sp -= size;
+            memcpy(exception_handler.param, stack->data + sp, size);
+            exception_handler.param_size = size;
+            exception_handler.f = f;
+            break;
+        }
+        case 36: {
+            dbg("checked add i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            i64 result = (i64)b + a;
+            if (result < INT32_MIN || result > INT32_MAX) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(i32, result);
+            break;
+        }
+        case 37: {
+            dbg("checked add u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            u32 result = (u32)b + a;
+            if (result > UINT8_MAX) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(u8, result);
+            break;
+        }
+        case 38: {
+            dbg("checked multiply i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            i64 result = (i64)b * a;
+            if (result < INT32_MIN || result > INT32_MAX) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(i32, result);
+            break;
+        }
+        case 39: {
+            dbg("checked multiply u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            u32 result = (u32)b * a;
+            if (result > UINT8_MAX) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(u8, result);
+            break;
+        }
+        case 40: {
+            dbg("checked divide i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            if (b == INT32_MIN && a == -1) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(i32, b / a);
+            break;
+        }
+        case 41: {
+            dbg("saturating add i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            i64 result = (i64)b + a;
+            if (result < INT32_MIN) {
+                result = INT32_MIN;
+            } else if (result > INT32_MAX) {
+                result = INT32_MAX;
+            }
+            PUSH(i32, result);
+            break;
+        }
+        case 42: {
+            dbg("saturating add u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            u32 result = (u32)b + a;
+            if (result > UINT8_MAX) {
+                result = UINT8_MAX;
+            }
+            PUSH(u8, result);
+            break;
+        }
+        case 43: {
+            dbg("saturating multiply i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            i64 result = (i64)b * a;
+            if (result < INT32_MIN) {
+                result = INT32_MIN;
+            } else if (result > INT32_MAX) {
+                result = INT32_MAX;
+            }
+            PUSH(i32, result);
+            break;
+        }
+        case 44: {
+            dbg("saturating multiply u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            u32 result = (u32)b * a;
+            if (result > UINT8_MAX) {
+                result = UINT8_MAX;
+            }
+            PUSH(u8, result);
+            break;
+        }
+        case 45: {
+            dbg("saturating divide i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            if (b == INT32_MIN && a == -1) {
+                PUSH(i32, INT32_MAX);
+                break;
+            }
+            PUSH(i32, b / a);
+            break;
+        }
+        case 46: {
+            dbg("subtract i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, (i32)((u32)b - (u32)a));
+            break;
+        }
+        case 47: {
+            dbg("subtract u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            PUSH(u8, b - a);
+            break;
+        }
+        case 48: {
+            dbg("negate i32!\n");
+            pc++;
+            POP(i32, a);
+            PUSH(i32, (i32)(0u - (u32)a));
+            break;
+        }
+        case 49: {
+            dbg("negate u8!\n");
+            pc++;
+            POP(u8, a);
+            PUSH(u8, -a);
+            break;
+        }
+        case 50: {
+            dbg("equal i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, b == a);
+            break;
+        }
+        case 51: {
+            dbg("equal u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            ensure_size(&stack, &sp, sizeof(i32));
+            PUSH(i32, b == a);
+            break;
+        }
+        case 52: {
+            dbg("less than i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, b < a);
+            break;
+        }
+        case 53: {
+            dbg("less than u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            ensure_size(&stack, &sp, sizeof(i32));
+            PUSH(i32, b < a);
+            break;
+        }
+        case 54: {
+            dbg("bitwise and i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, b & a);
+            break;
+        }
+        case 55: {
+            dbg("bitwise and u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            PUSH(u8, b & a);
+            break;
+        }
+        case 56: {
+            dbg("bitwise or i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, b | a);
+            break;
+        }
+        case 57: {
+            dbg("bitwise or u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            PUSH(u8, b | a);
+            break;
+        }
+        case 58: {
+            dbg("bitwise xor i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, b ^ a);
+            break;
+        }
+        case 59: {
+            dbg("bitwise xor u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            PUSH(u8, b ^ a);
+            break;
+        }
+        case 60: {
+            dbg("shift left i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, (i32)((u32)b << (a & 31)));
+            break;
+        }
+        case 61: {
+            dbg("shift left u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            PUSH(u8, b << (a & 7));
+            break;
+        }
+        case 62: {
+            dbg("shift right i32!\n");
+            pc++;
+            POP(i32, a);
+            POP(i32, b);
+            PUSH(i32, b >> (a & 31));
+            break;
+        }
+        case 63: {
+            dbg("shift right u8!\n");
+            pc++;
+            POP(u8, a);
+            POP(u8, b);
+            PUSH(u8, b >> (a & 7));
+            break;
+        }
+        case 64: {
+            dbg("i64 literal!\n");
+            pc++;
+            INSTR_PARAM(i64, lit);
+            ensure_size(&stack, &sp, sizeof(lit));
+            PUSH(i64, lit);
+            break;
+        }
+        case 65: {
+            dbg("u64 literal!\n");
+            pc++;
+            INSTR_PARAM(u64, lit);
+            ensure_size(&stack, &sp, sizeof(lit));
+            PUSH(u64, lit);
+            break;
+        }
+        case 66: {
+            dbg("cast!\n");
+            pc++;
+            INSTR_PARAM(u8, from);
+            INSTR_PARAM(u8, to);
+            // integers convert through their 64-bit two's complement bits,
+            // extended by the signedness of the type they come from and then truncated
+            u64 bits = 0;
+            f64 val = 0;
+            switch (from) {
+            case NUM_U8: {
+                POP(u8, a);
+                bits = a;
+                break;
+            }
+            case NUM_I32: {
+                POP(i32, a);
+                bits = (u64)(i64)a;
+                break;
+            }
+            case NUM_F32: {
+                POP(f32, a);
+                val = a;
+                break;
+            }
+            case NUM_F64: {
+                POP(f64, a);
+                val = a;
+                break;
+            }
+            default: {
+                POP(u64, a);
+                bits = a;
+                break;
+            }
+            }
+            int from_float = from == NUM_F32 || from == NUM_F64;
+            int from_signed = from == NUM_I32 || from == NUM_I64;
+            if (from_float) {
+                bits = float_to_int(val, to);
+            }
+            ensure_size(&stack, &sp, sizeof(bits));
+            switch (to) {
+            case NUM_U8:
+                PUSH(u8, (u8)bits);
+                break;
+            case NUM_I32:
+                PUSH(i32, (i32)(u32)bits);
+                break;
+            case NUM_F32:
+                PUSH(f32, canonical_f32(from_float ? (f32)val : from_signed ? (f32)(i64)bits : (f32)bits));
+                break;
+            case NUM_F64:
+                PUSH(f64, canonical_f64(from_float ? val : from_signed ? (f64)(i64)bits : (f64)bits));
+                break;
+            default:
+                PUSH(u64, bits);
+                break;
+            }
+            break;
+        }
+        case 67: {
+            dbg("add i64s!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, (i64)((u64)b + (u64)a));
+            break;
+        }
+        case 68: {
+            dbg("multiply i64s!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, (i64)((u64)b * (u64)a));
+            break;
+        }
+        case 69: {
+            dbg("divide i64s!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            if (b == INT64_MIN && a == -1) {
+                PUSH(i64, INT64_MIN);
+                break;
+            }
+            PUSH(i64, b / a);
+            break;
+        }
+        case 70: {
+            dbg("modulo i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            if (a == -1) {
+                PUSH(i64, 0);
+                break;
+            }
+            PUSH(i64, b % a);
+            break;
+        }
+        case 71: {
+            dbg("checked add i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (add_overflows_i64(b, a)) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(i64, b + a);
+            break;
+        }
+        case 72: {
+            dbg("checked multiply i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (mul_overflows_i64(b, a)) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(i64, b * a);
+            break;
+        }
+        case 73: {
+            dbg("checked divide i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            if (b == INT64_MIN && a == -1) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(i64, b / a);
+            break;
+        }
+        case 74: {
+            dbg("saturating add i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (add_overflows_i64(b, a)) {
+                PUSH(i64, a > 0 ? INT64_MAX : INT64_MIN);
+                break;
+            }
+            PUSH(i64, b + a);
+            break;
+        }
+        case 75: {
+            dbg("saturating multiply i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (mul_overflows_i64(b, a)) {
+                PUSH(i64, (a < 0) == (b < 0) ? INT64_MAX : INT64_MIN);
+                break;
+            }
+            PUSH(i64, b * a);
+            break;
+        }
+        case 76: {
+            dbg("saturating divide i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            if (b == INT64_MIN && a == -1) {
+                PUSH(i64, INT64_MAX);
+                break;
+            }
+            PUSH(i64, b / a);
+            break;
+        }
+        case 77: {
+            dbg("subtract i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, (i64)((u64)b - (u64)a));
+            break;
+        }
+        case 78: {
+            dbg("negate i64!\n");
+            pc++;
+            POP(i64, a);
+            PUSH(i64, (i64)(0 - (u64)a));
+            break;
+        }
+        case 79: {
+            dbg("equal i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i32, b == a);
+            break;
+        }
+        case 80: {
+            dbg("less than i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i32, b < a);
+            break;
+        }
+        case 81: {
+            dbg("bitwise and i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, b & a);
+            break;
+        }
+        case 82: {
+            dbg("bitwise or i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, b | a);
+            break;
+        }
+        case 83: {
+            dbg("bitwise xor i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, b ^ a);
+            break;
+        }
+        case 84: {
+            dbg("shift left i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, (i64)((u64)b << (a & 63)));
+            break;
+        }
+        case 85: {
+            dbg("shift right i64!\n");
+            pc++;
+            POP(i64, a);
+            POP(i64, b);
+            PUSH(i64, b >> (a & 63));
+            break;
+        }
+        case 86: {
+            dbg("add u64s!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b + a);
+            break;
+        }
+        case 87: {
+            dbg("multiply u64s!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b * a);
+            break;
+        }
+        case 88: {
+            dbg("divide u64s!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            PUSH(u64, b / a);
+            break;
+        }
+        case 89: {
+            dbg("modulo u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            if (a == 0) {
+                FAULT("Division by zero!");
+            }
+            PUSH(u64, b % a);
+            break;
+        }
+        case 90: {
+            dbg("checked add u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            if (b > UINT64_MAX - a) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(u64, b + a);
+            break;
+        }
+        case 91: {
+            dbg("checked multiply u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            if (a != 0 && b > UINT64_MAX / a) {
+                FAULT("Arithmetic overflow!");
+            }
+            PUSH(u64, b * a);
+            break;
+        }
+        case 92: {
+            dbg("saturating add u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b > UINT64_MAX - a ? UINT64_MAX : b + a);
+            break;
+        }
+        case 93: {
+            dbg("saturating multiply u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, a != 0 && b > UINT64_MAX / a ? UINT64_MAX : b * a);
+            break;
+        }
+        case 94: {
+            dbg("subtract u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b - a);
+            break;
+        }
+        case 95: {
+            dbg("negate u64!\n");
+            pc++;
+            POP(u64, a);
+            PUSH(u64, 0 - a);
+            break;
+        }
+        case 96: {
+            dbg("equal u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(i32, b == a);
+            break;
+        }
+        case 97: {
+            dbg("less than u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(i32, b < a);
+            break;
+        }
+        case 98: {
+            dbg("bitwise and u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b & a);
+            break;
+        }
+        case 99: {
+            dbg("bitwise or u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b | a);
+            break;
+        }
+        case 100: {
+            dbg("bitwise xor u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b ^ a);
+            break;
+        }
+        case 101: {
+            dbg("shift left u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b << (a & 63));
+            break;
+        }
+        case 102: {
+            dbg("shift right u64!\n");
+            pc++;
+            POP(u64, a);
+            POP(u64, b);
+            PUSH(u64, b >> (a & 63));
+            break;
+        }
+        case 103: {
+            dbg("f32 literal!\n");
+            pc++;
+            INSTR_PARAM(f32, lit);
+            ensure_size(&stack, &sp, sizeof(lit));
+            PUSH(f32, lit);
+            break;
+        }
+        case 104: {
+            dbg("f64 literal!\n");
+            pc++;
+            INSTR_PARAM(f64, lit);
+            ensure_size(&stack, &sp, sizeof(lit));
+            PUSH(f64, lit);
+            break;
+        }
+        case 105: {
+            dbg("add f32!\n");
+            pc++;
+            POP(f32, a);
+            POP(f32, b);
+            PUSH(f32, canonical_f32(b + a));
+            break;
+        }
+        case 106: {
+            dbg("add f64!\n");
+            pc++;
+            POP(f64, a);
+            POP(f64, b);
+            PUSH(f64, canonical_f64(b + a));
+            break;
+        }
+        case 107: {
+            dbg("subtract f32!\n");
+            pc++;
+            POP(f32, a);
+            POP(f32, b);
+            PUSH(f32, canonical_f32(b - a));
+            break;
+        }
+        case 108: {
+            dbg("subtract f64!\n");
+            pc++;
+            POP(f64, a);
+            POP(f64, b);
+            PUSH(f64, canonical_f64(b - a));
+            break;
+        }
+        case 109: {
+            dbg("multiply f32!\n");
+            pc++;
+            POP(f32, a);
+            POP(f32, b);
+            PUSH(f32, canonical_f32(b * a));
+            break;
+        }
+        case 110: {
+            dbg("multiply f64!\n");
+            pc++;
+            POP(f64, a);
+            POP(f64, b);
+            PUSH(f64, canonical_f64(b * a));
+            break;
+        }
+        case 111: {
+            dbg("divide f32!\n");
+            pc++;
+            POP(f32, a);
+            POP(f32, b);
+            PUSH(f32, canonical_f32(b / a));
+            break;
+        }
+        case 112: {
+            dbg("divide f64!\n");
+            pc++;
+            POP(f64, a);
+            POP(f64, b);
+            PUSH(f64, canonical_f64(b / a));
+            break;
+        }
+        case 113: {
+            dbg("negate f32!\n");
+            pc++;
+            POP(f32, a);
+            PUSH(f32, canonical_f32(-a));
+            break;
+        }
+        case 114: {
+            dbg("negate f64!\n");
+            pc++;
+            POP(f64, a);
+            PUSH(f64, canonical_f64(-a));
+            break;
+        }
+        case 115: {
+            dbg("equal f32!\n");
+            pc++;
+            POP(f32, a);
+            POP(f32, b);
+            PUSH(i32, b == a);
+            break;
+        }
+        case 116: {
+            dbg("equal f64!\n");
+            pc++;
+            POP(f64, a);
+            POP(f64, b);
+            PUSH(i32, b == a);
+            break;
+        }
+        case 117: {
+            dbg("less than f32!\n");
+            pc++;
+            POP(f32, a);
+            POP(f32, b);
+            PUSH(i32, b < a);
+            break;
+        }
+        case 118: {
+            dbg("less than f64!\n");
+            pc++;
+            POP(f64, a);
+            POP(f64, b);
+            PUSH(i32, b < a);
+            break;
+        }
+        case 119: {
+            dbg("inject!\n");
+            pc++;
+            INSTR_PARAM(u8, tag);
+            INSTR_PARAM(size_t, size);
+            INSTR_PARAM(size_t, max);
+            LEAVE_EMPTY_CHUNK
+            sp -= size;
+            // the payload moves if the variant starts a new chunk
+            u8 *payload = stack->data + sp;
+            ensure_size(&stack, &sp, max + sizeof(i32));
+            memmove(stack->data + sp, payload, size);
+            memset(stack->data + sp + size, 0, max - size);
+            sp += max;
+            PUSH(i32, tag);
+            break;
+        }
+        case 120: {
+            dbg("case!\n");
+            pc++;
+            INSTR_PARAM(size_t, max);
+            INSTR_PARAM(u8, n);
+            u32 fs[256];
+            for (u8 i = 0; i < n; i++) {
+                POP(u32, f);
+                fs[i] = f;
+            }
+            POP(i32, tag);
+            size_t size;
+            memcpy(&size, instrs + pc + tag * sizeof(size), sizeof(size));
+            // drop the padding, leaving the payload on top
+            sp -= max - size;
+            pc = fs[tag];
+            exception_handler.f = 0;
+            break;
+        }
+        case 121: {
+            dbg("switch i32!\n");
+            pc++;
+            INSTR_PARAM(u8, n);
+            u32 fs[256];
+            for (u8 i = 0; i < n; i++) {
+                POP(u32, f);
+                fs[i] = f;
+            }
+            POP(u32, default_f);
+            POP(i32, x);
+            pc = x >= 0 && x < n ? fs[x] : default_f;
+            exception_handler.f = 0;
+            break;
+        }
+        case 122: {
+            dbg("switch u8!\n");
+            pc++;
+            INSTR_PARAM(u8, n);
+            u32 fs[256];
+            for (u8 i = 0; i < n; i++) {
+                POP(u32, f);
+                fs[i] = f;
+            }
+            POP(u32, default_f);
+            POP(u8, x);
+            pc = x < n ? fs[x] : default_f;
+            exception_handler.f = 0;
+            break;
+        }
+        case 123: {
+            dbg("new filled array!\n");
+            pc++;
+            INSTR_PARAM(size_t, elem_size);
+            POP(i32, len);
+            if (len < 0) FAULT("Negative array length (%d).", len);
+            POP_BYTES(elem, elem_size);
+            POP(Region*, r);
+            size_t size = elem_size * len;
+            Pointer ptr = alloc_object(r, sizeof(size) + size);
+            if (ptr.reference == NULL) FAULT("Allocation too big for region!");
+            memcpy(ptr.reference, &size, sizeof(size));
+            for (i32 i = 0; i < len; i++) {
+                memcpy(ptr.reference + sizeof(size) + elem_size * i, elem, elem_size);
+            }
+            ensure_size(&stack, &sp, sizeof(ptr));
+            PUSH(Pointer, ptr);
+            break;
+        }
         default: {
             printf("internal error!! Unknown IR op %d, please let the SaberVM team know!!", instrs[pc]);
-            return 1;
+            return INTERNAL_ERROR;
         }
         }
     }
@@ -11,26 +11,40 @@
 #include <stdio.h>
 #include <string.h>
 #include <unistd.h>
-#include <fcntl.h>
 #include <sys/select.h>
 #include <sys/file.h>
-#include <signal.h>
 
 typedef uint64_t u64;
 typedef int64_t i64;
 typedef uint32_t u32;
 typedef uint8_t u8;
 typedef int32_t i32;
+typedef float f32;
+typedef double f64;
 
 /*
  * The size of each contiguous chunk of the stack.
  */
 #define STACK_CHUNK_SIZE 4096
 
+/*
+ * The number types that `cast` converts between, numbered as in `NumType` in header.rs.
+ */
+enum NumType {
+    NUM_U8 = 0,
+    NUM_I32 = 1,
+    NUM_I64 = 2,
+    NUM_U64 = 3,
+    NUM_F32 = 4,
+    NUM_F64 = 5,
+};
+
 /*
  * A pointer to an object within a region.
  * The `generation` field is used to detect when a pointer becomes invalid.
  * The `reference` field is the actual pointer.
+ * On the stack a pointer is these 16 bytes, generation first, so a `Ptr(Tuple(...))` value
+ * is what the in-place `proj` and `init` pop, check with `check_ptr`, and read or write through.
  */
 typedef struct {
     i64 generation;
@@ -72,21 +86,41 @@ Region *new_region();
  * Allocate an object in a region 
  * The type system ensures it gets initialized before it is read,
  * so there's no need to initialize the memory.
+ * The reference is NULL if the object doesn't fit in the region.
  */
 Pointer alloc_object(Region *r, u64 size);
 
 /*
- * Crash if the given pointer is no longer valid.
- * This happens if the object it's pointing at has been freed.
- * In the future this will jump to the exception handler instead of crashing.
+ * Check whether the given pointer is still valid, returning 0 if it isn't.
+ * It isn't if the object it's pointing at has been freed,
+ * in which case the op using it jumps to the exception handler.
  */
-void check_ptr(Pointer ptr);
+int check_ptr(Pointer ptr);
 
 /*
- * Free an object within a region. 
+ * Free an object within a region, returning 0 if it was already freed.
  * Generations are used to keep this safe, instead of static analysis.
  */
-void free_object(Pointer ptr);
+int free_object(Pointer ptr);
+
+/*
+ * The given float, or the canonical quiet NaN if it's any NaN,
+ * so that float ops give the same bits on every platform.
+ */
+f32 canonical_f32(f32 x);
+f64 canonical_f64(f64 x);
+
+/*
+ * The bits of a float converted to the given integer type,
+ * rounded toward zero and saturated to the type's range, with NaN converting to 0.
+ */
+u64 float_to_int(f64 x, u8 to);
+
+/*
+ * Whether b + a or b * a overflows an i64, for the checked and saturating ops.
+ */
+int add_overflows_i64(i64 b, i64 a);
+int mul_overflows_i64(i64 b, i64 a);
 
 /*
  * Free a region of memory.
@@ -95,11 +129,33 @@ void free_object(Pointer ptr);
 void free_region(Region *r);
 
 /*
- * The entry point.
+ * Where the op at the given position in the code came from in the source program, as `file:line:col`,
+ * or NULL if the module has no debug info for it.
+ * This is implemented in Rust, from the debug sections of the modules.
+ */
+const char *source_location(u32 pc);
+
+/*
+ * Print where the op being evaluated came from, after printing a runtime error.
+ */
+void print_source_location();
+
+/*
+ * How a run stopped. Only a program that halts has a status;
+ * the others stop after printing the error.
+ */
+#define HALTED 0
+#define FAULTED 1
+#define INTERNAL_ERROR 2
+
+/*
+ * The entry point, returning how the program stopped,
+ * and setting the status it passed to `halt` if it halted.
  */
-extern uint8_t vm_function(u8 instrs[]);
+extern uint8_t vm_function(u8 instrs[], u8 *status);
 
 /*
- * The actual VM implementation.
+ * The actual VM implementation, returning how the task stopped,
+ * and setting the status it passed to `halt` if it halted.
  */
-u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack);
\ No newline at end of file
+u8 eval(u8 instrs[], u32 pc, u32 sp, u32 data_section_size, struct Stack *stack, u8 *status);
\ No newline at end of file
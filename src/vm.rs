@@ -5,88 +5,290 @@
  */
 
 use std::collections::HashMap;
-use std::vec;
 
 use crate::header::*;
-use crate::pretty::Pretty;
+use crate::pretty::{json_string, label_name, Pretty};
+use std::ffi::{c_char, CString};
 use std::fs;
+use std::sync::Mutex;
 
 extern "C" {
-    fn vm_function(bytes: *mut u8) -> u8;
+    fn vm_function(bytes: *mut u8, status: *mut u8) -> u8;
 }
 
-pub fn go(ir_programs: Vec<IRProgram>) -> u8 {
-    let mut str = String::new();
-    let code_size = 4 + ir_programs.iter().map(program_size).sum::<usize>();
-    let mut code = Vec::with_capacity(code_size);
-    let mut import_map = HashMap::new();
-    let mut prog_id = 0;
-    for prog in &ir_programs {
-        for (k,v) in &prog.exports {
-            import_map.insert(*k, (prog_id, *v));
+// How `vm_function` says the program stopped, as in `vm.h`.
+const HALTED: u8 = 0;
+const FAULTED: u8 = 1;
+
+/// Why a program stopped without halting. The runtime system has printed the error already.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// A runtime error, like an index out of bounds, with no exception handler installed to jump to.
+    Fault,
+    /// A bug in SaberVM itself.
+    Internal,
+}
+
+/// Held for the whole of a run on `vm.c`, whose state is global, so that runs from different threads take turns.
+static VM_C: Mutex<()> = Mutex::new(());
+
+/// The source locations of the ops of the running code, by their position in the code.
+/// There's one for the whole process, like `vm.c`'s state, so only one program can run on `vm.c` at a time.
+static SOURCE_LOCATIONS: Mutex<Option<HashMap<u32, CString>>> = Mutex::new(None);
+
+/// Where the op at the given position in the running code came from, for `vm.c` to report runtime errors,
+/// or null if the module it came from has no debug info for it.
+#[no_mangle]
+extern "C" fn source_location(pc: u32) -> *const c_char {
+    let locations = SOURCE_LOCATIONS.lock().unwrap();
+    match locations.as_ref().and_then(|locations| locations.get(&pc)) {
+        // the strings stay put until the next call to `go`
+        Some(location) => location.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Where and in what format `go` should dump the linked IR before running it.
+pub struct Dump {
+    pub target: DumpTarget,
+    pub format: DumpFormat,
+}
+
+pub enum DumpTarget {
+    Stdout,
+    File(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DumpFormat {
+    Text,
+    Json,
+}
+
+/// Which runtime system `go` runs the linked code on.
+/// They behave the same, so either can check the other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// `vm.c`.
+    #[default]
+    C,
+    /// The `interp` module.
+    Rust,
+}
+
+/// Where the pieces of the IR programs end up in the linked code.
+struct Layout {
+    /// The start of each program's part of the shared data section.
+    data_sec_positions: Vec<usize>,
+    /// The position of each function in the code, keyed by program index and label.
+    func_positions: HashMap<(usize, Label), u32>,
+    /// The function each export refers to, as a program index and label.
+    exports: HashMap<(u64, u64), (usize, Label)>,
+    code_size: usize,
+}
+
+impl Layout {
+    fn new(ir_programs: &[IRProgram]) -> Self {
+        let mut exports = HashMap::new();
+        let mut data_sec_positions = vec![];
+        let mut data_section_size = 0;
+        for (prog_id, prog) in ir_programs.iter().enumerate() {
+            for (k, v) in &prog.exports {
+                exports.insert(*k, (prog_id, *v));
+            }
+            data_sec_positions.push(data_section_size);
+            data_section_size += prog.data_section.len();
+        }
+        let mut func_positions = HashMap::new();
+        let mut pos = (4 + data_section_size) as u32;
+        for (prog_id, prog) in ir_programs.iter().enumerate() {
+            for Stmt2::Func(l, _, ops, _) in &prog.funcs {
+                func_positions.insert((prog_id, *l), pos);
+                pos += ops.iter().map(op_len).sum::<usize>() as u32;
+            }
+        }
+        let code_size = 4 + ir_programs.iter().map(program_size).sum::<usize>();
+        assert!(pos == code_size as u32);
+        Layout {
+            data_sec_positions,
+            func_positions,
+            exports,
+            code_size,
         }
-        prog_id += 1;
     }
-    let mut data_sec_positions = HashMap::new();
-    code.extend(vec![0, 0, 0, 0]);
-    let mut pos: u32 = 4;
-    prog_id = 0;
-    for prog in &ir_programs {
-        data_sec_positions.insert(prog_id, pos - 4);
-        let data_section_len = prog.data_section.len();
-        code.extend(prog.data_section.iter());
-        pos += data_section_len as u32;
-        prog_id += 1;
+
+    /// Resolve the label of a `global_func` op in the given program to the function it refers to after linking,
+    /// as a program index, label, and position in the code.
+    fn resolve(&self, ir_programs: &[IRProgram], prog_id: usize, label: Label) -> (usize, Label, u32) {
+        let (prog_id, label) = match ir_programs[prog_id].imports.get(&label) {
+            Some(id) => *self.exports.get(id).unwrap(),
+            None => (prog_id, label),
+        };
+        (prog_id, label, *self.func_positions.get(&(prog_id, label)).unwrap())
     }
-    code[0..4].copy_from_slice(&(pos - 4).to_le_bytes());
-    let mut func_positions = HashMap::new();
-    let mut pos2 = pos;
-    prog_id = 0;
-    for prog in &ir_programs {
-        for Stmt2::Func(l, _, ops) in &prog.funcs {
-            func_positions.insert((prog_id, *l), pos2);
-            pos2 += ops.iter().map(op_len).sum::<usize>() as u32;
-        }
-        prog_id += 1;
+}
+
+/// Link the IR programs into the byte array that `vm.c` runs:
+/// the length of the shared data section, the data section, and then the code of every function.
+fn link(ir_programs: &[IRProgram], layout: &Layout) -> Vec<u8> {
+    let mut code = Vec::with_capacity(layout.code_size);
+    let data_section_size: usize = ir_programs.iter().map(|prog| prog.data_section.len()).sum();
+    code.extend((data_section_size as u32).to_le_bytes());
+    for prog in ir_programs {
+        code.extend(prog.data_section.iter());
     }
-    assert!(pos2 == code_size as u32);
-    assert!(pos < pos2);
-    prog_id = 0;
-    for prog in &ir_programs {
-        let mut label_map = HashMap::new();
-        let mut pos2 = pos;
-        for Stmt2::Func(label, _, ops) in &prog.funcs {
-            label_map.insert(*label, pos2);
-            pos2 += ops.iter().map(op_len).sum::<usize>() as u32;
-        }
-        for Stmt2::Func(l, t, ops) in &prog.funcs {
-            str += &("function ".to_string() + &l.to_string() + ": " + &t.pretty() + "\n");
+    for (prog_id, prog) in ir_programs.iter().enumerate() {
+        for Stmt2::Func(_, _, ops, _) in &prog.funcs {
             for op in ops {
-                str += &(pos.to_string() + " " + &op.pretty() + "\n");
                 match op {
                     Op2::GlobalFunc(label) => {
-                        let func_pos = match label_map.get(label) {
-                            Some(pos) => *pos,
-                            None => {
-                                let func_id = import_map.get(prog.imports.get(label).unwrap()).unwrap();
-                                *func_positions.get(func_id).unwrap()
-                            },
-                        };
-                        code.extend(op_to_bytes(&Op2::GlobalFunc(func_pos as u32)));
+                        let (_, _, func_pos) = layout.resolve(ir_programs, prog_id, *label);
+                        code.extend(op_to_bytes(&Op2::GlobalFunc(func_pos)));
                     }
                     Op2::Data(data_pos) => {
-                        let data_sec_pos = data_sec_positions.get(&prog_id).unwrap();
-                        code.extend(op_to_bytes(&Op2::Data(*data_sec_pos as usize + *data_pos)));
+                        let data_sec_pos = layout.data_sec_positions[prog_id];
+                        code.extend(op_to_bytes(&Op2::Data(data_sec_pos + *data_pos)));
                     }
                     _ => code.extend(op_to_bytes(op)),
                 }
+            }
+        }
+    }
+    code
+}
+
+/// The source locations of the ops in the linked code, from the debug sections of the programs.
+fn source_locations(ir_programs: &[IRProgram], layout: &Layout) -> HashMap<u32, CString> {
+    let mut locations = HashMap::new();
+    for (prog_id, prog) in ir_programs.iter().enumerate() {
+        let Some(debug_info) = &prog.debug_info else {
+            continue;
+        };
+        for Stmt2::Func(l, _, ops, op_positions) in &prog.funcs {
+            let mut pos = *layout.func_positions.get(&(prog_id, *l)).unwrap();
+            for (op, op_pos) in ops.iter().zip(op_positions) {
+                if let Some(location) = debug_info.location(*op_pos).and_then(|loc| CString::new(loc).ok()) {
+                    locations.insert(pos, location);
+                }
+                pos += op_len(op) as u32;
+            }
+        }
+    }
+    locations
+}
+
+/// Print the linked IR, with the type of every function and the position of every op in the code.
+/// `global_func` and `data` ops are shown with what they refer to after linking.
+pub fn listing(ir_programs: &[IRProgram], format: DumpFormat) -> String {
+    let layout = Layout::new(ir_programs);
+    let mut str = String::new();
+    let mut json_programs = vec![];
+    let no_names = Names::default();
+    let names_of = |prog_id: usize| ir_programs[prog_id].names.as_ref().unwrap_or(&no_names);
+    for (prog_id, prog) in ir_programs.iter().enumerate() {
+        let data_sec_pos = layout.data_sec_positions[prog_id];
+        str += &format!(
+            "program {}: data section at {}, {} bytes\n",
+            prog_id,
+            data_sec_pos,
+            prog.data_section.len()
+        );
+        let mut json_funcs = vec![];
+        let names = names_of(prog_id);
+        for Stmt2::Func(l, t, ops, _) in &prog.funcs {
+            let mut pos = *layout.func_positions.get(&(prog_id, *l)).unwrap();
+            str += &("function ".to_string() + &label_name(*l, names) + ": " + &t.pretty_named(names) + "\n");
+            let mut json_ops = vec![];
+            for op in ops {
+                let (resolved, json_resolved) = match op {
+                    Op2::GlobalFunc(label) => {
+                        let (prog_id2, label2, func_pos) = layout.resolve(ir_programs, prog_id, *label);
+                        let names2 = names_of(prog_id2);
+                        (
+                            format!(
+                                " -> function {} of program {}, at {}",
+                                label_name(label2, names2),
+                                prog_id2,
+                                func_pos
+                            ),
+                            format!(
+                                ",\"target\":{{\"program\":{},\"label\":{},\"position\":{}}}",
+                                prog_id2, label2, func_pos
+                            ),
+                        )
+                    }
+                    Op2::Data(data_pos) => (
+                        format!(" -> data section byte {}", data_sec_pos + data_pos),
+                        format!(",\"data_offset\":{}", data_sec_pos + data_pos),
+                    ),
+                    _ => (String::new(), String::new()),
+                };
+                str += &(pos.to_string() + " " + &op.pretty() + &resolved + "\n");
+                json_ops.push(format!(
+                    "{{\"position\":{},\"op\":{}{}}}",
+                    pos,
+                    json_string(&op.pretty()),
+                    json_resolved
+                ));
                 pos += op_len(op) as u32;
             }
+            let json_name = match names.func(*l) {
+                Some(name) => format!(",\"name\":{}", json_string(name)),
+                None => String::new(),
+            };
+            json_funcs.push(format!(
+                "{{\"label\":{}{},\"type\":{},\"position\":{},\"ops\":[{}]}}",
+                l,
+                json_name,
+                json_string(&t.pretty_named(names)),
+                layout.func_positions.get(&(prog_id, *l)).unwrap(),
+                json_ops.join(",")
+            ));
         }
-        prog_id += 1;
+        json_programs.push(format!(
+            "{{\"data_section\":{{\"offset\":{},\"size\":{}}},\"functions\":[{}]}}",
+            data_sec_pos,
+            prog.data_section.len(),
+            json_funcs.join(",")
+        ));
+    }
+    match format {
+        DumpFormat::Text => str,
+        DumpFormat::Json => format!("{{\"programs\":[{}]}}\n", json_programs.join(",")),
+    }
+}
+
+/// Link and run the IR programs, dumping the linked IR first if asked to,
+/// and return the status the program passed to `halt`.
+/// The first function of the first program is the entry point.
+pub fn go(ir_programs: &[IRProgram], dump: Option<&Dump>, backend: Backend) -> Result<u8, RuntimeError> {
+    let layout = Layout::new(ir_programs);
+    let mut code = link(ir_programs, &layout);
+    if let Some(dump) = dump {
+        let str = listing(ir_programs, dump.format);
+        match &dump.target {
+            DumpTarget::Stdout => print!("{}", str),
+            DumpTarget::File(path) => {
+                if let Err(e) = fs::write(path, str) {
+                    eprintln!("Could not write the IR dump to {}: {}", path, e);
+                }
+            }
+        }
+    }
+    let locations = source_locations(ir_programs, &layout);
+    match backend {
+        Backend::C => {
+            let _turn = VM_C.lock().unwrap();
+            *SOURCE_LOCATIONS.lock().unwrap() = Some(locations);
+            let mut status = 0;
+            match unsafe { vm_function(code.as_mut_ptr(), &mut status) } {
+                HALTED => Ok(status),
+                FAULTED => Err(RuntimeError::Fault),
+                _ => Err(RuntimeError::Internal),
+            }
+        }
+        Backend::Rust => crate::interp::go(&code, &locations),
     }
-    let _ = fs::write("t.txt", str);
-    unsafe { vm_function(code.as_mut_ptr()) }
 }
 
 fn op_to_bytes(op: &Op2) -> Vec<u8> {
@@ -153,6 +355,106 @@ fn op_to_bytes(op: &Op2) -> Vec<u8> {
         Op2::I32ToU8 => vec![32],
         Op2::Read(c) => vec![33, *c],
         Op2::Write(c) => vec![34, *c],
+        Op2::Catch(size) => [vec![35], size.to_le_bytes().to_vec()].concat(),
+        Op2::CheckedAddI32 => vec![36],
+        Op2::CheckedAddU8 => vec![37],
+        Op2::CheckedMulI32 => vec![38],
+        Op2::CheckedMulU8 => vec![39],
+        Op2::CheckedDivI32 => vec![40],
+        Op2::SaturatingAddI32 => vec![41],
+        Op2::SaturatingAddU8 => vec![42],
+        Op2::SaturatingMulI32 => vec![43],
+        Op2::SaturatingMulU8 => vec![44],
+        Op2::SaturatingDivI32 => vec![45],
+        Op2::SubI32 => vec![46],
+        Op2::SubU8 => vec![47],
+        Op2::NegI32 => vec![48],
+        Op2::NegU8 => vec![49],
+        Op2::EqI32 => vec![50],
+        Op2::EqU8 => vec![51],
+        Op2::LtI32 => vec![52],
+        Op2::LtU8 => vec![53],
+        Op2::AndI32 => vec![54],
+        Op2::AndU8 => vec![55],
+        Op2::OrI32 => vec![56],
+        Op2::OrU8 => vec![57],
+        Op2::XorI32 => vec![58],
+        Op2::XorU8 => vec![59],
+        Op2::ShlI32 => vec![60],
+        Op2::ShlU8 => vec![61],
+        Op2::ShrI32 => vec![62],
+        Op2::ShrU8 => vec![63],
+        Op2::I64Lit(n) => [vec![64], n.to_le_bytes().to_vec()].concat(),
+        Op2::U64Lit(n) => [vec![65], n.to_le_bytes().to_vec()].concat(),
+        Op2::Cast(from, to) => vec![66, *from as u8, *to as u8],
+        Op2::F32Lit(bits) => [vec![103], bits.to_le_bytes().to_vec()].concat(),
+        Op2::F64Lit(bits) => [vec![104], bits.to_le_bytes().to_vec()].concat(),
+        Op2::AddF32 => vec![105],
+        Op2::AddF64 => vec![106],
+        Op2::SubF32 => vec![107],
+        Op2::SubF64 => vec![108],
+        Op2::MulF32 => vec![109],
+        Op2::MulF64 => vec![110],
+        Op2::DivF32 => vec![111],
+        Op2::DivF64 => vec![112],
+        Op2::NegF32 => vec![113],
+        Op2::NegF64 => vec![114],
+        Op2::EqF32 => vec![115],
+        Op2::EqF64 => vec![116],
+        Op2::LtF32 => vec![117],
+        Op2::LtF64 => vec![118],
+        Op2::AddI64 => vec![67],
+        Op2::MulI64 => vec![68],
+        Op2::DivI64 => vec![69],
+        Op2::ModuloI64 => vec![70],
+        Op2::CheckedAddI64 => vec![71],
+        Op2::CheckedMulI64 => vec![72],
+        Op2::CheckedDivI64 => vec![73],
+        Op2::SaturatingAddI64 => vec![74],
+        Op2::SaturatingMulI64 => vec![75],
+        Op2::SaturatingDivI64 => vec![76],
+        Op2::SubI64 => vec![77],
+        Op2::NegI64 => vec![78],
+        Op2::EqI64 => vec![79],
+        Op2::LtI64 => vec![80],
+        Op2::AndI64 => vec![81],
+        Op2::OrI64 => vec![82],
+        Op2::XorI64 => vec![83],
+        Op2::ShlI64 => vec![84],
+        Op2::ShrI64 => vec![85],
+        Op2::AddU64 => vec![86],
+        Op2::MulU64 => vec![87],
+        Op2::DivU64 => vec![88],
+        Op2::ModuloU64 => vec![89],
+        Op2::CheckedAddU64 => vec![90],
+        Op2::CheckedMulU64 => vec![91],
+        Op2::SaturatingAddU64 => vec![92],
+        Op2::SaturatingMulU64 => vec![93],
+        Op2::SubU64 => vec![94],
+        Op2::NegU64 => vec![95],
+        Op2::EqU64 => vec![96],
+        Op2::LtU64 => vec![97],
+        Op2::AndU64 => vec![98],
+        Op2::OrU64 => vec![99],
+        Op2::XorU64 => vec![100],
+        Op2::ShlU64 => vec![101],
+        Op2::ShrU64 => vec![102],
+        Op2::Inj(i, size, max) => [
+            vec![119, *i],
+            size.to_le_bytes().to_vec(),
+            max.to_le_bytes().to_vec(),
+        ]
+        .concat(),
+        Op2::SwitchI32(n) => vec![121, *n],
+        Op2::SwitchU8(n) => vec![122, *n],
+        Op2::NewArrFill(size) => [vec![123], size.to_le_bytes().to_vec()].concat(),
+        Op2::Case(max, sizes) => [
+            vec![120],
+            max.to_le_bytes().to_vec(),
+            vec![sizes.len() as u8],
+            sizes.iter().flat_map(|size| size.to_le_bytes()).collect(),
+        ]
+        .concat(),
     }
 }
 
@@ -194,12 +496,101 @@ fn op_len(op: &Op2) -> usize {
         Op2::I32ToU8 => 1,
         Op2::Read(_) => 1 + 1,
         Op2::Write(_) => 1 + 1,
+        Op2::Catch(_) => 1 + 8,
+        Op2::CheckedAddI32 => 1,
+        Op2::CheckedAddU8 => 1,
+        Op2::CheckedMulI32 => 1,
+        Op2::CheckedMulU8 => 1,
+        Op2::CheckedDivI32 => 1,
+        Op2::SaturatingAddI32 => 1,
+        Op2::SaturatingAddU8 => 1,
+        Op2::SaturatingMulI32 => 1,
+        Op2::SaturatingMulU8 => 1,
+        Op2::SaturatingDivI32 => 1,
+        Op2::SubI32 => 1,
+        Op2::SubU8 => 1,
+        Op2::NegI32 => 1,
+        Op2::NegU8 => 1,
+        Op2::EqI32 => 1,
+        Op2::EqU8 => 1,
+        Op2::LtI32 => 1,
+        Op2::LtU8 => 1,
+        Op2::AndI32 => 1,
+        Op2::AndU8 => 1,
+        Op2::OrI32 => 1,
+        Op2::OrU8 => 1,
+        Op2::XorI32 => 1,
+        Op2::XorU8 => 1,
+        Op2::ShlI32 => 1,
+        Op2::ShlU8 => 1,
+        Op2::ShrI32 => 1,
+        Op2::ShrU8 => 1,
+        Op2::I64Lit(_) => 1 + 8,
+        Op2::U64Lit(_) => 1 + 8,
+        Op2::Cast(_, _) => 1 + 2,
+        Op2::F32Lit(_) => 1 + 4,
+        Op2::F64Lit(_) => 1 + 8,
+        Op2::AddF32 => 1,
+        Op2::AddF64 => 1,
+        Op2::SubF32 => 1,
+        Op2::SubF64 => 1,
+        Op2::MulF32 => 1,
+        Op2::MulF64 => 1,
+        Op2::DivF32 => 1,
+        Op2::DivF64 => 1,
+        Op2::NegF32 => 1,
+        Op2::NegF64 => 1,
+        Op2::EqF32 => 1,
+        Op2::EqF64 => 1,
+        Op2::LtF32 => 1,
+        Op2::LtF64 => 1,
+        Op2::AddI64 => 1,
+        Op2::MulI64 => 1,
+        Op2::DivI64 => 1,
+        Op2::ModuloI64 => 1,
+        Op2::CheckedAddI64 => 1,
+        Op2::CheckedMulI64 => 1,
+        Op2::CheckedDivI64 => 1,
+        Op2::SaturatingAddI64 => 1,
+        Op2::SaturatingMulI64 => 1,
+        Op2::SaturatingDivI64 => 1,
+        Op2::SubI64 => 1,
+        Op2::NegI64 => 1,
+        Op2::EqI64 => 1,
+        Op2::LtI64 => 1,
+        Op2::AndI64 => 1,
+        Op2::OrI64 => 1,
+        Op2::XorI64 => 1,
+        Op2::ShlI64 => 1,
+        Op2::ShrI64 => 1,
+        Op2::AddU64 => 1,
+        Op2::MulU64 => 1,
+        Op2::DivU64 => 1,
+        Op2::ModuloU64 => 1,
+        Op2::CheckedAddU64 => 1,
+        Op2::CheckedMulU64 => 1,
+        Op2::SaturatingAddU64 => 1,
+        Op2::SaturatingMulU64 => 1,
+        Op2::SubU64 => 1,
+        Op2::NegU64 => 1,
+        Op2::EqU64 => 1,
+        Op2::LtU64 => 1,
+        Op2::AndU64 => 1,
+        Op2::OrU64 => 1,
+        Op2::XorU64 => 1,
+        Op2::ShlU64 => 1,
+        Op2::ShrU64 => 1,
+        Op2::Inj(_, _, _) => 1 + 1 + 8 + 8,
+        Op2::Case(_, sizes) => 1 + 8 + 1 + 8 * sizes.len(),
+        Op2::SwitchI32(_) => 1 + 1,
+        Op2::SwitchU8(_) => 1 + 1,
+        Op2::NewArrFill(_) => 1 + 8,
     }
 }
 
 fn program_size(prog: &IRProgram) -> usize {
     let mut out = prog.data_section.len();
-    for Stmt2::Func(_, _, ops) in &prog.funcs {
+    for Stmt2::Func(_, _, ops, _) in &prog.funcs {
         out += ops.iter().map(op_len).sum::<usize>();
     }
     out
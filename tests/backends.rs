@@ -0,0 +1,741 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Run programs on both runtime systems, vm.c and the Rust interpreter,
+//! and check that they print the same things and exit with the same status, like `difftest.sh`.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What a run printed to stdout, and the status it exited with.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    stdout: String,
+    status: i32,
+}
+
+fn run_on(backend: &str, paths: &[PathBuf], input: &[u8]) -> Outcome {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sabervm"))
+        .args(["run", "--backend", backend])
+        .args(paths)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    Outcome {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        status: output.status.code().unwrap(),
+    }
+}
+
+/// Run the modules on both backends with the given input, checking they agree, and return what they did.
+fn run_both_with_input(paths: &[PathBuf], input: &[u8]) -> Outcome {
+    let c = run_on("c", paths, input);
+    let rust = run_on("rust", paths, input);
+    assert_eq!(c, rust, "the backends disagree");
+    c
+}
+
+fn run_both(paths: &[PathBuf]) -> Outcome {
+    run_both_with_input(paths, b"")
+}
+
+/// Assemble a module into a file of its own, named after the test.
+fn assemble(name: &str, src: &str) -> PathBuf {
+    let bytes = sabervm::assemble::go(src).unwrap();
+    let path = std::env::temp_dir().join(format!(
+        "sabervm-backends-{}-{}.svm",
+        std::process::id(),
+        name
+    ));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn run_src_with_input(name: &str, src: &str, input: &[u8]) -> Outcome {
+    let path = assemble(name, src);
+    let outcome = run_both_with_input(&[path.clone()], input);
+    std::fs::remove_file(path).ok();
+    outcome
+}
+
+fn run_src(name: &str, src: &str) -> Outcome {
+    run_src_with_input(name, src, b"")
+}
+
+fn repo_file(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+}
+
+/// The status the programs of `halts_with` exit with when an op faults.
+const FAULTED: i32 = 250;
+
+/// Run the ops with a handler installed that halts with `FAULTED`, and return the status.
+fn halts_with(name: &str, ops: &str) -> i32 {
+    let src = format!(
+        "func 0; lced\nfunc 0; lced\n\nglobal_func 1; catch\n{}\n\nu8_lit {}; halt\n",
+        ops, FAULTED
+    );
+    let outcome = run_src(name, &src);
+    assert_eq!(outcome.stdout, "", "{}", ops);
+    outcome.status
+}
+
+/// Check that each case's ops leave the same value as its literal, comparing the two with `eq`.
+fn check_values(name: &str, cases: &[(impl AsRef<str>, &str)]) {
+    for (ops, literal) in cases {
+        let ops = ops.as_ref();
+        let program = format!("{}\n{}; eq; i32_to_u8; halt", ops, literal);
+        assert_eq!(halts_with(name, &program), 1, "{} isn't {}", ops, literal);
+    }
+}
+
+/// Check that each program of ops faults, and its handler runs.
+fn check_faults(name: &str, cases: &[impl AsRef<str>]) {
+    for ops in cases {
+        let ops = ops.as_ref();
+        let program = format!("{}\nu8_lit 0; halt", ops);
+        assert_eq!(halts_with(name, &program), FAULTED, "{} doesn't fault", ops);
+    }
+}
+
+#[test]
+fn arithmetic_wraps() {
+    let src = "func 0; lced
+func 0; lced
+
+global_func 1; catch
+lit 2000000000; lit 2000000000; add; lit -7; div; lit 1000; modulo; lit 3; mul
+u8_lit 200; u8_lit 100; add; u8_to_i32; add; i32_to_u8; halt
+
+u8_lit 1; halt
+";
+    assert_eq!(run_src("arithmetic_wraps", src).status, 87);
+}
+
+#[test]
+fn integer_ops() {
+    check_values(
+        "integer_ops",
+        &[
+            ("lit 5; lit 7; sub", "lit -2"),
+            ("lit -2147483648; lit 1; sub", "lit 2147483647"),
+            ("lit 5; neg", "lit -5"),
+            ("lit -2147483648; neg", "lit -2147483648"),
+            ("lit 3; lit 3; eq", "lit 1"),
+            ("lit 3; lit -3; eq", "lit 0"),
+            ("lit -1; lit 1; lt", "lit 1"),
+            ("lit 1; lit -1; lt", "lit 0"),
+            ("lit 1; lit 1; lt", "lit 0"),
+            ("lit 12; lit 10; and", "lit 8"),
+            ("lit 12; lit 10; or", "lit 14"),
+            ("lit 12; lit 10; xor", "lit 6"),
+            ("lit -1; lit 255; and", "lit 255"),
+            // shift amounts are taken modulo the width
+            ("lit 1; lit 31; shl", "lit -2147483648"),
+            ("lit 1; lit 32; shl", "lit 1"),
+            ("lit 1; lit 33; shl", "lit 2"),
+            ("lit -8; lit 1; shr", "lit -4"),
+            ("lit -8; lit 35; shr", "lit -1"),
+            ("lit 8; lit 35; shr", "lit 1"),
+            ("u8_lit 5; u8_lit 7; sub", "u8_lit 254"),
+            ("u8_lit 5; neg", "u8_lit 251"),
+            ("u8_lit 0; neg", "u8_lit 0"),
+            ("u8_lit 9; u8_lit 9; eq", "lit 1"),
+            // `u8`s compare unsigned
+            ("u8_lit 100; u8_lit 200; lt", "lit 1"),
+            ("u8_lit 200; u8_lit 100; lt", "lit 0"),
+            ("u8_lit 240; u8_lit 60; and", "u8_lit 48"),
+            ("u8_lit 240; u8_lit 60; or", "u8_lit 252"),
+            ("u8_lit 240; u8_lit 60; xor", "u8_lit 204"),
+            ("u8_lit 1; u8_lit 7; shl", "u8_lit 128"),
+            ("u8_lit 1; u8_lit 8; shl", "u8_lit 1"),
+            ("u8_lit 3; u8_lit 9; shl", "u8_lit 6"),
+            // and shift in zeros
+            ("u8_lit 128; u8_lit 1; shr", "u8_lit 64"),
+            ("u8_lit 128; u8_lit 9; shr", "u8_lit 64"),
+        ],
+    );
+}
+
+#[test]
+fn division_and_overflow() {
+    check_faults(
+        "division_and_overflow",
+        &[
+            "lit 7; lit 0; div",
+            "lit 7; lit 0; modulo",
+            "u8_lit 7; u8_lit 0; div",
+            "u8_lit 7; u8_lit 0; modulo",
+            "lit 7; lit 0; checked_div",
+            "lit -2147483648; lit -1; checked_div",
+            "lit 7; lit 0; saturating_div",
+            "u8_lit 7; u8_lit 0; checked_div",
+            "lit 2147483647; lit 1; checked_add",
+            "lit -2147483648; lit -1; checked_add",
+            "lit 65536; lit 32768; checked_mul",
+            "lit -2147483648; lit -1; checked_mul",
+            "u8_lit 255; u8_lit 1; checked_add",
+            "u8_lit 16; u8_lit 16; checked_mul",
+        ],
+    );
+    check_values(
+        "division_and_overflow",
+        &[
+            // plain division wraps, and rounds toward zero
+            ("lit -2147483648; lit -1; div", "lit -2147483648"),
+            ("lit -2147483648; lit -1; modulo", "lit 0"),
+            ("lit -7; lit 2; div", "lit -3"),
+            ("lit -7; lit 2; modulo", "lit -1"),
+            ("u8_lit 255; u8_lit 2; div", "u8_lit 127"),
+            ("lit 2147483647; lit 1; add", "lit -2147483648"),
+            ("lit 65536; lit 32768; mul", "lit -2147483648"),
+            ("u8_lit 16; u8_lit 16; mul", "u8_lit 0"),
+            // right at the boundaries, the checked ops don't fault
+            ("lit 2147483646; lit 1; checked_add", "lit 2147483647"),
+            ("lit -2147483647; lit -1; checked_add", "lit -2147483648"),
+            ("lit 65536; lit -32768; checked_mul", "lit -2147483648"),
+            ("lit -2147483647; lit -1; checked_div", "lit 2147483647"),
+            ("u8_lit 254; u8_lit 1; checked_add", "u8_lit 255"),
+            ("u8_lit 15; u8_lit 17; checked_mul", "u8_lit 255"),
+            ("lit 2147483647; lit 1; saturating_add", "lit 2147483647"),
+            ("lit -2147483648; lit -1; saturating_add", "lit -2147483648"),
+            ("lit 65536; lit 32768; saturating_mul", "lit 2147483647"),
+            ("lit 65536; lit -32769; saturating_mul", "lit -2147483648"),
+            ("lit -2147483648; lit -1; saturating_mul", "lit 2147483647"),
+            ("lit -2147483648; lit -1; saturating_div", "lit 2147483647"),
+            ("u8_lit 255; u8_lit 1; saturating_add", "u8_lit 255"),
+            ("u8_lit 16; u8_lit 16; saturating_mul", "u8_lit 255"),
+        ],
+    );
+}
+
+#[test]
+fn wide_integers_and_casts() {
+    check_values(
+        "wide_integers_and_casts",
+        &[
+            (
+                "i64_lit 9223372036854775807; i64_lit 1; add",
+                "i64_lit -9223372036854775808",
+            ),
+            ("i64_lit 5; i64_lit 7; sub", "i64_lit -2"),
+            ("i64_lit 3000000000; i64_lit 3; mul", "i64_lit 9000000000"),
+            ("i64_lit -7; i64_lit 2; div", "i64_lit -3"),
+            ("i64_lit -7; i64_lit 2; modulo", "i64_lit -1"),
+            (
+                "i64_lit -9223372036854775808; i64_lit -1; div",
+                "i64_lit -9223372036854775808",
+            ),
+            (
+                "i64_lit -9223372036854775808; neg",
+                "i64_lit -9223372036854775808",
+            ),
+            ("i64_lit -1; i64_lit 1; lt", "lit 1"),
+            ("i64_lit 1; i64_lit 63; shl", "i64_lit -9223372036854775808"),
+            ("i64_lit 1; i64_lit 64; shl", "i64_lit 1"),
+            ("i64_lit -8; i64_lit 65; shr", "i64_lit -4"),
+            (
+                "i64_lit 9223372036854775807; i64_lit 1; saturating_add",
+                "i64_lit 9223372036854775807",
+            ),
+            (
+                "i64_lit 4294967296; i64_lit -4294967296; saturating_mul",
+                "i64_lit -9223372036854775808",
+            ),
+            (
+                "i64_lit -9223372036854775808; i64_lit -1; saturating_div",
+                "i64_lit 9223372036854775807",
+            ),
+            ("u64_lit 0; u64_lit 1; sub", "u64_lit 18446744073709551615"),
+            (
+                "u64_lit 18446744073709551615; u64_lit 2; div",
+                "u64_lit 9223372036854775807",
+            ),
+            (
+                "u64_lit 18446744073709551615; u64_lit 10; modulo",
+                "u64_lit 5",
+            ),
+            // `u64`s compare unsigned and shift in zeros
+            ("u64_lit 1; u64_lit 18446744073709551615; lt", "lit 1"),
+            ("u64_lit 18446744073709551615; u64_lit 63; shr", "u64_lit 1"),
+            (
+                "u64_lit 18446744073709551615; u64_lit 1; saturating_add",
+                "u64_lit 18446744073709551615",
+            ),
+            (
+                "u64_lit 4294967296; u64_lit 4294967296; saturating_mul",
+                "u64_lit 18446744073709551615",
+            ),
+            // casts sign-extend from signed types, zero-extend from unsigned ones, and truncate
+            ("lit -1; i64; cast", "i64_lit -1"),
+            ("lit -1; u64; cast", "u64_lit 18446744073709551615"),
+            ("u8_lit 200; i64; cast", "i64_lit 200"),
+            ("u8_lit 200; i32; cast", "lit 200"),
+            ("lit 300; u8; cast", "u8_lit 44"),
+            ("lit -1; u8; cast", "u8_lit 255"),
+            ("i64_lit 4294967297; i32; cast", "lit 1"),
+            ("i64_lit -1; u8; cast", "u8_lit 255"),
+            ("i64_lit -1; u64; cast", "u64_lit 18446744073709551615"),
+            ("i64_lit 2147483648; i32; cast", "lit -2147483648"),
+            ("u64_lit 18446744073709551615; i32; cast", "lit -1"),
+            ("u64_lit 18446744073709551615; i64; cast", "i64_lit -1"),
+            ("u64_lit 4294967552; u8; cast", "u8_lit 0"),
+        ],
+    );
+    check_faults(
+        "wide_integers_and_casts",
+        &[
+            "i64_lit 7; i64_lit 0; div",
+            "u64_lit 7; u64_lit 0; modulo",
+            "i64_lit 9223372036854775807; i64_lit 1; checked_add",
+            "i64_lit 4294967296; i64_lit 2147483648; checked_mul",
+            "i64_lit -9223372036854775808; i64_lit -1; checked_div",
+            "u64_lit 18446744073709551615; u64_lit 1; checked_add",
+            "u64_lit 4294967296; u64_lit 4294967296; checked_mul",
+        ],
+    );
+}
+
+#[test]
+fn floats() {
+    check_values(
+        "floats",
+        &[
+            ("f64_lit 1.5; f64_lit 2.25; add", "f64_lit 3.75"),
+            ("f64_lit 1.5; f64_lit 2.25; sub", "f64_lit -0.75"),
+            ("f64_lit 1.5; f64_lit 2.25; mul", "f64_lit 3.375"),
+            ("f32_lit 1.0; f32_lit 3.0; div", "f32_lit 0.33333334"),
+            ("f32_lit 2.5; neg", "f32_lit -2.5"),
+            // division by zero doesn't fault, and keeps the sign of the zero
+            ("f64_lit 1.0; f64_lit 0.0; div", "f64_lit inf"),
+            ("f64_lit 1.0; f64_lit 0.0; neg; div", "f64_lit -inf"),
+            ("f32_lit -1.0; f32_lit -0.0; div", "f32_lit inf"),
+            ("f64_lit -0.0; f64_lit 0.0; eq", "lit 1"),
+            // NaN is unordered, and equal to nothing, not even itself
+            ("f64_lit NaN; f64_lit NaN; eq", "lit 0"),
+            (
+                "f64_lit 0.0; f64_lit 0.0; div; f64_lit 0.0; f64_lit 0.0; div; eq",
+                "lit 0",
+            ),
+            (
+                "f64_lit 0x7ff8000000000001; f64_lit 0x7ff8000000000001; eq",
+                "lit 0",
+            ),
+            ("f32_lit NaN; f32_lit 1.0; lt", "lit 0"),
+            ("f32_lit 1.0; f32_lit NaN; lt", "lit 0"),
+            ("f64_lit -inf; f64_lit 1.0; lt", "lit 1"),
+            // casts to integers round toward zero and saturate, with NaN going to 0
+            ("f64_lit -2.9; i32; cast", "lit -2"),
+            ("f64_lit NaN; i32; cast", "lit 0"),
+            ("f32_lit NaN; u64; cast", "u64_lit 0"),
+            ("f64_lit inf; i32; cast", "lit 2147483647"),
+            ("f64_lit -inf; i32; cast", "lit -2147483648"),
+            ("f64_lit 3000000000.0; i32; cast", "lit 2147483647"),
+            ("f64_lit 300.7; u8; cast", "u8_lit 255"),
+            ("f64_lit -5.0; u8; cast", "u8_lit 0"),
+            ("f64_lit 1e300; i64; cast", "i64_lit 9223372036854775807"),
+            ("f64_lit -1e300; i64; cast", "i64_lit -9223372036854775808"),
+            ("f64_lit -1.0; u64; cast", "u64_lit 0"),
+            ("f32_lit inf; u64; cast", "u64_lit 18446744073709551615"),
+            // and casts to floats round to the nearest one
+            ("lit 16777217; f32; cast", "f32_lit 16777216.0"),
+            ("i64_lit -3; f64; cast", "f64_lit -3.0"),
+            ("u8_lit 255; f32; cast", "f32_lit 255.0"),
+            (
+                "u64_lit 18446744073709551615; f64; cast",
+                "f64_lit 18446744073709551616.0",
+            ),
+            ("f64_lit 1e40; f32; cast", "f32_lit inf"),
+            ("f32_lit 0.1; f64; cast", "f64_lit 0.10000000149011612"),
+            ("f64_lit NaN; f32; cast; f32; cast; i32; cast", "lit 0"),
+        ],
+    );
+}
+
+#[test]
+fn floats_in_the_data_section() {
+    // 1.5 as an f32, 1.5 as an f64, an f32 infinity, and an f64 NaN with a payload
+    let src = "data_section { 0 0 192 63 0 0 0 0 0 0 248 63 0 0 128 127 1 0 0 0 0 0 248 127 }
+
+func 0; lced
+func 0; lced
+
+global_func 1; catch
+f32; tuple 1; data 0; deref; proj 0; f32_lit 1.5; eq
+f64; tuple 1; data 4; deref; proj 0; f64_lit 1.5; eq; add
+f32; tuple 1; data 12; deref; proj 0; f32_lit inf; eq; add
+f64; tuple 1; data 16; deref; proj 0; f64; tuple 1; data 16; deref; proj 0; eq; add
+f64; tuple 1; data 16; deref; proj 0; i32; cast; add
+i32_to_u8; halt
+
+u8_lit 1; halt
+";
+    let outcome = run_src("floats_in_the_data_section", src);
+    assert_eq!(outcome.stdout, "");
+    assert_eq!(outcome.status, 3);
+}
+
+#[test]
+fn case_calls_the_continuation_for_the_component() {
+    // the i32s below the variant put it at every offset around the end of the first 4096-byte stack chunk
+    for filler in 1012..1028 {
+        for (component, expected) in [("u8_lit 7; inj 0", 8), ("i64_lit 300; inj 1", 46)] {
+            let src = format!(
+                "func 0; lced
+u8; func 1; lced
+i64; func 1; lced
+
+{}i64; u8; variant 2; {}
+global_func 2; global_func 1; case 2
+
+u8_lit 1; add; halt
+
+u8; cast; u8_lit 2; add; halt
+",
+                "lit 0; ".repeat(filler),
+                component
+            );
+            let outcome = run_src("case_calls_the_continuation_for_the_component", &src);
+            assert_eq!(outcome.stdout, "");
+            assert_eq!(outcome.status, expected, "{} on {} i32s", component, filler);
+        }
+    }
+}
+
+#[test]
+fn switch_calls_the_function_for_the_index() {
+    for (index, expected) in [
+        ("lit 0", 10),
+        ("lit 1", 11),
+        ("lit 2", 9),
+        ("lit -1", 9),
+        ("lit -2147483648", 9),
+        ("u8_lit 1", 11),
+        ("u8_lit 200", 9),
+    ] {
+        // the default function is the deepest, and the function for index 0 is on top
+        let src = format!(
+            "func 0; lced
+func 0; lced
+func 0; lced
+func 0; lced
+
+{}; global_func 1; global_func 3; global_func 2; switch 2
+
+u8_lit 9; halt
+
+u8_lit 10; halt
+
+u8_lit 11; halt
+",
+            index
+        );
+        let outcome = run_src("switch_calls_the_function_for_the_index", &src);
+        assert_eq!(outcome.status, expected, "{}", index);
+    }
+}
+
+#[test]
+fn arrays_and_the_data_section() {
+    let src = "data_section { \"hello\" 5 0 0 0 }
+
+func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 256
+get 0; ctget 0; u8; arr; lit 4; malloc
+u8_lit 7; lit 2; arr_mut
+lit 2; arr_proj
+data_sec; u8; arr; data 0; lit 1; arr_proj; add
+i32; tuple 1; data 5; deref; proj 0; i32_to_u8; add; halt
+
+u8_lit 1; halt
+";
+    assert_eq!(run_src("arrays_and_the_data_section", src).status, 113);
+}
+
+#[test]
+fn arrays_of_any_element_type() {
+    // a region, then an array of eight u8s with three set, then another of all zeros on top
+    let u8s = "new_rgn 256
+get 0; ctget 0; u8; arr; lit 8; malloc
+u8_lit 5; lit 0; arr_mut; u8_lit 6; lit 1; arr_mut; u8_lit 7; lit 7; arr_mut
+get 1; ctget 0; u8; arr; lit 8; malloc";
+    check_values(
+        "arrays_of_any_element_type",
+        &[
+            (format!("{}\nget 1; lit 7; arr_proj", u8s), "u8_lit 7"),
+            (format!("{}\nlit 7; arr_proj", u8s), "u8_lit 0"),
+            (
+                format!("{}\nget 1; lit 2; copy_n; lit 1; arr_proj", u8s),
+                "u8_lit 6",
+            ),
+            (
+                format!("{}\nget 1; lit 2; copy_n; lit 2; arr_proj", u8s),
+                "u8_lit 0",
+            ),
+        ],
+    );
+    check_faults(
+        "arrays_of_any_element_type",
+        &[
+            format!("{}\nlit 8; arr_proj", u8s),
+            format!("{}\nlit -1; arr_proj", u8s),
+            format!("{}\nu8_lit 1; lit 8; arr_mut", u8s),
+            format!("{}\nget 1; lit 9; copy_n", u8s),
+            format!("{}\nget 1; lit -1; copy_n", u8s),
+        ],
+    );
+
+    // an array of four zeroed (i32, u8) tuples with the last one set
+    let tuples = "new_rgn 256
+get 0; ctget 0; u8; i32; tuple 2; arr; lit 4; malloc
+u8; i32; tuple 2; malloc; lit 300; init 0; u8_lit 9; init 1; lit 3; arr_mut";
+    check_values(
+        "arrays_of_any_element_type",
+        &[
+            (format!("{}\nlit 3; arr_proj; proj 0", tuples), "lit 300"),
+            (format!("{}\nlit 3; arr_proj; proj 1", tuples), "u8_lit 9"),
+            (format!("{}\nlit 2; arr_proj; proj 0", tuples), "lit 0"),
+            (
+                format!(
+                    "{}\nget 1; ctget 0; u8; i32; tuple 2; arr; lit 4; malloc; get 1; lit 4; copy_n\nlit 3; arr_proj; proj 1",
+                    tuples
+                ),
+                "u8_lit 9",
+            ),
+        ],
+    );
+    check_faults(
+        "arrays_of_any_element_type",
+        &[format!("{}\nlit 4; arr_proj", tuples)],
+    );
+
+    // pointers to (i32, u8) tuples can't be zero, so the array starts out filled with the first one,
+    // and the second one goes at index 1
+    let pointer = "ctget 0; u8; i32; tuple 2; ptr";
+    let pointers = format!(
+        "new_rgn 256
+get 0; {pointer}; malloc; lit 1; init 0; u8_lit 2; init 1
+get 1; get 1; ctget 0; {pointer}; arr; lit 4; malloc
+get 2; {pointer}; malloc; lit 10; init 0; u8_lit 20; init 1
+get 1; get 1; lit 1; arr_mut"
+    );
+    check_values(
+        "arrays_of_any_element_type",
+        &[
+            (format!("{}\nlit 0; arr_proj; proj 0", pointers), "lit 1"),
+            (format!("{}\nlit 1; arr_proj; proj 1", pointers), "u8_lit 20"),
+            (format!("{}\nlit 3; arr_proj; proj 0", pointers), "lit 1"),
+            (
+                format!(
+                    "{}\nget 4; get 2; ctget 0; {pointer}; arr; lit 4; malloc; get 1; lit 2; copy_n\nlit 0; arr_proj; proj 0",
+                    pointers
+                ),
+                "lit 1",
+            ),
+            (
+                format!(
+                    "{}\nget 4; get 2; ctget 0; {pointer}; arr; lit 4; malloc; get 1; lit 2; copy_n\nlit 2; arr_proj; proj 0",
+                    pointers
+                ),
+                "lit 10",
+            ),
+        ],
+    );
+    check_faults(
+        "arrays_of_any_element_type",
+        &[
+            format!("{}\nlit 4; arr_proj", pointers),
+            format!("{}\nlit -1; arr_proj", pointers),
+            format!("{}\nget 1; lit 4; arr_mut", pointers),
+            format!("{}\nget 0; lit 5; copy_n", pointers),
+            format!(
+                "{}\nget 4; get 4; ctget 0; {pointer}; arr; lit -1; malloc",
+                pointers
+            ),
+        ],
+    );
+}
+
+#[test]
+fn arrays_of_closures() {
+    // a closure is a function and the environment it takes, of a type hidden in an existential
+    let closure_type = "size 4; some; ctget 0; ctget 0; func 1; tuple 2; end";
+    let closure = |env: i32| {
+        format!(
+            "{closure_type}; i32; i32; func 1; tuple 2; malloc; global_func 2; init 0; lit {env}; init 1; i32; pack"
+        )
+    };
+    for (index, expected) in [(0, 10), (2, 14), (3, 10)] {
+        // an array filled with the closure of 5, with the closure of 7 at index 2,
+        // copied over the first three elements of another array filled with the closure of 5
+        let src = format!(
+            "func 0; lced
+func 0; lced
+i32; func 1; lced
+
+global_func 1; catch
+new_rgn 256
+{}
+get 1; get 1; ctget 0; {closure_type}; arr; lit 4; malloc
+{}; lit 2; arr_mut
+get 2; get 2; ctget 0; {closure_type}; arr; lit 4; malloc
+get 1; lit 3; copy_n
+lit {index}; arr_proj
+unpack; get 0; proj 1; get 1; proj 0; call
+
+u8_lit 1; halt
+
+lit 2; mul; i32_to_u8; halt
+",
+            closure(5),
+            closure(7)
+        );
+        let outcome = run_src("arrays_of_closures", &src);
+        assert_eq!(outcome.stdout, "");
+        assert_eq!(outcome.status, expected, "index {}", index);
+    }
+}
+
+#[test]
+fn values_across_stack_chunks() {
+    // more than one 4096-byte chunk of i32s, summed back down
+    let src = format!(
+        "func 0; lced\n\n{}{}i32_to_u8; halt\n",
+        "lit 1; ".repeat(1100),
+        "add; ".repeat(1099)
+    );
+    assert_eq!(
+        run_src("values_across_stack_chunks", &src).status,
+        (1100 % 256) as i32
+    );
+}
+
+#[test]
+fn faults_outside_any_function_stop_the_program() {
+    // the input doesn't fit in the region `read` gets, and there's no function to catch that
+    let src = "func 0; lced
+rgn; ctget 0; tuple 0; ptr; ctget 1; u8; arr; func 2; end; lced
+func 0; lced
+
+global_func 2; catch
+new_rgn 16
+ctget 0; tuple 0; ptr
+ctget 1; tuple 0; ptr
+ctget 2; u8; arr
+func 2
+tuple 2
+malloc
+get 1
+ctget 0; tuple 0; ptr; malloc
+init 1
+ctget 0; global_func 1; app
+init 0
+size 16; some
+ctget 0
+ctget 0
+ctget 3; u8; arr
+func 2
+tuple 2
+end
+ctget 1; tuple 0; ptr
+pack
+get 1
+read 0
+u8_lit 0; halt
+
+global_func 2; catch
+lit 1; arr_proj; halt
+
+u8_lit 9; halt
+";
+    let outcome = run_src_with_input("faults_outside_any_function_stop_the_program", src, b"AZ\n");
+    assert_eq!(
+        outcome.stdout,
+        "Runtime Error! Allocation too big for region!\n"
+    );
+    assert_eq!(outcome.status, 1);
+}
+
+#[test]
+fn faults_jump_to_the_handler() {
+    let src = "func 0; lced
+i32; func 1; lced
+
+lit 42; global_func 1; catch
+lit 7; lit 0; div; i32_to_u8; halt
+
+i32_to_u8; halt
+";
+    let outcome = run_src("faults_jump_to_the_handler", src);
+    assert_eq!(outcome.stdout, "");
+    assert_eq!(outcome.status, 42);
+}
+
+#[test]
+fn faults_in_a_handler_jump_to_its_own_handler() {
+    let src = "func 0; lced
+func 0; lced
+func 0; lced
+
+global_func 1; catch
+lit 7; lit 0; div; i32_to_u8; halt
+
+global_func 2; catch
+lit 7; lit 0; modulo; i32_to_u8; halt
+
+u8_lit 43; halt
+";
+    let outcome = run_src("faults_in_a_handler_jump_to_its_own_handler", src);
+    assert_eq!(outcome.stdout, "");
+    assert_eq!(outcome.status, 43);
+}
+
+#[test]
+fn repo_programs() {
+    assert_eq!(run_both(&[repo_file("tuples.svm")]).status, 0);
+    let outcome = run_both(&[repo_file("bin.svm"), repo_file("runtime.svm")]);
+    assert_eq!(outcome.stdout, "7\n");
+    assert_eq!(outcome.status, 0);
+}
+
+fn load_src(src: &str) -> sabervm::Linked {
+    let bytes = sabervm::assemble::go(src).unwrap();
+    sabervm::link(vec![sabervm::load(&bytes).unwrap()]).unwrap()
+}
+
+#[test]
+fn runs_from_several_threads() {
+    // every run allocates an array and writes to it, which overlapping runs on vm.c's global state would mix up
+    let src = "func 0; lced
+func 0; lced
+
+global_func 1; catch
+new_rgn 4096
+get 0; ctget 0; u8; arr; lit 1000; malloc
+u8_lit 3; lit 999; arr_mut
+lit 999; arr_proj
+halt
+
+u8_lit 1; halt
+";
+    let linked = load_src(src);
+    std::thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                for _ in 0..50 {
+                    assert_eq!(sabervm::run(&linked, &sabervm::Config::default()), Ok(3));
+                }
+            });
+        }
+    });
+}